@@ -7,7 +7,7 @@ use std::fs;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiPatchResult {
     pub description: String,
-    pub target: String, // "basic", "groups", "current"
+    pub target: String, // "basic", "groups", "local", "current"
     pub operations: Vec<serde_json::Value>,
 }
 
@@ -34,6 +34,7 @@ fn build_system_prompt(
 **配置文件说明**:
 - `basic.yml`: 基础配置，包含通用代理组、规则。修改此文件会影响未来的所有配置生成。
 - `groups.yml`: 额外的代理组定义。
+- `rules.local.yml`: 个人规则覆盖层，结构为 `prepend`（插入到 basic.yml 规则之前）、`append`（插入到最终 MATCH 规则之前）和 `overrides`（按规则目标分组重定向，如 `谷歌服务: AI-专用`）。更新内置模板时不会被覆盖。
 - `current.yaml`: 当前正在运行的最终配置（临时修改）。
 
 **可用的 proxy-groups**:
@@ -44,14 +45,14 @@ fn build_system_prompt(
 **输出格式要求（必须是有效的 JSON）**:
 {{
   "description": "简短描述这次修改做了什么",
-  "target": "basic", // 或 "groups", "current"
+  "target": "local", // 或 "basic", "groups", "current"
   "operations": [
-    {{"op": "add", "path": "/rules/0", "value": "DOMAIN-SUFFIX,google.com,Taiwan"}}
+    {{"op": "add", "path": "/prepend/0", "value": "DOMAIN-SUFFIX,google.com,Taiwan"}}
   ]
 }}
 
 **注意**:
-1. 如果是修改规则(rules)，通常建议修改 `basic.yml`。
+1. 如果是添加或调整个人规则，优先修改 `rules.local.yml`（target 为 "local"）；只有修改基础规则本身时才修改 `basic.yml`。
 2. 如果是添加新的代理组，通常修改 `groups.yml`。
 3. 如果是临时调整某个组的节点选择，或者用户明确要求立即生效不影响基础配置，修改 `current.yaml`。
 4. path 使用 JSON Pointer 格式。
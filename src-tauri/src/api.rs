@@ -0,0 +1,412 @@
+use crate::rules::{self, RuleSource};
+use crate::server::AppState;
+use crate::subscription::{self, SubscriptionPatch};
+use crate::types::{ConfigVersion, Subscription};
+use crate::version::{self, VersionDiff};
+use axum::{
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tracing::warn;
+
+/// JSON error body: `{"error": "..."}`
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn not_found(message: String) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message,
+        }
+    }
+
+    fn bad_request(message: String) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+
+    fn conflict(message: String) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            message,
+        }
+    }
+}
+
+/// Errors of the operation itself are server faults; client mistakes are caught before
+/// calling it (`find_*`, `validate_*`) and get a 4xx status
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("{:#}", e),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            Json(serde_json::json!({ "error": self.message })),
+        )
+            .into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Management API, nested under `/api`. Every route requires an admin token.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/subscriptions",
+            get(list_subscriptions).post(add_subscription),
+        )
+        .route(
+            "/subscriptions/:id",
+            get(get_subscription)
+                .put(update_subscription)
+                .delete(delete_subscription),
+        )
+        .route("/subscriptions/:id/refresh", post(refresh_subscription))
+        .route("/refresh", post(refresh_all))
+        .route("/merge", post(merge))
+        .route("/versions", get(list_versions).post(create_snapshot))
+        .route("/versions/:id", get(get_version).delete(delete_version))
+        .route("/versions/:id/diff", get(diff_version))
+        .route("/versions/:id/rollback", post(rollback_version))
+        .route("/rules", get(list_rule_sources).post(add_rule_source))
+        .route(
+            "/rules/:id",
+            get(get_rule_source)
+                .put(update_rule_source)
+                .delete(delete_rule_source),
+        )
+        .route("/rules/:id/refresh", post(refresh_rule_source))
+        .route_layer(middleware::from_fn(require_admin))
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+async fn require_admin(
+    Query(query): Query<TokenQuery>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = match crate::storage::load_hangar_config() {
+        Ok(c) => c,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    let presented = crate::auth::extract_token(authorization, query.token.as_deref());
+
+    match crate::auth::authorize_admin(&config, presented, chrono::Utc::now().timestamp()) {
+        Ok(_) => next.run(request).await,
+        Err(reason) => {
+            let addr = remote
+                .map(|ConnectInfo(addr)| addr.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            warn!(
+                "🚫 拒绝 {} {} 请求: {} (来自 {})",
+                request.method(),
+                request.uri().path(),
+                reason,
+                addr
+            );
+            ApiError {
+                status: StatusCode::UNAUTHORIZED,
+                message: format!("Unauthorized: {}", reason),
+            }
+            .into_response()
+        }
+    }
+}
+
+fn find_subscription(key: &str) -> Result<Subscription, ApiError> {
+    let subs = crate::storage::load_subscriptions()?;
+    subscription::find_subscription(&subs, key)
+        .map(|idx| subs[idx].clone())
+        .ok_or_else(|| ApiError::not_found(format!("Subscription not found: '{}'", key)))
+}
+
+fn find_rule_source(key: &str) -> Result<RuleSource, ApiError> {
+    rules::resolve_rule_source(key).map_err(|e| ApiError::not_found(e.to_string()))
+}
+
+fn find_version(key: &str) -> Result<String, ApiError> {
+    version::resolve_version_id(key).map_err(|e| ApiError::not_found(e.to_string()))
+}
+
+fn require_non_empty(field: &str, value: &str) -> Result<(), ApiError> {
+    if value.trim().is_empty() {
+        return Err(ApiError::bad_request(format!(
+            "'{}' must not be empty",
+            field
+        )));
+    }
+    Ok(())
+}
+
+fn require_http_url(url: &str) -> Result<(), ApiError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        _ => Err(ApiError::bad_request(format!(
+            "Invalid URL, expected http(s): '{}'",
+            url
+        ))),
+    }
+}
+
+/// Names are lookup keys, so they must stay unique (`except` is the subscription being renamed)
+fn validate_subscription_name(name: &str, except: Option<&str>) -> Result<(), ApiError> {
+    require_non_empty("name", name)?;
+    let subs = crate::storage::load_subscriptions()?;
+    if subs
+        .iter()
+        .any(|s| s.name == name && Some(s.id.as_str()) != except)
+    {
+        return Err(ApiError::conflict(format!(
+            "A subscription named '{}' already exists",
+            name
+        )));
+    }
+    Ok(())
+}
+
+// Subscriptions
+
+async fn list_subscriptions() -> ApiResult<Vec<Subscription>> {
+    Ok(Json(crate::storage::load_subscriptions()?))
+}
+
+async fn get_subscription(Path(id): Path<String>) -> ApiResult<Subscription> {
+    Ok(Json(find_subscription(&id)?))
+}
+
+#[derive(Deserialize)]
+struct NewSubscription {
+    name: String,
+    url: String,
+}
+
+async fn add_subscription(
+    Json(body): Json<NewSubscription>,
+) -> Result<(StatusCode, Json<Subscription>), ApiError> {
+    validate_subscription_name(&body.name, None)?;
+    require_http_url(&body.url)?;
+    let sub = subscription::add_subscription(body.name, body.url)?;
+    Ok((StatusCode::CREATED, Json(sub)))
+}
+
+async fn update_subscription(
+    Path(id): Path<String>,
+    Json(patch): Json<SubscriptionPatch>,
+) -> ApiResult<Subscription> {
+    let sub = find_subscription(&id)?;
+    if let Some(name) = &patch.name {
+        validate_subscription_name(name, Some(&sub.id))?;
+    }
+    if let Some(url) = &patch.url {
+        require_http_url(url)?;
+    }
+    Ok(Json(subscription::update_subscription(&sub.id, patch)?))
+}
+
+async fn delete_subscription(Path(id): Path<String>) -> ApiResult<Subscription> {
+    let sub = find_subscription(&id)?;
+    Ok(Json(subscription::remove_subscription(&sub.id)?))
+}
+
+async fn refresh_subscription(Path(id): Path<String>) -> ApiResult<Subscription> {
+    let sub = find_subscription(&id)?;
+    Ok(Json(subscription::refresh_subscription(&sub.id).await?))
+}
+
+// Refresh and merge
+
+#[derive(Serialize)]
+struct MergeSummary {
+    proxies: usize,
+    proxy_groups: usize,
+    rules: usize,
+}
+
+async fn summary(state: &AppState) -> MergeSummary {
+    let config = state.config.read().await;
+    MergeSummary {
+        proxies: config.proxies.len(),
+        proxy_groups: config.proxy_groups.len(),
+        rules: config.rules.len(),
+    }
+}
+
+/// Download all enabled subscriptions, then merge
+async fn refresh_all(State(state): State<AppState>) -> ApiResult<MergeSummary> {
+    state.refresh().await?;
+    Ok(Json(summary(&state).await))
+}
+
+/// Merge cached subscriptions into current.yaml without downloading
+async fn merge(State(state): State<AppState>) -> ApiResult<MergeSummary> {
+    let merged = crate::proxy::regenerate_current_config().await?;
+    state.set_config(merged).await?;
+    Ok(Json(summary(&state).await))
+}
+
+// Versions
+
+async fn list_versions() -> ApiResult<Vec<ConfigVersion>> {
+    Ok(Json(version::list_versions()?))
+}
+
+#[derive(Serialize)]
+struct VersionContent {
+    id: String,
+    content: String,
+}
+
+async fn get_version(Path(id): Path<String>) -> ApiResult<VersionContent> {
+    let id = find_version(&id)?;
+    let content =
+        version::get_version_content(&id).map_err(|e| ApiError::not_found(e.to_string()))?;
+    Ok(Json(VersionContent { id, content }))
+}
+
+#[derive(Deserialize)]
+struct SnapshotRequest {
+    description: String,
+}
+
+async fn create_snapshot(
+    Json(body): Json<SnapshotRequest>,
+) -> Result<(StatusCode, Json<ConfigVersion>), ApiError> {
+    if !crate::storage::get_current_config_path()?.exists() {
+        return Err(ApiError::not_found(
+            "No merged config to snapshot yet".to_string(),
+        ));
+    }
+    let version = version::snapshot_current(&body.description)?;
+    Ok((StatusCode::CREATED, Json(version)))
+}
+
+async fn delete_version(Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    let id = find_version(&id)?;
+    version::delete_version(&id).map_err(|e| ApiError::not_found(e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// Version to compare with (default: the current config)
+    against: Option<String>,
+}
+
+async fn diff_version(
+    Path(id): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> ApiResult<VersionDiff> {
+    find_version(&id)?;
+    if let Some(against) = &query.against {
+        find_version(against)?;
+    }
+    Ok(Json(version::diff_versions(&id, query.against.as_deref())?))
+}
+
+/// Restore a version into the file it was taken from; current.yaml is re-merged if needed
+async fn rollback_version(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<MergeSummary> {
+    let id = find_version(&id)?;
+    version::rollback_to_version(&id).await?;
+    state
+        .reload_from_file(&crate::storage::get_current_config_path()?)
+        .await?;
+    Ok(Json(summary(&state).await))
+}
+
+// Rule sources
+
+async fn list_rule_sources() -> ApiResult<Vec<RuleSource>> {
+    Ok(Json(rules::load_rule_sources()?))
+}
+
+async fn get_rule_source(Path(id): Path<String>) -> ApiResult<RuleSource> {
+    Ok(Json(find_rule_source(&id)?))
+}
+
+#[derive(Deserialize)]
+struct NewRuleSource {
+    name: String,
+    url: String,
+    interval: Option<u64>,
+}
+
+async fn add_rule_source(
+    Json(body): Json<NewRuleSource>,
+) -> Result<(StatusCode, Json<RuleSource>), ApiError> {
+    require_non_empty("name", &body.name)?;
+    require_http_url(&body.url)?;
+    if let Some(interval) = body.interval {
+        rules::validate_rule_interval(interval)
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+    }
+    if rules::load_rule_sources()?
+        .iter()
+        .any(|s| s.url == body.url)
+    {
+        return Err(ApiError::conflict(format!(
+            "A rule source with URL '{}' already exists",
+            body.url
+        )));
+    }
+    let source = rules::add_rule_source(body.name, body.url, body.interval)?;
+    Ok((StatusCode::CREATED, Json(source)))
+}
+
+#[derive(Deserialize)]
+struct RuleSourcePatch {
+    enabled: bool,
+}
+
+async fn update_rule_source(
+    Path(id): Path<String>,
+    Json(patch): Json<RuleSourcePatch>,
+) -> ApiResult<RuleSource> {
+    let source = find_rule_source(&id)?;
+    Ok(Json(rules::set_rule_source_enabled(
+        &source.id,
+        patch.enabled,
+    )?))
+}
+
+async fn delete_rule_source(Path(id): Path<String>) -> ApiResult<RuleSource> {
+    let source = find_rule_source(&id)?;
+    rules::remove_rule_source(&source.id)?;
+    Ok(Json(source))
+}
+
+async fn refresh_rule_source(Path(id): Path<String>) -> ApiResult<RuleSource> {
+    let source = find_rule_source(&id)?;
+    Ok(Json(rules::refresh_rule_source(&source.id).await?))
+}
@@ -0,0 +1,227 @@
+use crate::storage;
+use crate::types::{AccessToken, HangarConfig};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+
+/// Outcome of checking a request's token
+#[derive(Debug, Clone)]
+pub enum AuthResult {
+    /// No tokens are configured, the server is open
+    Open,
+    Granted(AccessToken),
+    Denied(String),
+}
+
+/// Generate a new random token secret
+pub fn generate_token() -> String {
+    format!(
+        "hgr_{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// SHA-256 hash (hex) of a token secret, as stored in config.json
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Parse an expiry given as a duration from now (`30d`, `12h`, `45m`),
+/// a date (`2026-12-31`) or a unix timestamp
+pub fn parse_expiry(input: &str, now: i64) -> Result<i64> {
+    let input = input.trim();
+
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        let end_of_day = date.and_hms_opt(23, 59, 59).context("Invalid date")?;
+        return Ok(end_of_day.and_utc().timestamp());
+    }
+
+    let invalid = || format!("Invalid expiry: '{}'", input);
+    const UNITS: &[(&str, i64)] = &[("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+    let (number, multiplier) = UNITS
+        .iter()
+        .find_map(|(unit, m)| input.strip_suffix(unit).map(|n| (n, *m)))
+        .unwrap_or((input, 0));
+
+    let n: i64 = number.parse().with_context(invalid)?;
+    if n < 0 {
+        anyhow::bail!("Invalid expiry: '{}' is negative", input);
+    }
+    if multiplier == 0 {
+        // A unix timestamp
+        return Ok(n);
+    }
+    n.checked_mul(multiplier)
+        .and_then(|seconds| now.checked_add(seconds))
+        .with_context(|| format!("Invalid expiry: '{}' is too far in the future", input))
+}
+
+/// Create a token, store its hash and return the secret (shown only once)
+pub fn create_token(
+    name: &str,
+    profile: &str,
+    expires_at: Option<i64>,
+    admin: bool,
+) -> Result<(String, AccessToken)> {
+    storage::modify_hangar_config(|config| {
+        if config.server.tokens.iter().any(|t| t.name == name) {
+            anyhow::bail!("A token named '{}' already exists", name);
+        }
+        if profile != "default" && !config.profiles.iter().any(|p| p.name == profile) {
+            anyhow::bail!("Profile not found: '{}'", profile);
+        }
+
+        let secret = generate_token();
+        let token = AccessToken {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            token_hash: hash_token(&secret),
+            profile: profile.to_string(),
+            expires_at,
+            created_at: chrono::Utc::now().timestamp(),
+            admin,
+        };
+
+        config.server.tokens.push(token.clone());
+        Ok((secret, token))
+    })
+}
+
+/// Revoke a token by name or ID
+pub fn revoke_token(key: &str) -> Result<AccessToken> {
+    storage::modify_hangar_config(|config| {
+        let idx = config
+            .server
+            .tokens
+            .iter()
+            .position(|t| t.name == key || t.id == key)
+            .with_context(|| format!("Token not found: '{}'", key))?;
+        Ok(config.server.tokens.remove(idx))
+    })
+}
+
+/// Check a presented token against the configured ones
+pub fn authenticate(config: &HangarConfig, presented: Option<&str>, now: i64) -> AuthResult {
+    if config.server.tokens.is_empty() {
+        return AuthResult::Open;
+    }
+
+    let Some(presented) = presented.filter(|t| !t.is_empty()) else {
+        return AuthResult::Denied("missing token".to_string());
+    };
+
+    let hash = hash_token(presented);
+    match config.server.tokens.iter().find(|t| t.token_hash == hash) {
+        Some(token) if token.expires_at.is_some_and(|exp| exp <= now) => {
+            AuthResult::Denied(format!("token '{}' expired", token.name))
+        }
+        Some(token) => AuthResult::Granted(token.clone()),
+        None => AuthResult::Denied("invalid token".to_string()),
+    }
+}
+
+/// Check a presented token for the management API, which always needs an admin token
+pub fn authorize_admin(
+    config: &HangarConfig,
+    presented: Option<&str>,
+    now: i64,
+) -> std::result::Result<AccessToken, String> {
+    match authenticate(config, presented, now) {
+        AuthResult::Granted(token) if token.admin => Ok(token),
+        AuthResult::Granted(token) => Err(format!("token '{}' is not an admin token", token.name)),
+        AuthResult::Open => Err("no admin token configured".to_string()),
+        AuthResult::Denied(reason) => Err(reason),
+    }
+}
+
+/// Extract a token from an `Authorization: Bearer` header value or a `?token=` query value
+pub fn extract_token<'a>(
+    authorization: Option<&'a str>,
+    query_token: Option<&'a str>,
+) -> Option<&'a str> {
+    authorization
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.trim())
+        .or(query_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_token(secret: &str, expires_at: Option<i64>) -> HangarConfig {
+        let mut config = HangarConfig::default();
+        config.server.tokens.push(AccessToken {
+            id: "id".to_string(),
+            name: "phone".to_string(),
+            token_hash: hash_token(secret),
+            profile: "default".to_string(),
+            expires_at,
+            created_at: 0,
+            admin: false,
+        });
+        config
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let now = 1_000_000;
+        assert!(matches!(
+            authenticate(&HangarConfig::default(), None, now),
+            AuthResult::Open
+        ));
+
+        let config = config_with_token("secret", None);
+        assert!(matches!(
+            authenticate(&config, Some("secret"), now),
+            AuthResult::Granted(_)
+        ));
+        assert!(matches!(
+            authenticate(&config, Some("wrong"), now),
+            AuthResult::Denied(_)
+        ));
+        assert!(matches!(
+            authenticate(&config, None, now),
+            AuthResult::Denied(_)
+        ));
+
+        let expired = config_with_token("secret", Some(now - 1));
+        assert!(matches!(
+            authenticate(&expired, Some("secret"), now),
+            AuthResult::Denied(_)
+        ));
+    }
+
+    #[test]
+    fn test_authorize_admin() {
+        let now = 1_000_000;
+        assert!(authorize_admin(&HangarConfig::default(), None, now).is_err());
+
+        let mut config = config_with_token("secret", None);
+        assert!(authorize_admin(&config, Some("secret"), now).is_err());
+
+        config.server.tokens[0].admin = true;
+        assert!(authorize_admin(&config, Some("secret"), now).is_ok());
+    }
+
+    #[test]
+    fn test_extract_token() {
+        assert_eq!(extract_token(Some("Bearer abc"), None), Some("abc"));
+        assert_eq!(extract_token(None, Some("xyz")), Some("xyz"));
+        assert_eq!(extract_token(Some("Basic abc"), None), None);
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(parse_expiry("30d", 100).unwrap(), 100 + 30 * 86400);
+        assert_eq!(parse_expiry("12h", 0).unwrap(), 12 * 3600);
+        assert_eq!(parse_expiry("1767225599", 0).unwrap(), 1767225599);
+        assert_eq!(parse_expiry("2025-12-31", 0).unwrap(), 1767225599);
+        assert!(parse_expiry("soon", 0).is_err());
+        assert!(parse_expiry("30天", 0).is_err());
+        assert!(parse_expiry("1ü", 0).is_err());
+        assert!(parse_expiry("9999999999999999d", 0).is_err());
+        assert!(parse_expiry("-5d", 0).is_err());
+        assert!(parse_expiry("-100", 0).is_err());
+    }
+}
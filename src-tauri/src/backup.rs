@@ -0,0 +1,891 @@
+use crate::rules::{self, RuleSource};
+use crate::storage;
+use crate::types::{ConfigVersion, HangarConfig, Subscription, SubscriptionList, VersionSource};
+use crate::version::{self, VersionIndex};
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Bumped when the bundle layout changes incompatibly
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Environment variable read instead of prompting for the passphrase
+pub const PASSPHRASE_ENV: &str = "HANGAR_BACKUP_PASSPHRASE";
+
+const MANIFEST: &str = "manifest.json";
+const CONFIG: &str = "config.json";
+const SUBSCRIPTIONS: &str = "subscriptions.json";
+const RULE_SOURCES: &str = "rule_sources.json";
+const SECRETS_PLAIN: &str = "secrets.json";
+const SECRETS_ENCRYPTED: &str = "secrets.enc.json";
+const VERSIONS_INDEX: &str = "versions/index.json";
+
+/// Config files copied as they are, by path relative to ~/.hangar
+const CONFIG_FILES: &[&str] = &[
+    "basic.yml",
+    "groups.yml",
+    "rules.local.yml",
+    "templates/basic.yml",
+    "templates/groups.yml",
+];
+
+/// How the secrets of config.json are stored in a bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretsMode {
+    /// Left out (or there were none)
+    Omitted,
+    /// Encrypted with a key derived from a passphrase
+    Encrypted,
+    Plaintext,
+}
+
+/// `manifest.json`, the first entry of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format: u32,
+    pub hangar_version: String,
+    pub created_at: i64,
+    pub secrets: SecretsMode,
+    pub includes_history: bool,
+    /// Entries of the bundle besides the manifest
+    pub files: Vec<String>,
+}
+
+/// Secrets removed from config.json before it is bundled
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Secrets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    llm_api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template_secret: Option<String>,
+}
+
+impl Secrets {
+    fn take(config: &mut HangarConfig) -> Self {
+        Self {
+            llm_api_key: Some(std::mem::take(&mut config.llm.api_key)).filter(|k| !k.is_empty()),
+            template_secret: config.template.params.secret.take(),
+        }
+    }
+
+    fn restore(self, config: &mut HangarConfig) {
+        if let Some(key) = self.llm_api_key {
+            config.llm.api_key = key;
+        }
+        if let Some(secret) = self.template_secret {
+            config.template.params.secret = Some(secret);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Secrets encrypted with ChaCha20-Poly1305 under an Argon2id key
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedSecrets {
+    kdf: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Key> {
+    let params = argon2::Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive key: {}", e))?;
+    Ok(*Key::from_slice(&key))
+}
+
+fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<EncryptedSecrets> {
+    let params = argon2::Params::default();
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(
+        passphrase,
+        &salt,
+        params.m_cost(),
+        params.t_cost(),
+        params.p_cost(),
+    )?;
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt secrets"))?;
+
+    Ok(EncryptedSecrets {
+        kdf: "argon2id".to_string(),
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+/// Upper bounds for the key derivation parameters read from a bundle, so a crafted
+/// bundle can't make the import allocate gigabytes or spin for minutes
+const MAX_M_COST: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+
+fn decrypt(encrypted: &EncryptedSecrets, passphrase: &str) -> Result<Vec<u8>> {
+    if encrypted.kdf != "argon2id" {
+        anyhow::bail!("Unsupported key derivation: {}", encrypted.kdf);
+    }
+    if encrypted.m_cost > MAX_M_COST
+        || encrypted.t_cost > MAX_T_COST
+        || encrypted.p_cost > MAX_P_COST
+    {
+        anyhow::bail!(
+            "Key derivation parameters exceed the limits (m_cost {}, t_cost {}, p_cost {})",
+            MAX_M_COST,
+            MAX_T_COST,
+            MAX_P_COST
+        );
+    }
+    let salt = BASE64.decode(&encrypted.salt).context("Invalid salt")?;
+    let nonce = BASE64.decode(&encrypted.nonce).context("Invalid nonce")?;
+    if nonce.len() != 12 {
+        anyhow::bail!("Invalid nonce");
+    }
+    let ciphertext = BASE64
+        .decode(&encrypted.ciphertext)
+        .context("Invalid ciphertext")?;
+
+    let key = derive_key(
+        passphrase,
+        &salt,
+        encrypted.m_cost,
+        encrypted.t_cost,
+        encrypted.p_cost,
+    )?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase, or the secrets are corrupted"))
+}
+
+/// What `hangar backup export` puts in the bundle
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Encrypt the secrets with this passphrase
+    pub passphrase: Option<String>,
+    /// Include the secrets unencrypted when there is no passphrase
+    pub plaintext_secrets: bool,
+    /// Include versions/ (snapshots and their index)
+    pub include_history: bool,
+}
+
+/// Write a .tar.gz bundle of the configuration (caches, logs and current.yaml are left out)
+pub fn export(path: &Path, options: &ExportOptions) -> Result<BackupManifest> {
+    let hangar_dir = storage::get_hangar_dir()?;
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    let mut config = storage::load_hangar_config()?;
+    let secrets = Secrets::take(&mut config);
+    let secrets_mode = if secrets.is_empty() {
+        SecretsMode::Omitted
+    } else if let Some(passphrase) = &options.passphrase {
+        let encrypted = encrypt(&serde_json::to_vec(&secrets)?, passphrase)?;
+        files.insert(
+            SECRETS_ENCRYPTED.to_string(),
+            serde_json::to_vec_pretty(&encrypted)?,
+        );
+        SecretsMode::Encrypted
+    } else if options.plaintext_secrets {
+        files.insert(
+            SECRETS_PLAIN.to_string(),
+            serde_json::to_vec_pretty(&secrets)?,
+        );
+        SecretsMode::Plaintext
+    } else {
+        SecretsMode::Omitted
+    };
+    files.insert(CONFIG.to_string(), serde_json::to_vec_pretty(&config)?);
+
+    let subscriptions = SubscriptionList {
+        subscriptions: storage::load_subscriptions()?,
+    };
+    files.insert(
+        SUBSCRIPTIONS.to_string(),
+        serde_json::to_vec_pretty(&subscriptions)?,
+    );
+    files.insert(
+        RULE_SOURCES.to_string(),
+        serde_json::to_vec_pretty(&rules::load_rule_sources()?)?,
+    );
+
+    for name in CONFIG_FILES {
+        let path = hangar_dir.join(name);
+        if path.exists() {
+            files.insert(
+                name.to_string(),
+                fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?,
+            );
+        }
+    }
+
+    if options.include_history {
+        let mut index = version::load_index()?;
+        for version in &mut index.versions {
+            let content = fs::read(&version.file_path)
+                .with_context(|| format!("Failed to read version {}", version.id))?;
+            files.insert(format!("versions/{}", version.file), content);
+            version.file_path.clear();
+        }
+        files.insert(
+            VERSIONS_INDEX.to_string(),
+            serde_json::to_vec_pretty(&index)?,
+        );
+    }
+
+    let manifest = BackupManifest {
+        format: FORMAT_VERSION,
+        hangar_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: chrono::Utc::now().timestamp(),
+        secrets: secrets_mode,
+        includes_history: options.include_history,
+        files: files.keys().cloned().collect(),
+    };
+    write_bundle(path, &manifest, &files)?;
+    Ok(manifest)
+}
+
+fn write_bundle(
+    path: &Path,
+    manifest: &BackupManifest,
+    files: &BTreeMap<String, Vec<u8>>,
+) -> Result<()> {
+    // The bundle may carry unencrypted secrets: readable only by the current user
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(path)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    // `mode` only applies to new files; re-exporting over an older bundle keeps its mode
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let manifest_content = serde_json::to_vec_pretty(manifest)?;
+    let entries = std::iter::once((MANIFEST, &manifest_content))
+        .chain(files.iter().map(|(name, content)| (name.as_str(), content)));
+    for (name, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(manifest.created_at.max(0) as u64);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, name, content.as_slice())
+            .with_context(|| format!("Failed to add {} to the bundle", name))?;
+    }
+
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// A bundle read into memory
+struct Bundle {
+    manifest: BackupManifest,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    fn read(path: &Path) -> Result<Self> {
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        let mut files = BTreeMap::new();
+        for entry in archive.entries().context("Not a Hangar backup bundle")? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.insert(name, content);
+        }
+
+        let manifest: BackupManifest = files
+            .remove(MANIFEST)
+            .context("Not a Hangar backup bundle: manifest.json is missing")
+            .and_then(|content| {
+                serde_json::from_slice(&content).context("Failed to parse manifest.json")
+            })?;
+        if manifest.format > FORMAT_VERSION {
+            anyhow::bail!(
+                "Backup format {} was written by a newer Hangar ({}); please upgrade",
+                manifest.format,
+                manifest.hangar_version
+            );
+        }
+        Ok(Self { manifest, files })
+    }
+
+    fn json<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        self.files
+            .get(name)
+            .map(|content| {
+                serde_json::from_slice(content).with_context(|| format!("Failed to parse {}", name))
+            })
+            .transpose()
+    }
+
+    fn text(&self, name: &str) -> Result<Option<String>> {
+        self.files
+            .get(name)
+            .map(|content| {
+                String::from_utf8(content.clone()).with_context(|| format!("{} is not UTF-8", name))
+            })
+            .transpose()
+    }
+}
+
+/// Read a bundle's manifest, e.g. to know whether import needs a passphrase
+pub fn read_manifest(path: &Path) -> Result<BackupManifest> {
+    Ok(Bundle::read(path)?.manifest)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportMode {
+    /// Add what's missing locally; differing items are conflicts
+    #[default]
+    Merge,
+    /// Replace the local configuration with the bundle's
+    Replace,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub mode: ImportMode,
+    /// Resolve merge conflicts in favour of the bundle instead of local data
+    pub prefer_backup: bool,
+    /// Passphrase of encrypted secrets; without it they are skipped
+    pub passphrase: Option<String>,
+    /// Only report what would change
+    pub dry_run: bool,
+}
+
+/// Which side a conflict was resolved to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kept {
+    Local,
+    Backup,
+}
+
+/// An item that differs between the bundle and the local configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportConflict {
+    pub item: String,
+    pub detail: String,
+    pub kept: Kept,
+}
+
+/// What an import changed (or would change, with `dry_run`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub manifest: BackupManifest,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub conflicts: Vec<ImportConflict>,
+    pub secrets_restored: bool,
+    pub versions: Option<version::HistoryImport>,
+    pub warnings: Vec<String>,
+}
+
+impl ImportReport {
+    fn new(manifest: BackupManifest) -> Self {
+        Self {
+            manifest,
+            added: vec![],
+            updated: vec![],
+            removed: vec![],
+            conflicts: vec![],
+            secrets_restored: false,
+            versions: None,
+            warnings: vec![],
+        }
+    }
+
+    fn conflict(&mut self, item: String, detail: String, prefer_backup: bool) -> bool {
+        let kept = if prefer_backup {
+            Kept::Backup
+        } else {
+            Kept::Local
+        };
+        self.conflicts.push(ImportConflict { item, detail, kept });
+        prefer_backup
+    }
+}
+
+/// Restore a bundle into ~/.hangar, merging it or replacing the local configuration
+pub fn import(path: &Path, options: &ImportOptions) -> Result<ImportReport> {
+    let bundle = Bundle::read(path)?;
+    let replace = options.mode == ImportMode::Replace;
+    let apply = !options.dry_run;
+    let mut report = ImportReport::new(bundle.manifest.clone());
+
+    let secrets = match bundle.manifest.secrets {
+        SecretsMode::Omitted => None,
+        SecretsMode::Plaintext => bundle.json::<Secrets>(SECRETS_PLAIN)?,
+        SecretsMode::Encrypted => match &options.passphrase {
+            Some(passphrase) => {
+                let encrypted: EncryptedSecrets = bundle
+                    .json(SECRETS_ENCRYPTED)?
+                    .context("secrets.enc.json is missing from the bundle")?;
+                Some(
+                    serde_json::from_slice(&decrypt(&encrypted, passphrase)?)
+                        .context("Failed to parse decrypted secrets")?,
+                )
+            }
+            None => {
+                report.warnings.push(
+                    "Secrets are encrypted and no passphrase was given; kept local secrets"
+                        .to_string(),
+                );
+                None
+            }
+        },
+    };
+
+    // Versions first, so the snapshots taken before overwriting files below survive a replace
+    if bundle.manifest.includes_history {
+        let index: VersionIndex = bundle
+            .json(VERSIONS_INDEX)?
+            .context("versions/index.json is missing from the bundle")?;
+        let mut entries: Vec<(ConfigVersion, String)> = Vec::new();
+        for version in index.versions {
+            let content = bundle
+                .text(&format!("versions/{}", version.file))?
+                .with_context(|| {
+                    format!("Version file {} is missing from the bundle", version.file)
+                })?;
+            entries.push((version, content));
+        }
+        let imported = version::import_versions(entries, replace, options.dry_run)?;
+        for tag in &imported.dropped_tags {
+            report.conflict(
+                format!("tag '{}'", tag),
+                "already names a local version".to_string(),
+                false,
+            );
+        }
+        report.versions = Some(imported);
+    }
+
+    import_config(&bundle, secrets, options, &mut report)?;
+
+    // Lists are merged under their locks; a dry run merges into a copy
+    let backup: SubscriptionList = bundle.json(SUBSCRIPTIONS)?.unwrap_or(SubscriptionList {
+        subscriptions: vec![],
+    });
+    storage::modify_subscriptions(|subscriptions| {
+        let mut merged = subscriptions.clone();
+        let changed = merge_items(
+            &mut merged,
+            backup.subscriptions,
+            "subscription",
+            |s: &Subscription| (&s.id, &s.name, &s.url),
+            options,
+            &mut report,
+        );
+        if changed && apply {
+            *subscriptions = merged;
+        }
+        Ok(())
+    })?;
+
+    let backup: Vec<RuleSource> = bundle.json(RULE_SOURCES)?.unwrap_or_default();
+    rules::modify_rule_sources(|sources| {
+        let mut merged = sources.clone();
+        let changed = merge_items(
+            &mut merged,
+            backup,
+            "rule source",
+            |s: &RuleSource| (&s.id, &s.name, &s.url),
+            options,
+            &mut report,
+        );
+        if changed && apply {
+            *sources = merged;
+        }
+        Ok(())
+    })?;
+
+    import_files(&bundle, options, &mut report)?;
+
+    if apply {
+        crate::git_history::record(VersionSource::Manual, "Import backup");
+    }
+    Ok(report)
+}
+
+/// Merge config.json: settings missing locally are added, differing ones are conflicts
+fn import_config(
+    bundle: &Bundle,
+    secrets: Option<Secrets>,
+    options: &ImportOptions,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let Some(mut backup) = bundle.json::<HangarConfig>(CONFIG)? else {
+        return Ok(());
+    };
+    storage::modify_hangar_config(|local| {
+        import_config_into(local, &mut backup, secrets, options, report)
+    })
+}
+
+fn import_config_into(
+    local: &mut HangarConfig,
+    backup: &mut HangarConfig,
+    secrets: Option<Secrets>,
+    options: &ImportOptions,
+    report: &mut ImportReport,
+) -> Result<()> {
+    // Without the bundle's secrets, keep the local ones rather than blanking them
+    let local_secrets = Secrets::take(&mut local.clone());
+    let bundle_secrets = secrets.clone().unwrap_or_default();
+    match secrets {
+        Some(secrets) => secrets.restore(backup),
+        None => local_secrets.clone().restore(backup),
+    }
+
+    let merged: HangarConfig = if options.mode == ImportMode::Replace {
+        backup.clone()
+    } else {
+        let mut merged = serde_json::to_value(&*local)?;
+        merge_settings(
+            &mut merged,
+            serde_json::to_value(&*backup)?,
+            "",
+            options.prefer_backup,
+            report,
+        );
+        serde_json::from_value(merged).context("Failed to merge config.json")?
+    };
+
+    // Only secrets the merged config took from the bundle count as restored; in a merge
+    // the local ones win conflicts
+    let merged_secrets = Secrets::take(&mut merged.clone());
+    let took = |bundle: &Option<String>, merged: &Option<String>, local: &Option<String>| {
+        bundle.is_some() && merged == bundle && local != bundle
+    };
+    report.secrets_restored = took(
+        &bundle_secrets.llm_api_key,
+        &merged_secrets.llm_api_key,
+        &local_secrets.llm_api_key,
+    ) || took(
+        &bundle_secrets.template_secret,
+        &merged_secrets.template_secret,
+        &local_secrets.template_secret,
+    );
+
+    if serde_json::to_value(&merged)? != serde_json::to_value(&*local)? {
+        report.updated.push(CONFIG.to_string());
+        if !options.dry_run {
+            *local = merged;
+        }
+    }
+    Ok(())
+}
+
+/// Settings whose values are never shown in conflict reports
+const SECRET_SETTINGS: &[&str] = &["llm.api_key", "template.params.secret"];
+
+/// Merge `backup` into `local` key by key. Lists are merged by `id` (or `name`) of their
+/// entries; other differing values are conflicts.
+fn merge_settings(
+    local: &mut serde_json::Value,
+    backup: serde_json::Value,
+    key: &str,
+    prefer_backup: bool,
+    report: &mut ImportReport,
+) {
+    use serde_json::Value;
+
+    let show = |value: &Value| {
+        if SECRET_SETTINGS.contains(&key) {
+            "•••".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+
+    match (local, backup) {
+        (Value::Object(local), Value::Object(backup)) => {
+            for (name, value) in backup {
+                let child = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", key, name)
+                };
+                match local.get_mut(&name) {
+                    Some(existing) => {
+                        merge_settings(existing, value, &child, prefer_backup, report)
+                    }
+                    None => {
+                        local.insert(name, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(local), Value::Array(backup)) => {
+            let identity = |value: &Value| {
+                value
+                    .get("id")
+                    .or_else(|| value.get("name"))
+                    .cloned()
+                    .unwrap_or_else(|| value.clone())
+            };
+            for value in backup {
+                let id = identity(&value);
+                match local.iter_mut().find(|existing| identity(existing) == id) {
+                    Some(existing) if *existing == value => {}
+                    Some(existing) => {
+                        let label = match &id {
+                            Value::String(name) => format!("{} '{}'", key, name),
+                            _ => key.to_string(),
+                        };
+                        if report.conflict(label, "differs".to_string(), prefer_backup) {
+                            *existing = value;
+                        }
+                    }
+                    None => local.push(value),
+                }
+            }
+        }
+        (local, backup) if *local != backup => {
+            let detail = format!("local {} / backup {}", show(local), show(&backup));
+            if report.conflict(key.to_string(), detail, prefer_backup) {
+                *local = backup;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merge a list of subscriptions or rule sources, identified by ID, URL or name.
+/// Returns whether `local` changed.
+fn merge_items<T: Clone>(
+    local: &mut Vec<T>,
+    backup: Vec<T>,
+    kind: &str,
+    key: impl Fn(&T) -> (&String, &String, &String),
+    options: &ImportOptions,
+    report: &mut ImportReport,
+) -> bool {
+    if options.mode == ImportMode::Replace {
+        let ids = |items: &[T]| -> Vec<(String, String, String)> {
+            items
+                .iter()
+                .map(|item| {
+                    let (id, name, url) = key(item);
+                    (id.clone(), name.clone(), url.clone())
+                })
+                .collect()
+        };
+        let (old, new) = (ids(local), ids(&backup));
+        for (id, name, _) in &old {
+            if !new.iter().any(|(other, _, _)| other == id) {
+                report.removed.push(format!("{} '{}'", kind, name));
+            }
+        }
+        for entry in &new {
+            match old.iter().find(|(id, _, _)| *id == entry.0) {
+                None => report.added.push(format!("{} '{}'", kind, entry.1)),
+                Some(existing) if existing != entry => {
+                    report.updated.push(format!("{} '{}'", kind, entry.1))
+                }
+                Some(_) => {}
+            }
+        }
+        let changed = old != new;
+        *local = backup;
+        return changed;
+    }
+
+    let mut changed = false;
+    for item in backup {
+        let (id, name, url) = key(&item);
+        let position = local.iter().position(|l| key(l).0 == id).or_else(|| {
+            local
+                .iter()
+                .position(|l| key(l).2 == url || key(l).1 == name)
+        });
+        let Some(position) = position else {
+            report.added.push(format!("{} '{}'", kind, name));
+            local.push(item);
+            changed = true;
+            continue;
+        };
+
+        let (_, local_name, local_url) = key(&local[position]);
+        if local_url == url && local_name == name {
+            continue;
+        }
+        let detail = if local_url != url {
+            "URL differs".to_string()
+        } else {
+            format!("named '{}' locally", local_name)
+        };
+        if report.conflict(
+            format!("{} '{}'", kind, name),
+            detail,
+            options.prefer_backup,
+        ) {
+            local[position] = item;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Version target of a config file, if its versions are kept
+fn file_target(name: &str) -> Option<&'static str> {
+    match name {
+        "basic.yml" => Some("basic"),
+        "groups.yml" => Some("groups"),
+        "rules.local.yml" => Some("local"),
+        _ => None,
+    }
+}
+
+/// Restore basic.yml, groups.yml, rules.local.yml and the installed templates.
+/// Local files that get overwritten or removed are snapshotted first.
+fn import_files(bundle: &Bundle, options: &ImportOptions, report: &mut ImportReport) -> Result<()> {
+    let hangar_dir = storage::get_hangar_dir()?;
+    let replace = options.mode == ImportMode::Replace;
+
+    for name in CONFIG_FILES {
+        let path = hangar_dir.join(name);
+        let local = path
+            .exists()
+            .then(|| fs::read_to_string(&path))
+            .transpose()?;
+        let backup = bundle.text(name)?;
+
+        let write = match (&local, &backup) {
+            (None, None) => continue,
+            (Some(_), None) if !replace => continue,
+            (Some(local), Some(backup)) if local == backup => continue,
+            (None, Some(_)) => {
+                report.added.push(name.to_string());
+                true
+            }
+            (Some(_), None) => {
+                report.removed.push(name.to_string());
+                true
+            }
+            (Some(_), Some(_)) if replace => {
+                report.updated.push(name.to_string());
+                true
+            }
+            (Some(_), Some(_)) => report.conflict(
+                name.to_string(),
+                "content differs".to_string(),
+                options.prefer_backup,
+            ),
+        };
+        if !write || options.dry_run {
+            continue;
+        }
+
+        if let (Some(local), Some(target)) = (&local, file_target(name)) {
+            version::save_version(target, VersionSource::Manual, "Before backup import", local)?;
+        }
+        match &backup {
+            Some(content) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                storage::write_atomic(&path, content)?
+            }
+            None => {
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_roundtrip() {
+        let encrypted = encrypt(b"{\"llm_api_key\":\"sk-1\"}", "correct horse").unwrap();
+        assert!(!encrypted.ciphertext.contains("sk-1"));
+        assert_eq!(
+            decrypt(&encrypted, "correct horse").unwrap(),
+            b"{\"llm_api_key\":\"sk-1\"}"
+        );
+        assert!(decrypt(&encrypted, "wrong").is_err());
+
+        let mut crafted = encrypt(b"{}", "correct horse").unwrap();
+        crafted.m_cost = u32::MAX;
+        let error = decrypt(&crafted, "correct horse").unwrap_err();
+        assert!(error.to_string().contains("exceed the limits"));
+    }
+
+    #[test]
+    fn test_merge_settings_reports_conflicts() {
+        let mut local = serde_json::json!({
+            "llm": {"model": "gpt-4o", "api_key": "sk-local"},
+            "profiles": [{"name": "phone", "subscriptions": []}],
+        });
+        let backup = serde_json::json!({
+            "llm": {"model": "gpt-4o-mini", "api_key": "sk-backup", "base_url": "https://x"},
+            "profiles": [
+                {"name": "phone", "subscriptions": ["Home"]},
+                {"name": "laptop", "subscriptions": []},
+            ],
+        });
+        let mut report = ImportReport::new(BackupManifest {
+            format: FORMAT_VERSION,
+            hangar_version: String::new(),
+            created_at: 0,
+            secrets: SecretsMode::Omitted,
+            includes_history: false,
+            files: vec![],
+        });
+        merge_settings(&mut local, backup, "", false, &mut report);
+
+        // Local values win, missing keys and list entries are added
+        assert_eq!(local["llm"]["model"], "gpt-4o");
+        assert_eq!(local["llm"]["base_url"], "https://x");
+        assert_eq!(local["profiles"].as_array().unwrap().len(), 2);
+        assert_eq!(local["profiles"][0]["subscriptions"], serde_json::json!([]));
+
+        let items: Vec<&str> = report.conflicts.iter().map(|c| c.item.as_str()).collect();
+        assert_eq!(items, vec!["llm.model", "llm.api_key", "profiles 'phone'"]);
+        assert!(!report.conflicts[1].detail.contains("sk-"));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::types::{ChainMode, ProxyChain, ProxyGroup, ProxyNode, Subscription};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use tracing::{info, warn};
+
+/// Resolve the landing nodes of a chain (inline node and/or landing subscription)
+fn landing_nodes(chain: &ProxyChain, subscriptions: &[Subscription]) -> Result<Vec<ProxyNode>> {
+    let mut nodes = Vec::new();
+
+    if let Some(node) = &chain.landing_proxy {
+        nodes.push(node.clone());
+    }
+
+    if let Some(key) = &chain.landing_subscription {
+        let sub = subscriptions
+            .iter()
+            .find(|s| &s.name == key || &s.id == key)
+            .with_context(|| format!("Landing subscription not found: '{}'", key))?;
+        nodes.extend(crate::proxy::parse_cached_subscription(sub)?);
+    }
+
+    if nodes.is_empty() {
+        anyhow::bail!("Chain has neither a landing_proxy nor a landing_subscription");
+    }
+
+    Ok(nodes)
+}
+
+fn name_taken(name: &str, proxies: &[ProxyNode], groups: &[ProxyGroup]) -> bool {
+    proxies.iter().any(|p| p.name == name) || groups.iter().any(|g| g.name == name)
+}
+
+/// `name`, or `name-2`, `name-3`, ... if it is already used by a group or node
+fn unique_name(name: String, proxies: &[ProxyNode], groups: &[ProxyGroup]) -> String {
+    if !name_taken(&name, proxies, groups) {
+        return name;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !name_taken(candidate, proxies, groups))
+        .expect("unbounded suffix search")
+}
+
+/// Add the proxies and groups of one chain
+fn apply_chain(
+    chain: &ProxyChain,
+    landing: Vec<ProxyNode>,
+    proxies: &mut Vec<ProxyNode>,
+    groups: &mut Vec<ProxyGroup>,
+) -> Result<()> {
+    let front_exists = name_taken(&chain.front, proxies, groups)
+        || matches!(chain.front.as_str(), "DIRECT" | "REJECT");
+    if !front_exists {
+        anyhow::bail!("Front '{}' is not a known group or node", chain.front);
+    }
+    if name_taken(&chain.name, proxies, groups) {
+        anyhow::bail!("Name '{}' is already used by a group or node", chain.name);
+    }
+
+    let mut members = Vec::new();
+    match chain.mode {
+        ChainMode::DialerProxy => {
+            // Copies, so the landing nodes keep working unchained elsewhere
+            for mut node in landing {
+                node.name = unique_name(format!("{}-{}", chain.name, node.name), proxies, groups);
+                node.region = None;
+                node.extra.insert(
+                    "dialer-proxy".to_string(),
+                    serde_json::json!(chain.front.clone()),
+                );
+                members.push(node.name.clone());
+                proxies.push(node);
+            }
+        }
+        ChainMode::Relay => {
+            for mut node in landing {
+                // Reuse a node only if it is the same server, never one that shares its name
+                let same_node = proxies.iter().any(|p| {
+                    p.name == node.name
+                        && p.proxy_type == node.proxy_type
+                        && p.server == node.server
+                        && p.port == node.port
+                });
+                if !same_node {
+                    node.name = unique_name(node.name, proxies, groups);
+                }
+
+                let relay_name =
+                    unique_name(format!("{}-{}", chain.name, node.name), proxies, groups);
+                groups.push(ProxyGroup {
+                    name: relay_name.clone(),
+                    group_type: "relay".to_string(),
+                    proxies: vec![chain.front.clone(), node.name.clone()],
+                    extra: IndexMap::new(),
+                });
+                members.push(relay_name);
+
+                if !same_node {
+                    proxies.push(node);
+                }
+            }
+        }
+    }
+
+    groups.push(ProxyGroup {
+        name: chain.name.clone(),
+        group_type: "select".to_string(),
+        proxies: members,
+        extra: IndexMap::new(),
+    });
+
+    Ok(())
+}
+
+/// Generate chained proxies (dialer-proxy nodes or relay groups) for every chain.
+/// Chains that can't be built are skipped with a warning, like failing subscriptions.
+/// Returns the warnings.
+pub fn apply_chains(
+    chains: &[ProxyChain],
+    subscriptions: &[Subscription],
+    proxies: &mut Vec<ProxyNode>,
+    groups: &mut Vec<ProxyGroup>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for chain in chains {
+        let result = landing_nodes(chain, subscriptions)
+            .and_then(|landing| apply_chain(chain, landing, proxies, groups));
+
+        match result {
+            Ok(_) => info!("✓ Chain {} via {}", chain.name, chain.front),
+            Err(e) => {
+                warn!("⚠️ Skipped chain {}: {}", chain.name, e);
+                warnings.push(format!("Skipped chain {}: {}", chain.name, e));
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corp_chain(mode: ChainMode) -> ProxyChain {
+        serde_json::from_value(serde_json::json!({
+            "name": "Corp",
+            "front": "HK-地区",
+            "landing_proxy": {
+                "name": "corp-exit",
+                "type": "socks5",
+                "server": "exit.corp.example.com",
+                "port": 1080
+            },
+            "mode": mode,
+        }))
+        .unwrap()
+    }
+
+    fn front_group() -> ProxyGroup {
+        ProxyGroup {
+            name: "HK-地区".to_string(),
+            group_type: "load-balance".to_string(),
+            proxies: vec!["[A]-HK 01".to_string()],
+            extra: IndexMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_dialer_proxy_chain() {
+        let mut proxies = vec![];
+        let mut groups = vec![front_group()];
+        apply_chains(
+            &[corp_chain(ChainMode::DialerProxy)],
+            &[],
+            &mut proxies,
+            &mut groups,
+        );
+
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(proxies[0].name, "Corp-corp-exit");
+        assert_eq!(
+            proxies[0].extra["dialer-proxy"],
+            serde_json::json!("HK-地区")
+        );
+
+        let group = groups.iter().find(|g| g.name == "Corp").unwrap();
+        assert_eq!(group.proxies, vec!["Corp-corp-exit"]);
+    }
+
+    #[test]
+    fn test_relay_chain() {
+        let mut proxies = vec![];
+        let mut groups = vec![front_group()];
+        apply_chains(
+            &[corp_chain(ChainMode::Relay)],
+            &[],
+            &mut proxies,
+            &mut groups,
+        );
+
+        assert_eq!(proxies[0].name, "corp-exit");
+        assert!(!proxies[0].extra.contains_key("dialer-proxy"));
+
+        let relay = groups.iter().find(|g| g.name == "Corp-corp-exit").unwrap();
+        assert_eq!(relay.group_type, "relay");
+        assert_eq!(relay.proxies, vec!["HK-地区", "corp-exit"]);
+    }
+
+    #[test]
+    fn test_chain_names_never_shadow_existing_nodes() {
+        let existing = |name: &str, server: &str| ProxyNode {
+            name: name.to_string(),
+            proxy_type: "ss".to_string(),
+            server: server.to_string(),
+            port: 443,
+            region: None,
+            airport: String::new(),
+            extra: IndexMap::new(),
+        };
+
+        let mut proxies = vec![existing("Corp-corp-exit", "a.example.com")];
+        let mut groups = vec![front_group()];
+        apply_chains(
+            &[corp_chain(ChainMode::DialerProxy)],
+            &[],
+            &mut proxies,
+            &mut groups,
+        );
+        assert_eq!(proxies[0].server, "a.example.com");
+        assert_eq!(proxies[1].name, "Corp-corp-exit-2");
+
+        // A different node with the landing node's name must not be picked up by the relay
+        let mut proxies = vec![existing("corp-exit", "b.example.com")];
+        let mut groups = vec![front_group()];
+        apply_chains(
+            &[corp_chain(ChainMode::Relay)],
+            &[],
+            &mut proxies,
+            &mut groups,
+        );
+        assert_eq!(proxies[1].name, "corp-exit-2");
+        assert_eq!(proxies[1].server, "exit.corp.example.com");
+        let relay = groups.iter().find(|g| g.group_type == "relay").unwrap();
+        assert_eq!(relay.proxies, vec!["HK-地区", "corp-exit-2"]);
+    }
+
+    #[test]
+    fn test_chain_with_unknown_front_is_skipped() {
+        let mut proxies = vec![];
+        let mut groups = vec![];
+        apply_chains(
+            &[corp_chain(ChainMode::DialerProxy)],
+            &[],
+            &mut proxies,
+            &mut groups,
+        );
+        assert!(proxies.is_empty());
+        assert!(groups.is_empty());
+    }
+}
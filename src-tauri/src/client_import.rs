@@ -0,0 +1,593 @@
+use crate::profile::BUILTIN_POLICIES;
+use crate::storage;
+use crate::types::{ClashConfig, ProxyGroup, Subscription, VersionSource};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where profiles are imported from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClientKind {
+    /// Clash Verge Rev data directory (profiles.yaml)
+    ClashVerge,
+    /// mihomo party data directory (profile.yaml)
+    MihomoParty,
+    /// A standalone Clash config, e.g. subconverter output
+    ClashYaml,
+}
+
+impl std::fmt::Display for ClientKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::ClashVerge => "Clash Verge Rev",
+            Self::MihomoParty => "mihomo party",
+            Self::ClashYaml => "Clash YAML",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A subscription to create from a client profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedSubscription {
+    pub name: String,
+    /// Remote URL; None for a local subscription holding the profile's own proxies
+    pub url: Option<String>,
+    /// Nodes in the profile file (seeded into the proxies cache)
+    pub nodes: usize,
+    /// Why it won't be created, e.g. it already exists
+    pub skipped: Option<String>,
+    /// Profile file content, or the proxies of a local subscription
+    #[serde(skip)]
+    content: Option<String>,
+}
+
+/// A profile's settings, rules and groups offered as basic.yml and groups.yml
+#[derive(Debug, Clone, Serialize)]
+pub struct BaseImport {
+    /// Profile they come from
+    pub profile: String,
+    pub basic: String,
+    pub groups: String,
+    pub rule_count: usize,
+    pub group_count: usize,
+    pub has_dns: bool,
+    /// Group members naming the profile's nodes; Hangar fills groups with region groups instead
+    pub dropped_members: usize,
+    /// Top-level settings the profile lacks, kept from the current basic.yml
+    pub kept_settings: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// What `hangar import` found
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientImportPlan {
+    pub client: ClientKind,
+    pub source: PathBuf,
+    pub subscriptions: Vec<ImportedSubscription>,
+    pub base: Option<BaseImport>,
+    /// Profile items that aren't imported (merge/script enhancements, ...)
+    pub ignored: Vec<String>,
+}
+
+/// Data directories of supported clients, where they exist
+pub fn default_client_dirs() -> Vec<PathBuf> {
+    let candidates = [
+        dirs::data_dir().map(|d| d.join("io.github.clash-verge-rev.clash-verge-rev")),
+        dirs::config_dir().map(|d| d.join("mihomo-party")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter(|dir| detect(dir).is_some())
+        .collect()
+}
+
+fn detect(path: &Path) -> Option<ClientKind> {
+    if path.is_file() {
+        Some(ClientKind::ClashYaml)
+    } else if path.join("profiles.yaml").exists() {
+        Some(ClientKind::ClashVerge)
+    } else if path.join("profile.yaml").exists() {
+        Some(ClientKind::MihomoParty)
+    } else {
+        None
+    }
+}
+
+/// An item of Clash Verge Rev's profiles.yaml or mihomo party's profile.yaml
+#[derive(Debug, Deserialize)]
+struct ProfileItem {
+    /// Clash Verge Rev
+    uid: Option<String>,
+    /// mihomo party
+    id: Option<String>,
+    #[serde(rename = "type")]
+    item_type: Option<String>,
+    name: Option<String>,
+    /// Clash Verge Rev: file name under profiles/
+    file: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileList {
+    current: Option<String>,
+    #[serde(default)]
+    items: Vec<ProfileItem>,
+}
+
+/// Read profiles from a client data directory or a Clash YAML file. `profile` (name or ID)
+/// picks the one offered as basic.yml/groups.yml; by default it's the client's current one.
+pub fn read_client(path: &Path, profile: Option<&str>) -> Result<ClientImportPlan> {
+    let client = detect(path).with_context(|| {
+        format!(
+            "{:?} is neither a Clash YAML file nor a Clash Verge Rev / mihomo party directory",
+            path
+        )
+    })?;
+
+    let mut plan = ClientImportPlan {
+        client,
+        source: path.to_path_buf(),
+        subscriptions: vec![],
+        base: None,
+        ignored: vec![],
+    };
+
+    if client == ClientKind::ClashYaml {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "imported".to_string());
+        plan.subscriptions = local_subscriptions(&name, &content)?;
+        plan.base = Some(build_base(&name, &content)?);
+        return Ok(plan);
+    }
+
+    let list_file = match client {
+        ClientKind::ClashVerge => "profiles.yaml",
+        _ => "profile.yaml",
+    };
+    let list: ProfileList = serde_yaml::from_str(
+        &fs::read_to_string(path.join(list_file))
+            .with_context(|| format!("Failed to read {}", list_file))?,
+    )
+    .with_context(|| format!("Failed to parse {}", list_file))?;
+
+    let selected = profile.or(list.current.as_deref());
+    for item in &list.items {
+        let id = item
+            .uid
+            .as_deref()
+            .or(item.id.as_deref())
+            .unwrap_or_default();
+        let name = item.name.clone().unwrap_or_else(|| id.to_string());
+        let item_type = item.item_type.as_deref().unwrap_or("local");
+        if !matches!(item_type, "remote" | "local") {
+            plan.ignored.push(format!("{} ({})", name, item_type));
+            continue;
+        }
+
+        // mihomo party names profile files after the item ID
+        let file = match &item.file {
+            Some(file) => path.join("profiles").join(file),
+            None => path.join("profiles").join(format!("{}.yaml", id)),
+        };
+        let content = fs::read_to_string(&file).ok();
+
+        match (&item.url, item_type) {
+            (Some(url), "remote") => plan.subscriptions.push(ImportedSubscription {
+                name: name.clone(),
+                url: Some(url.clone()),
+                nodes: content.as_deref().map(count_proxies).unwrap_or(0),
+                skipped: None,
+                content: content.clone(),
+            }),
+            _ => match &content {
+                Some(content) => plan
+                    .subscriptions
+                    .extend(local_subscriptions(&name, content)?),
+                None => plan
+                    .ignored
+                    .push(format!("{} (profile file missing)", name)),
+            },
+        }
+
+        if selected.is_some_and(|s| s == id || s == name) {
+            if let Some(content) = &content {
+                plan.base = Some(build_base(&name, content)?);
+            }
+        }
+    }
+
+    if let (Some(profile), None) = (profile, &plan.base) {
+        anyhow::bail!("Profile '{}' not found", profile);
+    }
+    Ok(plan)
+}
+
+fn parse_mapping(content: &str) -> Result<Mapping> {
+    match serde_yaml::from_str(content).context("Failed to parse profile YAML")? {
+        Value::Mapping(mapping) => Ok(mapping),
+        Value::Null => Ok(Mapping::new()),
+        _ => anyhow::bail!("Profile is not a YAML mapping"),
+    }
+}
+
+fn count_proxies(content: &str) -> usize {
+    parse_mapping(content)
+        .ok()
+        .and_then(|doc| doc.get("proxies")?.as_sequence().map(Vec::len))
+        .unwrap_or(0)
+}
+
+/// A local subscription for the profile's proxies, plus one per HTTP proxy provider
+fn local_subscriptions(name: &str, content: &str) -> Result<Vec<ImportedSubscription>> {
+    let doc = parse_mapping(content)?;
+    let mut subscriptions = Vec::new();
+
+    if let Some(proxies) = doc.get("proxies").and_then(Value::as_sequence) {
+        if !proxies.is_empty() {
+            let mut local = Mapping::new();
+            local.insert("proxies".into(), Value::Sequence(proxies.clone()));
+            subscriptions.push(ImportedSubscription {
+                name: name.to_string(),
+                url: None,
+                nodes: proxies.len(),
+                skipped: None,
+                content: Some(serde_yaml::to_string(&local)?),
+            });
+        }
+    }
+
+    if let Some(providers) = doc.get("proxy-providers").and_then(Value::as_mapping) {
+        for (provider, config) in providers {
+            let url = config.get("url").and_then(Value::as_str);
+            let is_http = config.get("type").and_then(Value::as_str) == Some("http");
+            if let (Some(provider), Some(url), true) = (provider.as_str(), url, is_http) {
+                subscriptions.push(ImportedSubscription {
+                    name: provider.to_string(),
+                    url: Some(url.to_string()),
+                    nodes: 0,
+                    skipped: None,
+                    content: None,
+                });
+            }
+        }
+    }
+    Ok(subscriptions)
+}
+
+/// Split a profile into basic.yml (everything but proxies and groups) and groups.yml.
+/// Group members naming nodes are dropped: Hangar adds its region groups to every group.
+fn build_base(profile: &str, content: &str) -> Result<BaseImport> {
+    let mut doc = parse_mapping(content)?;
+    let node_names: HashSet<String> = doc
+        .get("proxies")
+        .and_then(Value::as_sequence)
+        .map(|proxies| {
+            proxies
+                .iter()
+                .filter_map(|p| p.get("name")?.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    doc.remove("proxies");
+    let had_providers = doc.remove("proxy-providers").is_some();
+
+    let mut groups: Vec<ProxyGroup> = match doc.remove("proxy-groups") {
+        Some(groups) => serde_yaml::from_value(groups).context("Failed to parse proxy-groups")?,
+        None => vec![],
+    };
+    let group_names: HashSet<String> = groups.iter().map(|g| g.name.clone()).collect();
+    let mut dropped_members = 0;
+    for group in &mut groups {
+        let before = group.proxies.len();
+        group.proxies.retain(|member| {
+            group_names.contains(member) || BUILTIN_POLICIES.contains(&member.as_str())
+        });
+        dropped_members += before - group.proxies.len();
+        // Providers become subscriptions, whose nodes reach groups through region groups
+        group.extra.shift_remove("use");
+    }
+
+    let mut warnings = Vec::new();
+    if had_providers {
+        warnings.push("proxy-providers were turned into subscriptions".to_string());
+    }
+    let rules: Vec<String> = doc
+        .get("rules")
+        .cloned()
+        .map(serde_yaml::from_value)
+        .transpose()
+        .context("Failed to parse rules")?
+        .unwrap_or_default();
+    let node_rules = rules
+        .iter()
+        .filter_map(|rule| crate::rules::rule_target(rule))
+        .filter(|target| node_names.contains(target))
+        .count();
+    if node_rules > 0 {
+        warnings.push(format!(
+            "{} rule(s) target single nodes, which are renamed in Hangar; point them at a group",
+            node_rules
+        ));
+    }
+
+    let basic = serde_yaml::to_string(&doc)?;
+    let groups_yaml = serde_yaml::to_string(&groups)?;
+    // Both must load the way merge reads them
+    serde_yaml::from_str::<ClashConfig>(&basic).context("Imported basic.yml is invalid")?;
+
+    Ok(BaseImport {
+        profile: profile.to_string(),
+        basic,
+        groups: groups_yaml,
+        rule_count: rules.len(),
+        group_count: groups.len(),
+        has_dns: doc.contains_key("dns"),
+        dropped_members,
+        kept_settings: vec![],
+        warnings,
+    })
+}
+
+/// Read a client's profiles and check them against the local setup: subscriptions that
+/// already exist are skipped, and settings the base profile lacks are kept from basic.yml
+pub fn plan_import(path: &Path, profile: Option<&str>) -> Result<ClientImportPlan> {
+    let mut plan = read_client(path, profile)?;
+
+    let existing = storage::load_subscriptions()?;
+    let mut names: HashSet<String> = existing.iter().map(|s| s.name.clone()).collect();
+    // Two profiles of the client may point at the same remote URL
+    let mut planned_urls: HashMap<String, String> = HashMap::new();
+    for sub in &mut plan.subscriptions {
+        if let Some(url) = &sub.url {
+            if let Some(other) = existing.iter().find(|s| &s.url == url) {
+                sub.skipped = Some(format!("same URL as '{}'", other.name));
+                continue;
+            }
+            if let Some(other) = planned_urls.get(url) {
+                sub.skipped = Some(format!("same URL as '{}' in this import", other));
+                continue;
+            }
+        }
+        if !names.insert(sub.name.clone()) {
+            sub.skipped = Some("name already in use".to_string());
+            continue;
+        }
+        if let Some(url) = &sub.url {
+            planned_urls.insert(url.clone(), sub.name.clone());
+        }
+    }
+
+    let basic_path = storage::get_basic_config_path()?;
+    if let (Some(base), true) = (&mut plan.base, basic_path.exists()) {
+        let current = parse_mapping(&fs::read_to_string(&basic_path)?)?;
+        let mut doc = parse_mapping(&base.basic)?;
+        for (key, value) in current {
+            let name = key.as_str().unwrap_or_default().to_string();
+            if !doc.contains_key(&key) && !matches!(name.as_str(), "proxies" | "proxy-groups") {
+                doc.insert(key, value);
+                base.kept_settings.push(name);
+            }
+        }
+        base.has_dns = doc.contains_key("dns");
+        base.basic = serde_yaml::to_string(&doc)?;
+    }
+    Ok(plan)
+}
+
+/// A subscription to add, the local proxies file it reads and the content seeding it
+type PlannedSubscription<'a> = (Subscription, Option<PathBuf>, Option<&'a str>);
+
+/// Write the local proxies files and caches, then add all subscriptions in one update.
+/// Paths of written files go to `written`, for cleaning up after a failure.
+fn add_planned(
+    planned: &[PlannedSubscription],
+    written: &mut Vec<PathBuf>,
+) -> Result<Vec<Subscription>> {
+    for (created, local_file, content) in planned {
+        if let Some(file) = local_file {
+            storage::write_atomic(file, content.unwrap_or_default())?;
+            written.push(file.clone());
+        }
+        if let Some(content) = content {
+            written.push(storage::save_proxies_cache(&created.id, content)?);
+        }
+    }
+    storage::modify_subscriptions(|subs| {
+        // The list may have changed since the plan was made
+        for (created, _, _) in planned {
+            if subs.iter().any(|s| s.name == created.name) {
+                anyhow::bail!("Subscription name already in use: '{}'", created.name);
+            }
+            if subs.iter().any(|s| s.url == created.url) {
+                anyhow::bail!("Subscription URL already in use: '{}'", created.name);
+            }
+        }
+        let created: Vec<Subscription> = planned.iter().map(|(s, _, _)| s.clone()).collect();
+        subs.extend(created.iter().cloned());
+        Ok(created)
+    })
+}
+
+/// Create the planned subscriptions (seeding their caches from the profile files) and,
+/// with `install_base`, replace basic.yml and groups.yml, snapshotting them first.
+/// Everything is checked before anything is written, and the subscriptions are added in
+/// one update, so a failure leaves no partial import behind.
+pub fn apply_import(plan: &ClientImportPlan, install_base: bool) -> Result<Vec<Subscription>> {
+    if let (true, Some(base)) = (install_base, &plan.base) {
+        serde_yaml::from_str::<ClashConfig>(&base.basic)
+            .context("Imported basic.yml is invalid")?;
+        serde_yaml::from_str::<Vec<ProxyGroup>>(&base.groups)
+            .context("Imported groups.yml is invalid")?;
+    }
+
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let local_dir = storage::get_local_proxies_dir()?;
+    let mut planned: Vec<PlannedSubscription> = Vec::new();
+    for sub in plan.subscriptions.iter().filter(|s| s.skipped.is_none()) {
+        let (url, local_file) = match &sub.url {
+            Some(url) => (url.clone(), None),
+            None => {
+                let file = local_dir.join(format!("{}.yaml", uuid::Uuid::new_v4()));
+                let url = reqwest::Url::from_file_path(&file)
+                    .map_err(|_| anyhow::anyhow!("Invalid local proxies path: {:?}", file))?
+                    .to_string();
+                (url, Some(file))
+            }
+        };
+        let seeded = sub.content.is_some();
+        planned.push((
+            Subscription {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: sub.name.clone(),
+                url,
+                enabled: true,
+                last_updated: seeded.then(|| now.clone()),
+                node_count: seeded.then_some(sub.nodes),
+                last_error: None,
+            },
+            local_file,
+            sub.content.as_deref(),
+        ));
+    }
+
+    // Snapshots of the files the base replaces are taken first; they are harmless on failure
+    let mut base_files = Vec::new();
+    if let (true, Some(base)) = (install_base, &plan.base) {
+        let description = format!("Before import from {}", plan.client);
+        for (target, path, content) in [
+            ("basic", storage::get_basic_config_path()?, &base.basic),
+            ("groups", storage::get_groups_config_path()?, &base.groups),
+        ] {
+            if path.exists() {
+                let existing = fs::read_to_string(&path)?;
+                if existing != *content {
+                    crate::version::save_version(
+                        target,
+                        VersionSource::Manual,
+                        &description,
+                        &existing,
+                    )?;
+                }
+            }
+            base_files.push((path, content));
+        }
+    }
+
+    let mut written = Vec::new();
+    let created = match add_planned(&planned, &mut written) {
+        Ok(created) => created,
+        Err(e) => {
+            for file in written {
+                let _ = fs::remove_file(file);
+            }
+            return Err(e);
+        }
+    };
+    for (path, content) in base_files {
+        storage::write_atomic(&path, content)?;
+    }
+
+    crate::git_history::record(
+        VersionSource::Manual,
+        &format!("Import from {}", plan.client),
+    );
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+mixed-port: 7897
+dns:
+  enable: true
+proxies:
+  - {name: "🇭🇰 HK 01", type: ss, server: hk.example.com, port: 443, cipher: aes-128-gcm, password: x}
+  - {name: "🇯🇵 JP 01", type: ss, server: jp.example.com, port: 443, cipher: aes-128-gcm, password: x}
+proxy-providers:
+  extra:
+    type: http
+    url: https://provider.example.com/sub
+proxy-groups:
+  - {name: Proxy, type: select, proxies: [Auto, "🇭🇰 HK 01", DIRECT], use: [extra]}
+  - {name: Auto, type: url-test, proxies: ["🇭🇰 HK 01", "🇯🇵 JP 01"], url: "http://www.gstatic.com/generate_204", interval: 300}
+rules:
+  - DOMAIN-SUFFIX,example.com,Proxy
+  - DOMAIN,jp.example.org,🇯🇵 JP 01
+  - MATCH,DIRECT
+"#;
+
+    #[test]
+    fn test_build_base() {
+        let base = build_base("Airport", PROFILE).unwrap();
+        assert_eq!(base.group_count, 2);
+        assert_eq!(base.rule_count, 3);
+        assert!(base.has_dns);
+        assert_eq!(base.dropped_members, 3);
+        assert_eq!(base.warnings.len(), 2);
+
+        assert!(!base.basic.contains("proxies"));
+        assert!(!base.basic.contains("proxy-providers"));
+        let groups: Vec<ProxyGroup> = serde_yaml::from_str(&base.groups).unwrap();
+        assert_eq!(groups[0].proxies, vec!["Auto", "DIRECT"]);
+        assert!(!groups[0].extra.contains_key("use"));
+        assert!(groups[1].proxies.is_empty());
+    }
+
+    #[test]
+    fn test_read_clash_verge_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("profiles")).unwrap();
+        fs::write(
+            dir.path().join("profiles.yaml"),
+            r#"
+current: RlocalA
+items:
+  - {uid: RremoteA, type: remote, name: Airport, file: RremoteA.yaml, url: "https://airport.example.com/sub?token=1"}
+  - {uid: RlocalA, type: local, name: Home, file: RlocalA.yaml}
+  - {uid: Merge, type: merge, name: Merge, file: Merge.yaml}
+"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("profiles/RremoteA.yaml"), PROFILE).unwrap();
+        fs::write(dir.path().join("profiles/RlocalA.yaml"), PROFILE).unwrap();
+
+        let plan = read_client(dir.path(), None).unwrap();
+        assert_eq!(plan.client, ClientKind::ClashVerge);
+        let subs: Vec<(&str, Option<&str>, usize)> = plan
+            .subscriptions
+            .iter()
+            .map(|s| (s.name.as_str(), s.url.as_deref(), s.nodes))
+            .collect();
+        assert_eq!(
+            subs,
+            vec![
+                (
+                    "Airport",
+                    Some("https://airport.example.com/sub?token=1"),
+                    2
+                ),
+                ("Home", None, 2),
+                ("extra", Some("https://provider.example.com/sub"), 0),
+            ]
+        );
+        assert_eq!(plan.base.unwrap().profile, "Home");
+        assert_eq!(plan.ignored, vec!["Merge (merge)"]);
+
+        assert!(read_client(dir.path(), Some("Missing")).is_err());
+        let plan = read_client(dir.path(), Some("Airport")).unwrap();
+        assert_eq!(plan.base.unwrap().profile, "Airport");
+    }
+}
@@ -0,0 +1,442 @@
+use crate::types::{ClashConfig, ProxyGroup, ProxyNode};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Structural diff between two Clash configs, insensitive to key order and re-serialization
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SemanticDiff {
+    pub proxies_added: Vec<String>,
+    pub proxies_removed: Vec<String>,
+    pub proxies_changed: Vec<ProxyChange>,
+    pub groups_added: Vec<String>,
+    pub groups_removed: Vec<String>,
+    pub groups_changed: Vec<GroupChange>,
+    pub rules_added: Vec<RuleChange>,
+    pub rules_removed: Vec<RuleChange>,
+    pub rules_moved: Vec<RuleMove>,
+    /// Base-config and rule-provider values by dotted path, e.g. `dns.enable`
+    pub settings_changed: Vec<SettingChange>,
+}
+
+/// A proxy present in both configs whose fields differ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxyChange {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// A group present in both configs whose members or settings differ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupChange {
+    pub name: String,
+    pub members_added: Vec<String>,
+    pub members_removed: Vec<String>,
+    /// Same members in a different order
+    pub reordered: bool,
+    pub fields: Vec<String>,
+}
+
+/// A rule and its index (in the old config when removed, the new one when added)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleChange {
+    pub index: usize,
+    pub rule: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleMove {
+    pub from: usize,
+    pub to: usize,
+    pub rule: String,
+}
+
+/// `old` is None when the key was added, `new` when it was removed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub old: Option<serde_yaml::Value>,
+    pub new: Option<serde_yaml::Value>,
+}
+
+impl SemanticDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Parse two YAML configs (empty content is an empty config) and diff them
+pub fn diff_yaml(old: &str, new: &str) -> Result<SemanticDiff> {
+    Ok(diff_configs(&parse(old)?, &parse(new)?))
+}
+
+fn parse(content: &str) -> Result<ClashConfig> {
+    if content.trim().is_empty() {
+        return Ok(ClashConfig {
+            base_config: IndexMap::new(),
+            proxies: vec![],
+            proxy_groups: vec![],
+            rules: vec![],
+            rule_providers: None,
+        });
+    }
+    serde_yaml::from_str(content).context("Failed to parse config as a Clash config")
+}
+
+pub fn diff_configs(old: &ClashConfig, new: &ClashConfig) -> SemanticDiff {
+    let mut diff = SemanticDiff::default();
+
+    let (added, removed, common) = diff_named(&old.proxies, &new.proxies, |p| &p.name);
+    diff.proxies_added = added;
+    diff.proxies_removed = removed;
+    diff.proxies_changed = common
+        .into_iter()
+        .filter_map(|(old, new)| {
+            let fields = proxy_fields_changed(old, new);
+            (!fields.is_empty()).then(|| ProxyChange {
+                name: new.name.clone(),
+                fields,
+            })
+        })
+        .collect();
+
+    let (added, removed, common) = diff_named(&old.proxy_groups, &new.proxy_groups, |g| &g.name);
+    diff.groups_added = added;
+    diff.groups_removed = removed;
+    diff.groups_changed = common
+        .into_iter()
+        .filter_map(|(old, new)| group_change(old, new))
+        .collect();
+
+    diff_rules(&old.rules, &new.rules, &mut diff);
+
+    let mut old_settings = IndexMap::new();
+    let mut new_settings = IndexMap::new();
+    for (key, value) in &old.base_config {
+        flatten_setting(key.clone(), value, &mut old_settings);
+    }
+    for (key, value) in &new.base_config {
+        flatten_setting(key.clone(), value, &mut new_settings);
+    }
+    for (name, value) in old.rule_providers.iter().flatten() {
+        flatten_setting(format!("rule-providers.{}", name), value, &mut old_settings);
+    }
+    for (name, value) in new.rule_providers.iter().flatten() {
+        flatten_setting(format!("rule-providers.{}", name), value, &mut new_settings);
+    }
+    for (key, old_value) in &old_settings {
+        match new_settings.get(key) {
+            Some(new_value) if new_value == old_value => {}
+            new_value => diff.settings_changed.push(SettingChange {
+                key: key.clone(),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            }),
+        }
+    }
+    for (key, new_value) in &new_settings {
+        if !old_settings.contains_key(key) {
+            diff.settings_changed.push(SettingChange {
+                key: key.clone(),
+                old: None,
+                new: Some(new_value.clone()),
+            });
+        }
+    }
+
+    diff
+}
+
+/// Names only in `new`, names only in `old`, and the pairs present in both (in new order)
+fn diff_named<'a, T>(
+    old: &'a [T],
+    new: &'a [T],
+    name: impl Fn(&T) -> &String,
+) -> (Vec<String>, Vec<String>, Vec<(&'a T, &'a T)>) {
+    let old_by_name: HashMap<&String, &T> = old.iter().map(|item| (name(item), item)).collect();
+    let new_names: HashSet<&String> = new.iter().map(&name).collect();
+
+    let mut added = Vec::new();
+    let mut common = Vec::new();
+    for item in new {
+        match old_by_name.get(name(item)) {
+            Some(old_item) => common.push((*old_item, item)),
+            None => added.push(name(item).clone()),
+        }
+    }
+    let removed = old
+        .iter()
+        .map(&name)
+        .filter(|n| !new_names.contains(n))
+        .cloned()
+        .collect();
+
+    (added, removed, common)
+}
+
+fn proxy_fields_changed(old: &ProxyNode, new: &ProxyNode) -> Vec<String> {
+    let mut fields = Vec::new();
+    if old.proxy_type != new.proxy_type {
+        fields.push("type".to_string());
+    }
+    if old.server != new.server {
+        fields.push("server".to_string());
+    }
+    if old.port != new.port {
+        fields.push("port".to_string());
+    }
+    if old.region != new.region {
+        fields.push("region".to_string());
+    }
+    fields.extend(extra_fields_changed(&old.extra, &new.extra));
+    fields
+}
+
+fn extra_fields_changed(
+    old: &IndexMap<String, serde_json::Value>,
+    new: &IndexMap<String, serde_json::Value>,
+) -> Vec<String> {
+    let mut fields: Vec<String> = old
+        .iter()
+        .filter(|(key, value)| new.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    fields.extend(new.keys().filter(|key| !old.contains_key(*key)).cloned());
+    fields
+}
+
+fn group_change(old: &ProxyGroup, new: &ProxyGroup) -> Option<GroupChange> {
+    let old_members: HashSet<&String> = old.proxies.iter().collect();
+    let new_members: HashSet<&String> = new.proxies.iter().collect();
+
+    let members_added: Vec<String> = new
+        .proxies
+        .iter()
+        .filter(|m| !old_members.contains(m))
+        .cloned()
+        .collect();
+    let members_removed: Vec<String> = old
+        .proxies
+        .iter()
+        .filter(|m| !new_members.contains(m))
+        .cloned()
+        .collect();
+    // Compare the order of the members both lists share
+    let reordered = old
+        .proxies
+        .iter()
+        .filter(|m| new_members.contains(m))
+        .ne(new.proxies.iter().filter(|m| old_members.contains(m)));
+
+    let mut fields = Vec::new();
+    if old.group_type != new.group_type {
+        fields.push("type".to_string());
+    }
+    fields.extend(extra_fields_changed(&old.extra, &new.extra));
+
+    if members_added.is_empty() && members_removed.is_empty() && !reordered && fields.is_empty() {
+        return None;
+    }
+    Some(GroupChange {
+        name: new.name.clone(),
+        members_added,
+        members_removed,
+        reordered,
+        fields,
+    })
+}
+
+/// Sequence diff of the rule lists; a rule deleted in one place and inserted in another is a move
+fn diff_rules(old: &[String], new: &[String], diff: &mut SemanticDiff) {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for op in similar::capture_diff_slices(similar::Algorithm::Myers, old, new) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            similar::DiffTag::Equal => {}
+            similar::DiffTag::Delete => removed.extend(old_range),
+            similar::DiffTag::Insert => added.extend(new_range),
+            similar::DiffTag::Replace => {
+                removed.extend(old_range);
+                added.extend(new_range);
+            }
+        }
+    }
+
+    for from in removed {
+        let rule = &old[from];
+        match added.iter().position(|&to| new[to] == *rule) {
+            Some(position) => {
+                let to = added.remove(position);
+                diff.rules_moved.push(RuleMove {
+                    from,
+                    to,
+                    rule: rule.clone(),
+                });
+            }
+            None => diff.rules_removed.push(RuleChange {
+                index: from,
+                rule: rule.clone(),
+            }),
+        }
+    }
+    diff.rules_added = added
+        .into_iter()
+        .map(|index| RuleChange {
+            index,
+            rule: new[index].clone(),
+        })
+        .collect();
+}
+
+/// Flatten nested mappings into dotted keys; sequences and scalars are compared whole
+fn flatten_setting(
+    key: String,
+    value: &serde_yaml::Value,
+    out: &mut IndexMap<String, serde_yaml::Value>,
+) {
+    match value {
+        serde_yaml::Value::Mapping(map) if !map.is_empty() => {
+            for (child_key, child) in map {
+                let child_key = match child_key {
+                    serde_yaml::Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                flatten_setting(format!("{}.{}", key, child_key), child, out);
+            }
+        }
+        _ => {
+            out.insert(key, value.clone());
+        }
+    }
+}
+
+/// One-line YAML rendering of a setting value
+pub fn format_value(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(s) => s.clone(),
+        serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) => {
+            serde_json::to_string(value).unwrap_or_default()
+        }
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"
+mixed-port: 7890
+dns:
+  enable: true
+  nameserver: [223.5.5.5]
+proxies:
+  - {name: HK 01, type: ss, server: hk1.example.com, port: 443, cipher: aes-128-gcm}
+  - {name: US 01, type: ss, server: us1.example.com, port: 443}
+  - {name: JP 01, type: trojan, server: jp1.example.com, port: 443}
+proxy-groups:
+  - {name: Proxy, type: select, proxies: [HK 01, US 01, JP 01]}
+  - {name: Old, type: select, proxies: [DIRECT]}
+rules:
+  - DOMAIN-SUFFIX,a.com,Proxy
+  - DOMAIN-SUFFIX,b.com,DIRECT
+  - DOMAIN-SUFFIX,c.com,Proxy
+  - MATCH,Proxy
+"#;
+
+    #[test]
+    fn test_reserialized_config_has_no_changes() {
+        let config: ClashConfig = serde_yaml::from_str(OLD).unwrap();
+        let reserialized = serde_yaml::to_string(&config).unwrap();
+        assert!(diff_yaml(OLD, &reserialized).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_semantic_diff() {
+        let new = r#"
+dns:
+  nameserver: [223.5.5.5]
+  enable: false
+mixed-port: 7890
+ipv6: true
+proxies:
+  - {name: JP 01, type: trojan, server: jp1.example.com, port: 443}
+  - {name: HK 01, type: ss, server: hk1.example.com, port: 8443, cipher: aes-256-gcm}
+  - {name: SG 01, type: ss, server: sg1.example.com, port: 443}
+proxy-groups:
+  - {name: Proxy, type: select, proxies: [JP 01, HK 01, SG 01]}
+rules:
+  - DOMAIN-SUFFIX,c.com,Proxy
+  - DOMAIN-SUFFIX,a.com,Proxy
+  - DOMAIN-SUFFIX,d.com,DIRECT
+  - MATCH,Proxy
+"#;
+        let diff = diff_yaml(OLD, new).unwrap();
+
+        assert_eq!(diff.proxies_added, vec!["SG 01"]);
+        assert_eq!(diff.proxies_removed, vec!["US 01"]);
+        assert_eq!(
+            diff.proxies_changed,
+            vec![ProxyChange {
+                name: "HK 01".to_string(),
+                fields: vec!["port".to_string(), "cipher".to_string()],
+            }]
+        );
+
+        assert!(diff.groups_added.is_empty());
+        assert_eq!(diff.groups_removed, vec!["Old"]);
+        assert_eq!(
+            diff.groups_changed,
+            vec![GroupChange {
+                name: "Proxy".to_string(),
+                members_added: vec!["SG 01".to_string()],
+                members_removed: vec!["US 01".to_string()],
+                reordered: true,
+                fields: vec![],
+            }]
+        );
+
+        assert_eq!(
+            diff.rules_moved,
+            vec![RuleMove {
+                from: 2,
+                to: 0,
+                rule: "DOMAIN-SUFFIX,c.com,Proxy".to_string(),
+            }]
+        );
+        assert_eq!(
+            diff.rules_removed,
+            vec![RuleChange {
+                index: 1,
+                rule: "DOMAIN-SUFFIX,b.com,DIRECT".to_string(),
+            }]
+        );
+        assert_eq!(
+            diff.rules_added,
+            vec![RuleChange {
+                index: 2,
+                rule: "DOMAIN-SUFFIX,d.com,DIRECT".to_string(),
+            }]
+        );
+
+        let keys: Vec<&str> = diff
+            .settings_changed
+            .iter()
+            .map(|c| c.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["dns.enable", "ipv6"]);
+        assert_eq!(
+            diff.settings_changed[0].new,
+            Some(serde_yaml::Value::Bool(false))
+        );
+        assert_eq!(diff.settings_changed[1].old, None);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::server::AppState;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+
+/// Arguments of the last `hangar serve`, reused by `hangar restart` and `hangar status`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServeArgs {
+    pub host: String,
+    pub port: u16,
+    pub interval: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<String>,
+    /// Serve beyond this machine even when no access token exists
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_unauthenticated: bool,
+}
+
+impl ServeArgs {
+    /// `serve` arguments reproducing these settings
+    pub fn to_cli_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "serve".to_string(),
+            "--host".to_string(),
+            self.host.clone(),
+            "--port".to_string(),
+            self.port.to_string(),
+            "--interval".to_string(),
+            self.interval.to_string(),
+        ];
+        if let (Some(cert), Some(key)) = (&self.tls_cert, &self.tls_key) {
+            args.extend([
+                "--tls-cert".to_string(),
+                cert.clone(),
+                "--tls-key".to_string(),
+                key.clone(),
+            ]);
+        }
+        if self.allow_unauthenticated {
+            args.push("--allow-unauthenticated".to_string());
+        }
+        args
+    }
+
+    /// TLS the server runs with: the `--tls-cert`/`--tls-key` pair, else `server.tls`
+    pub fn tls(&self) -> Result<Option<crate::types::TlsConfig>> {
+        Ok(match (&self.tls_cert, &self.tls_key) {
+            (Some(cert_path), Some(key_path)) => Some(crate::types::TlsConfig {
+                cert_path: cert_path.clone(),
+                key_path: key_path.clone(),
+            }),
+            _ => crate::storage::load_hangar_config()?.server.tls,
+        })
+    }
+}
+
+pub fn save_serve_args(args: &ServeArgs) -> Result<()> {
+    let path = crate::storage::get_server_args_path()?;
+    let content = serde_json::to_string_pretty(args)?;
+    crate::storage::write_atomic(&path, content).context("Failed to write server.json")
+}
+
+pub fn load_serve_args() -> Result<Option<ServeArgs>> {
+    let path = crate::storage::get_server_args_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path).context("Failed to read server.json")?;
+    Ok(Some(
+        serde_json::from_str(&content).context("Failed to parse server.json")?,
+    ))
+}
+
+/// Exclusive lock on the PID file, held for the server's lifetime.
+/// The kernel drops the lock when the process exits, so a leftover file is never mistaken for a live server.
+/// The file itself is never removed: another `serve` may already have it open, and unlinking it
+/// would let that one lock an orphaned inode while a third creates and locks a new file.
+pub struct PidLock {
+    file: File,
+}
+
+/// How long `PidLock::acquire` waits out a `running_pid` probe holding the lock
+const LOCK_ATTEMPTS: u32 = 10;
+const LOCK_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+impl PidLock {
+    /// Lock `path` and write this process's PID into it
+    pub fn acquire(path: &Path) -> Result<Self> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+
+        let mut attempts = 1;
+        while !try_lock(&file, LockMode::Exclusive)? {
+            if attempts == LOCK_ATTEMPTS {
+                let pid = std::fs::read_to_string(path).unwrap_or_default();
+                anyhow::bail!(
+                    "Another hangar server is already running (PID: {})",
+                    pid.trim()
+                );
+            }
+            std::thread::sleep(LOCK_RETRY_DELAY);
+            attempts += 1;
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for PidLock {
+    fn drop(&mut self) {
+        // Emptied rather than removed, still under the lock
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum LockMode {
+    /// Taken by the server for its lifetime
+    Exclusive,
+    /// Taken briefly by `running_pid` to probe for a holder
+    Shared,
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File, mode: LockMode) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = match mode {
+        LockMode::Exclusive => libc::LOCK_EX,
+        LockMode::Shared => libc::LOCK_SH,
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err).context("Failed to lock PID file")
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File, _mode: LockMode) -> Result<bool> {
+    Ok(true)
+}
+
+/// PID of the server holding the lock on `path`. A stale file is left in place: a starting
+/// server creates it before locking it, and `PidLock` rewrites it anyway
+pub fn running_pid(path: &Path) -> Result<Option<i32>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let pid = std::fs::read_to_string(path)?.trim().parse::<i32>().ok();
+
+    #[cfg(unix)]
+    {
+        // A shared probe, so concurrent `status` calls don't fail each other
+        let file = File::open(path)?;
+        if try_lock(&file, LockMode::Shared)? {
+            // Nobody holds it: the server is gone (or the PID was reused by another process)
+            return Ok(None);
+        }
+    }
+
+    Ok(pid)
+}
+
+/// Server signal handling: SIGHUP re-reads subscriptions and re-merges,
+/// SIGTERM and Ctrl-C shut the server down gracefully
+pub struct ServerSignals {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl ServerSignals {
+    /// Register before the startup merge, so an early SIGHUP doesn't kill the process
+    pub fn register() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                hangup: signal(SignalKind::hangup()).context("Failed to handle SIGHUP")?,
+                terminate: signal(SignalKind::terminate()).context("Failed to handle SIGTERM")?,
+            })
+        }
+
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Handle signals for `state`; `shutdown` fires on SIGTERM or Ctrl-C
+    pub fn spawn(self, state: AppState, shutdown: oneshot::Sender<()>) {
+        #[cfg(unix)]
+        let Self {
+            mut hangup,
+            mut terminate,
+        } = self;
+
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            loop {
+                tokio::select! {
+                    _ = hangup.recv() => {
+                        info!("🔁 收到 SIGHUP，重新读取订阅并合并...");
+                        crate::service::notify_reloading();
+                        match remerge(&state).await {
+                            Ok(_) => info!("✅ 重新合并完成"),
+                            Err(e) => error!("❌ 重新合并失败: {}", e),
+                        }
+                        crate::service::notify_ready("Reloaded");
+                    }
+                    _ = terminate.recv() => break,
+                    _ = tokio::signal::ctrl_c() => break,
+                }
+            }
+
+            #[cfg(not(unix))]
+            {
+                let _ = state;
+                let _ = tokio::signal::ctrl_c().await;
+            }
+
+            let _ = shutdown.send(());
+        });
+    }
+}
+
+async fn remerge(state: &AppState) -> Result<()> {
+    let merged = crate::proxy::regenerate_current_config().await?;
+    state.set_config(merged).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_args_cli() {
+        let args = ServeArgs {
+            host: "0.0.0.0".to_string(),
+            port: 9090,
+            interval: 300,
+            tls_cert: None,
+            tls_key: None,
+            allow_unauthenticated: true,
+        };
+        assert_eq!(
+            args.to_cli_args(),
+            vec![
+                "serve",
+                "--host",
+                "0.0.0.0",
+                "--port",
+                "9090",
+                "--interval",
+                "300",
+                "--allow-unauthenticated"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pid_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.pid");
+
+        // A leftover file without a lock holder is stale and left in place
+        std::fs::write(&path, "999999").unwrap();
+        assert_eq!(running_pid(&path).unwrap(), None);
+        assert!(path.exists());
+
+        let lock = PidLock::acquire(&path).unwrap();
+        assert_eq!(running_pid(&path).unwrap(), Some(std::process::id() as i32));
+        assert!(PidLock::acquire(&path).is_err());
+
+        drop(lock);
+        assert_eq!(running_pid(&path).unwrap(), None);
+        assert!(path.exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        // A server starting during a `running_pid` probe waits for the probe to finish
+        let probe = File::open(&path).unwrap();
+        assert!(try_lock(&probe, LockMode::Shared).unwrap());
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            drop(probe);
+        });
+        let lock = PidLock::acquire(&path).unwrap();
+        release.join().unwrap();
+        assert_eq!(running_pid(&path).unwrap(), Some(std::process::id() as i32));
+        drop(lock);
+    }
+}
@@ -0,0 +1,156 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before it starts missing some
+const CHANNEL_CAPACITY: usize = 256;
+
+lazy_static! {
+    static ref EVENTS: broadcast::Sender<EventRecord> = broadcast::channel(CHANNEL_CAPACITY).0;
+}
+
+/// Something the daemon did, streamed on `/events`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    RefreshStarted {
+        subscription: String,
+    },
+    RefreshFinished {
+        subscription: String,
+        node_count: Option<usize>,
+        error: Option<String>,
+    },
+    MergeFinished {
+        proxies: usize,
+        proxy_groups: usize,
+        rules: usize,
+        warnings: usize,
+    },
+    /// current.yaml changed on disk and was reloaded
+    ConfigReloaded {
+        changed: bool,
+    },
+    ValidationWarning {
+        message: String,
+    },
+    Notification {
+        title: String,
+        message: String,
+        severity: String,
+    },
+}
+
+impl DaemonEvent {
+    /// SSE event name, same as the `type` field
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::RefreshStarted { .. } => "refresh_started",
+            Self::RefreshFinished { .. } => "refresh_finished",
+            Self::MergeFinished { .. } => "merge_finished",
+            Self::ConfigReloaded { .. } => "config_reloaded",
+            Self::ValidationWarning { .. } => "validation_warning",
+            Self::Notification { .. } => "notification",
+        }
+    }
+
+    /// Redact URLs in free text, which may quote subscription URLs with their tokens
+    fn redacted(self) -> Self {
+        use crate::subscription::redact_urls;
+        match self {
+            Self::RefreshFinished {
+                subscription,
+                node_count,
+                error,
+            } => Self::RefreshFinished {
+                subscription,
+                node_count,
+                error: error.as_deref().map(redact_urls),
+            },
+            Self::ValidationWarning { message } => Self::ValidationWarning {
+                message: redact_urls(&message),
+            },
+            Self::Notification {
+                title,
+                message,
+                severity,
+            } => Self::Notification {
+                title,
+                message: redact_urls(&message),
+                severity,
+            },
+            other => other,
+        }
+    }
+}
+
+/// A published event with its timestamp (unix seconds)
+#[derive(Debug, Clone, Serialize)]
+pub struct EventRecord {
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub event: DaemonEvent,
+}
+
+/// Send an event to every current subscriber (dropped when nobody listens)
+pub fn publish(event: DaemonEvent) {
+    let _ = EVENTS.send(EventRecord {
+        timestamp: chrono::Utc::now().timestamp(),
+        event: event.redacted(),
+    });
+}
+
+/// Receive events published from now on
+pub fn subscribe() -> broadcast::Receiver<EventRecord> {
+    EVENTS.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json_is_tagged() {
+        let event = DaemonEvent::RefreshFinished {
+            subscription: "Home".to_string(),
+            node_count: Some(12),
+            error: None,
+        };
+        assert_eq!(event.kind(), "refresh_finished");
+
+        let json = serde_json::to_value(EventRecord {
+            timestamp: 1,
+            event,
+        })
+        .unwrap();
+        assert_eq!(json["type"], "refresh_finished");
+        assert_eq!(json["subscription"], "Home");
+        assert_eq!(json["node_count"], 12);
+        assert_eq!(json["timestamp"], 1);
+    }
+
+    #[test]
+    fn test_event_errors_are_redacted() {
+        let event = DaemonEvent::RefreshFinished {
+            subscription: "Home".to_string(),
+            node_count: None,
+            error: Some(
+                "error sending request for url (https://sub.example.com/s?token=secret)"
+                    .to_string(),
+            ),
+        }
+        .redacted();
+
+        let DaemonEvent::RefreshFinished {
+            error: Some(error), ..
+        } = event
+        else {
+            panic!("unexpected event");
+        };
+        assert!(!error.contains("secret"));
+        assert!(
+            error.starts_with("error sending request for url (https://sub.example.com/…#sha256=")
+        );
+        assert!(error.ends_with(')'));
+    }
+}
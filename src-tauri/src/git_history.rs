@@ -0,0 +1,380 @@
+use crate::storage;
+use crate::types::{Subscription, VersionSource};
+use anyhow::{Context, Result};
+use git2::{IndexAddOption, Repository, Signature};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Files tracked in the history repository, by name in its worktree
+pub const TRACKED_FILES: &[&str] = &[
+    "basic.yml",
+    "groups.yml",
+    "rules.local.yml",
+    "subscriptions.json",
+    "current.yaml",
+];
+
+/// A local git repository holding copies of the ~/.hangar configuration files
+pub struct HistoryRepo {
+    repo: Repository,
+}
+
+/// One commit of `hangar history git log`
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitInfo {
+    pub id: String,
+    pub timestamp: i64,
+    pub summary: String,
+    /// `Hangar-Source:` trailer of the message
+    pub source: Option<String>,
+    pub files: Vec<String>,
+}
+
+impl HistoryRepo {
+    pub fn open_or_init(dir: &Path) -> Result<Self> {
+        let repo = match Repository::open(dir) {
+            Ok(repo) => repo,
+            Err(_) => {
+                std::fs::create_dir_all(dir)?;
+                Repository::init(dir)
+                    .with_context(|| format!("Failed to init git repository at {:?}", dir))?
+            }
+        };
+        Ok(Self { repo })
+    }
+
+    fn workdir(&self) -> Result<&Path> {
+        self.repo
+            .workdir()
+            .context("History repository has no working directory")
+    }
+
+    /// Write `files` into the worktree (None deletes the file) and commit them.
+    /// Returns None when nothing changed.
+    pub fn commit(
+        &self,
+        files: &[(&str, Option<String>)],
+        source: VersionSource,
+        message: &str,
+    ) -> Result<Option<git2::Oid>> {
+        let workdir = self.workdir()?.to_path_buf();
+        for (name, content) in files {
+            let path = workdir.join(name);
+            match content {
+                Some(content) => storage::write_atomic(&path, content)?,
+                None if path.exists() => std::fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+
+        let mut index = self.repo.index()?;
+        let names: Vec<&str> = files.iter().map(|(name, _)| *name).collect();
+        index.add_all(names.iter(), IndexAddOption::DEFAULT, None)?;
+        index.update_all(names.iter(), None)?;
+        index.write()?;
+        let tree_id = index.write_tree()?;
+
+        let parent = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        if parent.as_ref().map(|p| p.tree_id()) == Some(tree_id) {
+            return Ok(None);
+        }
+
+        let tree = self.repo.find_tree(tree_id)?;
+        let signature = Signature::now("hangar", "hangar@localhost")?;
+        let message = format!("{}\n\nHangar-Source: {}\n", message.trim(), source);
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let oid = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )?;
+        Ok(Some(oid))
+    }
+
+    /// The newest `limit` commits, newest first
+    pub fn log(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        let mut walk = self.repo.revwalk()?;
+        if walk.push_head().is_err() {
+            return Ok(vec![]);
+        }
+
+        let mut commits = Vec::new();
+        for oid in walk.take(limit) {
+            let commit = self.repo.find_commit(oid?)?;
+            let message = commit.message().unwrap_or_default();
+            let source = message
+                .lines()
+                .find_map(|l| l.strip_prefix("Hangar-Source: "))
+                .map(str::to_string);
+
+            let tree = commit.tree()?;
+            let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+            let diff = self
+                .repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+            let files = diff
+                .deltas()
+                .filter_map(|d| d.new_file().path().or_else(|| d.old_file().path()))
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+
+            commits.push(CommitInfo {
+                id: commit.id().to_string(),
+                timestamp: commit.time().seconds(),
+                summary: commit
+                    .summary()
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default()
+                    .to_string(),
+                source,
+                files,
+            });
+        }
+        Ok(commits)
+    }
+}
+
+/// Subscriptions without secrets or refresh state: URLs keep only scheme and host,
+/// plus a hash so URL changes still show up in diffs
+pub fn redact_subscriptions(subscriptions: &[Subscription]) -> String {
+    let redacted: Vec<serde_json::Value> = subscriptions
+        .iter()
+        .map(|sub| {
+            serde_json::json!({
+                "id": sub.id,
+                "name": sub.name,
+                "url": crate::subscription::redact_url(&sub.url),
+                "enabled": sub.enabled,
+            })
+        })
+        .collect();
+    let mut content = serde_json::to_string_pretty(&redacted).unwrap_or_default();
+    content.push('\n');
+    content
+}
+
+/// Keys of proxy entries (including nested `plugin-opts`, `ws-opts`...) holding credentials
+const SECRET_KEYS: &[&str] = &[
+    "password",
+    "uuid",
+    "private-key",
+    "pre-shared-key",
+    "psk",
+    "auth",
+    "auth-str",
+    "obfs-password",
+    "token",
+    "passphrase",
+];
+
+fn redact_secrets(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            for (key, value) in map.iter_mut() {
+                if key.as_str().is_some_and(|k| SECRET_KEYS.contains(&k)) {
+                    *value = serde_yaml::Value::String("redacted".to_string());
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// The merged config without credentials: proxy passwords, UUIDs and keys, the
+/// controller `secret`, `authentication` users and proxy provider URLs
+pub fn redact_current_config(content: &str) -> String {
+    let mut config: serde_yaml::Value = match serde_yaml::from_str(content) {
+        Ok(config) => config,
+        Err(_) => return "# current.yaml could not be parsed and is not committed\n".to_string(),
+    };
+    if let Some(map) = config.as_mapping_mut() {
+        for key in ["secret", "authentication"] {
+            if let Some(value) = map.get_mut(key) {
+                *value = serde_yaml::Value::String("redacted".to_string());
+            }
+        }
+        if let Some(proxies) = map.get_mut("proxies") {
+            redact_secrets(proxies);
+        }
+        if let Some(providers) = map
+            .get_mut("proxy-providers")
+            .and_then(|p| p.as_mapping_mut())
+        {
+            for (_, provider) in providers.iter_mut() {
+                if let Some(url) = provider.get_mut("url") {
+                    if let Some(redacted) = url.as_str().map(crate::subscription::redact_url) {
+                        *url = serde_yaml::Value::String(redacted);
+                    }
+                }
+            }
+        }
+    }
+    serde_yaml::to_string(&config).unwrap_or_default()
+}
+
+/// Current content of each tracked file (None if it doesn't exist)
+fn tracked_contents() -> Result<Vec<(&'static str, Option<String>)>> {
+    let hangar_dir = storage::get_hangar_dir()?;
+    let mut files = Vec::new();
+    for name in TRACKED_FILES {
+        let content = if *name == "subscriptions.json" {
+            Some(redact_subscriptions(
+                &storage::load_subscriptions().unwrap_or_default(),
+            ))
+        } else {
+            let path = hangar_dir.join(name);
+            let content = path
+                .exists()
+                .then(|| std::fs::read_to_string(&path))
+                .transpose()?;
+            if *name == "current.yaml" {
+                content.map(|c| redact_current_config(&c))
+            } else {
+                content
+            }
+        };
+        files.push((*name, content));
+    }
+    Ok(files)
+}
+
+pub fn repo_dir() -> Result<PathBuf> {
+    Ok(storage::get_hangar_dir()?.join("history"))
+}
+
+/// Commit the tracked files to the history repository
+pub fn commit_current(source: VersionSource, message: &str) -> Result<Option<git2::Oid>> {
+    let repo = HistoryRepo::open_or_init(&repo_dir()?)?;
+    repo.commit(&tracked_contents()?, source, message)
+}
+
+/// Commit the tracked files if `history.git` is enabled; failures are logged, not returned
+pub fn record(source: VersionSource, message: &str) {
+    let enabled = storage::load_hangar_config()
+        .map(|c| c.history.git)
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+    if let Err(e) = commit_current(source, message) {
+        warn!("⚠️ Failed to commit to the history repository: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_and_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = HistoryRepo::open_or_init(dir.path()).unwrap();
+
+        let first = repo
+            .commit(
+                &[
+                    ("basic.yml", Some("mixed-port: 7890\n".to_string())),
+                    ("groups.yml", None),
+                ],
+                VersionSource::Manual,
+                "initial",
+            )
+            .unwrap();
+        assert!(first.is_some());
+
+        // Same content again: no commit
+        let unchanged = repo
+            .commit(
+                &[("basic.yml", Some("mixed-port: 7890\n".to_string()))],
+                VersionSource::Merge,
+                "no-op",
+            )
+            .unwrap();
+        assert!(unchanged.is_none());
+
+        repo.commit(
+            &[
+                ("basic.yml", None),
+                ("current.yaml", Some("rules: []\n".to_string())),
+            ],
+            VersionSource::Ai,
+            "use DIRECT",
+        )
+        .unwrap()
+        .unwrap();
+
+        // Reopening finds the same repository
+        let repo = HistoryRepo::open_or_init(dir.path()).unwrap();
+        let log = repo.log(10).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].summary, "use DIRECT");
+        assert_eq!(log[0].source.as_deref(), Some("ai"));
+        assert_eq!(log[0].files, vec!["basic.yml", "current.yaml"]);
+        assert_eq!(log[1].summary, "initial");
+        assert_eq!(log[1].files, vec!["basic.yml"]);
+        assert!(!dir.path().join("basic.yml").exists());
+    }
+
+    #[test]
+    fn test_redact_subscriptions() {
+        let subs = vec![Subscription {
+            id: "1".to_string(),
+            name: "Home".to_string(),
+            url: "https://sub.example.com/api/v1/client/subscribe?token=secret".to_string(),
+            enabled: true,
+            last_updated: Some("2026-01-01T00:00:00Z".to_string()),
+            node_count: Some(12),
+            last_error: None,
+        }];
+        let content = redact_subscriptions(&subs);
+        assert!(!content.contains("secret"));
+        assert!(!content.contains("node_count"));
+        assert!(content.contains("\"url\": \"https://sub.example.com/…#sha256="));
+    }
+
+    #[test]
+    fn test_redact_current_config() {
+        let content = r#"
+mixed-port: 7890
+secret: controller-secret
+proxies:
+  - {name: HK, type: ss, server: hk.example.com, port: 1, cipher: aes-128-gcm, password: ss-pass}
+  - name: US
+    type: vmess
+    server: us.example.com
+    port: 2
+    uuid: 5a1b2c3d-0000-0000-0000-000000000000
+    ws-opts: {path: /ws, headers: {Host: us.example.com}}
+  - {name: JP, type: trojan, server: jp.example.com, port: 3, password: trojan-pass, plugin-opts: {password: shadow-tls-pass}}
+proxy-providers:
+  remote: {type: http, url: "https://sub.example.com/sub?token=provider-token", interval: 3600}
+rules:
+  - MATCH,HK
+"#;
+        let redacted = redact_current_config(content);
+        for secret in [
+            "controller-secret",
+            "ss-pass",
+            "5a1b2c3d",
+            "trojan-pass",
+            "shadow-tls-pass",
+            "provider-token",
+        ] {
+            assert!(!redacted.contains(secret), "{} leaked", secret);
+        }
+        assert!(redacted.contains("hk.example.com"));
+        assert!(redacted.contains("MATCH,HK"));
+        assert!(redacted.contains("path: /ws"));
+    }
+}
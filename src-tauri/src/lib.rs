@@ -1,18 +1,35 @@
 pub mod ai;
+pub mod api;
+pub mod auth;
+pub mod backup;
+pub mod chain;
+pub mod client_import;
 pub mod config;
+pub mod config_diff;
+pub mod daemon;
+pub mod events;
+pub mod git_history;
+pub mod logging;
+pub mod merge3;
+pub mod metrics;
 pub mod notifications;
+pub mod profile;
 pub mod proxy;
 pub mod rules;
 pub mod server;
+pub mod service;
 pub mod storage;
 pub mod subscription;
+pub mod templates;
+pub mod tls;
 pub mod types;
 pub mod version;
 
-use config::{load_app_config, save_config};
-use proxy::merge_configs;
+#[cfg(feature = "gui")]
 use server::start_server;
+#[cfg(feature = "gui")]
 use std::sync::Arc;
+#[cfg(feature = "gui")]
 use tokio::sync::{oneshot, Mutex};
 
 #[cfg(feature = "gui")]
@@ -22,137 +39,49 @@ lazy_static::lazy_static! {
     static ref SERVER_SHUTDOWN: Arc<Mutex<Option<oneshot::Sender<()>>>> = Arc::new(Mutex::new(None));
 }
 
-#[cfg(feature = "gui")]
-// 获取配置文件路径
-fn get_config_path(app_handle: &tauri::AppHandle) -> Result<String, String> {
-    // 先尝试当前目录（开发模式）
-    let dev_paths = vec![
-        "subscriptions.json",
-        "../subscriptions.json",
-        "../../subscriptions.json",
-    ];
-
-    for path in &dev_paths {
-        if std::path::Path::new(path).exists() {
-            eprintln!("✓ 使用开发模式配置: {}", path);
-            return Ok(path.to_string());
-        }
-    }
-
-    // 生产模式：使用应用数据目录
-    use tauri::Manager;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("无法获取应用目录: {}", e))?;
-
-    // 确保目录存在
-    std::fs::create_dir_all(&app_dir).map_err(|e| format!("无法创建应用目录: {}", e))?;
-
-    let config_file = app_dir.join("subscriptions.json");
-
-    // 如果配置文件不存在，创建默认配置
-    if !config_file.exists() {
-        let default_config = r#"{
-  "subscriptions": [],
-  "server": {
-    "port": 8080,
-    "host": "127.0.0.1"
-  },
-  "output": {
-    "path": "output_config.yaml"
-  },
-  "basic_config": {
-    "path": "basic_test.yml"
-  }
-}"#;
-        std::fs::write(&config_file, default_config)
-            .map_err(|e| format!("无法创建默认配置: {}", e))?;
-        eprintln!("✓ 创建默认配置文件: {}", config_file.display());
-    }
-
-    Ok(config_file.to_string_lossy().to_string())
-}
-
 #[cfg(feature = "gui")]
 #[tauri::command]
-async fn start_proxy_server(app_handle: tauri::AppHandle) -> Result<String, String> {
-    eprintln!("🔍 start_proxy_server 被调用");
+async fn start_proxy_server() -> Result<String, String> {
+    tracing::debug!("🔍 start_proxy_server 被调用");
 
     let mut running = SERVER_RUNNING.lock().await;
 
     if *running {
-        eprintln!("⚠️  服务器已在运行中");
+        tracing::warn!("⚠️ 服务器已在运行中");
         return Ok("服务器已在运行中".to_string());
     }
 
-    // 获取配置文件路径
-    let config_path = get_config_path(&app_handle)?;
-    eprintln!("✓ 使用配置文件: {}", config_path);
+    // 与 CLI `hangar serve` 使用相同的 ~/.hangar 配置和订阅
+    let server_config = storage::load_hangar_config()
+        .map_err(|e| format!("❌ 无法加载配置文件: {}", e))?
+        .server;
 
-    let app_config = load_app_config(&config_path).map_err(|e| {
-        let err = format!("❌ 无法加载配置文件: {}\n\n路径: {}", e, config_path);
-        eprintln!("{}", err);
-        err
-    })?;
+    tracing::info!("✓ 开始合并配置...");
 
-    eprintln!(
-        "✓ 配置加载成功，订阅数量: {}",
-        app_config.subscriptions.len()
-    );
-
-    // Check for existence of basic config only for UX warning
-    let basic_config_paths = vec![
-        app_config.basic_config.path.clone(),
-        "_docs/basic.yml".to_string(),
-        "../_docs/basic.yml".to_string(),
-        "basic_test.yml".to_string(),
-    ];
-
-    let mut basic_exists = false;
-    for path in &basic_config_paths {
-        if std::path::Path::new(path).exists() {
-            basic_exists = true;
-            break;
-        }
-    }
-
-    if !basic_exists {
-        eprintln!("⚠️  Suggested basic config paths not found, merge might fail if resources are missing.");
-    }
-
-    eprintln!("✓ 开始合并配置...");
-
-    // 合并配置
-    let merged_config = merge_configs(&app_config.subscriptions)
-        .await
-        .map_err(|e| {
-            let err = format!("❌ 合并配置失败: {}", e);
-            eprintln!("{}", err);
-            err
-        })?;
-
-    eprintln!("✓ 配置合并成功");
-
-    // 保存配置
-    save_config(&merged_config, &app_config.output.path).map_err(|e| {
-        let err = format!("❌ 保存配置失败: {}", e);
-        eprintln!("{}", err);
+    let state = server::AppState::load_initial().await.map_err(|e| {
+        let err = format!("❌ 合并配置失败: {}", e);
+        tracing::error!("{}", err);
         err
     })?;
 
-    eprintln!("✓ 配置已保存到: {}", app_config.output.path);
+    tracing::info!("✓ 配置合并成功");
 
-    let host = app_config.server.host.clone();
-    let port = app_config.server.port;
+    let options = server::ServeOptions {
+        host: server_config.host.clone(),
+        port: server_config.port,
+        interval: 0,
+        tls: server_config.tls.clone(),
+        allow_unauthenticated: false,
+    };
+    let config_url = format!(
+        "{}://{}:{}/config",
+        server::scheme(&options.tls),
+        options.host,
+        options.port
+    );
 
     *running = true;
 
-    // 克隆 host 用于返回消息
-    let host_for_message = host.clone();
-
-    eprintln!("✓ 即将启动服务器: {}:{}", host, port);
-
     // 创建关闭通道
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
@@ -163,50 +92,53 @@ async fn start_proxy_server(app_handle: tauri::AppHandle) -> Result<String, Stri
 
     // 在后台启动服务器
     tokio::spawn(async move {
-        eprintln!("🚀 服务器任务已启动");
-        match start_server(merged_config, &host, port, shutdown_rx).await {
+        tracing::info!("🚀 服务器任务已启动");
+        match start_server(state, options, shutdown_rx).await {
             Ok(_) => {
-                eprintln!("✓ 服务器正常停止");
+                tracing::info!("✓ 服务器正常停止");
             }
             Err(e) => {
-                eprintln!("❌ 服务器错误: {}", e);
-                eprintln!("❌ 错误详情: {:?}", e);
+                tracing::error!("❌ 服务器错误: {}", e);
+                tracing::error!("❌ 错误详情: {:?}", e);
             }
         }
 
         // 无论如何都要更新运行状态
         let mut running = SERVER_RUNNING.lock().await;
         *running = false;
-        eprintln!("✓ 服务器状态已更新为停止");
+        tracing::info!("✓ 服务器状态已更新为停止");
     });
 
-    eprintln!("✅ 服务器启动命令执行成功");
+    tracing::info!("✅ 服务器启动命令执行成功");
 
-    Ok(format!("✅ 服务器已启动\n\n📍 订阅链接: http://{}:{}/config\n\n💡 在 Clash Verge 中添加此链接即可使用", host_for_message, port))
+    Ok(format!(
+        "✅ 服务器已启动\n\n📍 订阅链接: {}\n\n💡 在 Clash Verge 中添加此链接即可使用",
+        config_url
+    ))
 }
 
 #[cfg(feature = "gui")]
 #[tauri::command]
 async fn stop_proxy_server() -> Result<String, String> {
-    eprintln!("🔍 stop_proxy_server 被调用");
+    tracing::debug!("🔍 stop_proxy_server 被调用");
 
     let mut running = SERVER_RUNNING.lock().await;
 
     if !*running {
-        eprintln!("⚠️  服务器未运行");
+        tracing::warn!("⚠️ 服务器未运行");
         return Ok("服务器未运行".to_string());
     }
 
     // 获取关闭发送端并发送关闭信号
     let mut shutdown_sender = SERVER_SHUTDOWN.lock().await;
     if let Some(tx) = shutdown_sender.take() {
-        eprintln!("✓ 发送关闭信号");
+        tracing::info!("✓ 发送关闭信号");
         let _ = tx.send(()); // 忽略发送错误（接收端可能已关闭）
     }
 
     *running = false;
 
-    eprintln!("✅ 服务器停止命令执行成功");
+    tracing::info!("✅ 服务器停止命令执行成功");
 
     Ok("✅ 服务器已停止".to_string())
 }
@@ -221,41 +153,9 @@ async fn get_server_status() -> Result<bool, String> {
 #[cfg(feature = "gui")]
 #[tauri::command]
 async fn refresh_subscription(id: String) -> Result<types::Subscription, String> {
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
-    let sub_index = subscriptions
-        .iter()
-        .position(|s| s.id == id)
-        .ok_or_else(|| "Subscription not found".to_string())?;
-
-    let sub = &subscriptions[sub_index];
-
-    // Download fresh data
-    match subscription::download_subscription(sub).await {
-        Ok(_) => {}
-        Err(e) => return Err(format!("Failed to download subscription: {}", e)),
-    }
-
-    // Parse to get count
-    let proxies = proxy::parse_cached_subscription(sub)
-        .map_err(|e| format!("Failed to parse subscription: {}", e))?;
-
-    let node_count = proxies.len();
-    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-
-    // Update metadata
-    let mut updated_sub = subscriptions[sub_index].clone();
-    updated_sub.node_count = Some(node_count);
-    updated_sub.last_updated = Some(now);
-
-    subscriptions[sub_index] = updated_sub.clone();
-
-    // Save updated subscriptions list
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
-
-    Ok(updated_sub)
+    subscription::refresh_subscription(&id)
+        .await
+        .map_err(|e| format!("Failed to refresh subscription: {:#}", e))
 }
 
 #[cfg(feature = "gui")]
@@ -267,22 +167,8 @@ fn get_subscriptions() -> Result<Vec<types::Subscription>, String> {
 #[cfg(feature = "gui")]
 #[tauri::command]
 fn add_subscription(name: String, url: String) -> Result<String, String> {
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
-    let id = uuid::Uuid::new_v4().to_string();
-
-    subscriptions.push(types::Subscription {
-        id,
-        name,
-        url,
-        enabled: true,
-        last_updated: None,
-        node_count: None,
-    });
-
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
+    subscription::add_subscription(name, url)
+        .map_err(|e| format!("Failed to add subscription: {}", e))?;
 
     Ok("✅ 订阅添加成功".to_string())
 }
@@ -295,25 +181,13 @@ fn update_subscription(
     url: String,
     enabled: bool,
 ) -> Result<String, String> {
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
-    if index >= subscriptions.len() {
-        return Err("订阅索引超出范围".to_string());
-    }
-
-    let id = subscriptions[index].id.clone();
-    subscriptions[index] = types::Subscription {
-        id,
-        name,
-        url,
-        enabled,
-        last_updated: None,
-        node_count: None,
+    let patch = subscription::SubscriptionPatch {
+        name: Some(name),
+        url: Some(url),
+        enabled: Some(enabled),
     };
-
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
+    subscription::update_subscription_at(index, patch)
+        .map_err(|e| format!("Failed to update subscription: {}", e))?;
 
     Ok("✅ 订阅更新成功".to_string())
 }
@@ -321,30 +195,11 @@ fn update_subscription(
 #[cfg(feature = "gui")]
 #[tauri::command]
 fn delete_subscription(index: usize) -> Result<String, String> {
-    eprintln!("🔍 delete_subscription 被调用，index: {}", index);
+    tracing::debug!("🔍 delete_subscription 被调用，index: {}", index);
 
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
-    eprintln!("✓ 当前订阅数量: {}", subscriptions.len());
-
-    if index >= subscriptions.len() {
-        let err = format!(
-            "订阅索引超出范围: index={}, len={}",
-            index,
-            subscriptions.len()
-        );
-        eprintln!("❌ {}", err);
-        return Err(err);
-    }
-
-    let removed = subscriptions.remove(index);
-    eprintln!("✓ 已删除订阅: {}", removed.name);
-
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
-
-    eprintln!("✓ 配置已保存");
+    let removed = subscription::remove_subscription_at(index)
+        .map_err(|e| format!("Failed to delete subscription: {}", e))?;
+    tracing::info!("✓ 已删除订阅: {}", removed.name);
 
     Ok("✅ 订阅删除成功".to_string())
 }
@@ -352,20 +207,18 @@ fn delete_subscription(index: usize) -> Result<String, String> {
 #[cfg(feature = "gui")]
 #[tauri::command]
 fn batch_delete_subscriptions(indices: Vec<usize>) -> Result<String, String> {
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
     let mut sorted_indices = indices.clone();
     sorted_indices.sort_by(|a, b| b.cmp(a)); // Sort descending to remove without affecting other indices
 
-    for index in sorted_indices {
-        if index < subscriptions.len() {
-            subscriptions.remove(index);
+    storage::modify_subscriptions(|subscriptions| {
+        for index in sorted_indices {
+            if index < subscriptions.len() {
+                subscriptions.remove(index);
+            }
         }
-    }
-
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
 
     Ok("✅ 批量删除成功".to_string())
 }
@@ -373,17 +226,15 @@ fn batch_delete_subscriptions(indices: Vec<usize>) -> Result<String, String> {
 #[cfg(feature = "gui")]
 #[tauri::command]
 fn batch_toggle_subscriptions(indices: Vec<usize>, enabled: bool) -> Result<String, String> {
-    let mut subscriptions = storage::load_subscriptions()
-        .map_err(|e| format!("Failed to load subscriptions: {}", e))?;
-
-    for &index in &indices {
-        if index < subscriptions.len() {
-            subscriptions[index].enabled = enabled;
+    storage::modify_subscriptions(|subscriptions| {
+        for &index in &indices {
+            if index < subscriptions.len() {
+                subscriptions[index].enabled = enabled;
+            }
         }
-    }
-
-    storage::save_subscriptions(&subscriptions)
-        .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
+        Ok(())
+    })
+    .map_err(|e| format!("Failed to save subscriptions: {}", e))?;
 
     Ok("✅ 批量状态更新成功".to_string())
 }
@@ -409,25 +260,59 @@ fn import_subscriptions(path: String) -> Result<Vec<types::Subscription>, String
     let list: types::SubscriptionList =
         serde_json::from_str(&content).map_err(|e| e.to_string())?;
 
-    let mut current_subs = storage::load_subscriptions().map_err(|e| e.to_string())?;
-
-    // Add imported subscriptions (avoiding ID duplicates if any, though UUIDs should be unique)
-
-    for mut sub in list.subscriptions {
-        if !current_subs.iter().any(|s| s.id == sub.id) {
-            // If ID matches but name/url different, we could generate new ID,
-            // but for simplicity we just append if ID is not there.
-            current_subs.push(sub);
-        } else {
-            // Regnerate ID to avoid collision
-            sub.id = uuid::Uuid::new_v4().to_string();
-            current_subs.push(sub);
+    storage::modify_subscriptions(|current_subs| {
+        // Add imported subscriptions (avoiding ID duplicates if any, though UUIDs should be unique)
+
+        for mut sub in list.subscriptions {
+            if !current_subs.iter().any(|s| s.id == sub.id) {
+                // If ID matches but name/url different, we could generate new ID,
+                // but for simplicity we just append if ID is not there.
+                current_subs.push(sub);
+            } else {
+                // Regnerate ID to avoid collision
+                sub.id = uuid::Uuid::new_v4().to_string();
+                current_subs.push(sub);
+            }
         }
-    }
+        Ok(current_subs.clone())
+    })
+    .map_err(|e| e.to_string())
+}
 
-    storage::save_subscriptions(&current_subs).map_err(|e| e.to_string())?;
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn export_backup(
+    path: String,
+    passphrase: Option<String>,
+    include_history: bool,
+) -> Result<backup::BackupManifest, String> {
+    let options = backup::ExportOptions {
+        passphrase: passphrase.filter(|p| !p.is_empty()),
+        plaintext_secrets: false,
+        include_history,
+    };
+    backup::export(std::path::Path::new(&path), &options).map_err(|e| e.to_string())
+}
 
-    Ok(current_subs)
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn import_backup(
+    path: String,
+    passphrase: Option<String>,
+    replace: bool,
+    prefer_backup: bool,
+) -> Result<backup::ImportReport, String> {
+    let options = backup::ImportOptions {
+        mode: if replace {
+            backup::ImportMode::Replace
+        } else {
+            backup::ImportMode::Merge
+        },
+        prefer_backup,
+        passphrase: passphrase.filter(|p| !p.is_empty()),
+        dry_run: false,
+    };
+    backup::import(std::path::Path::new(&path), &options).map_err(|e| e.to_string())
 }
 
 #[cfg(feature = "gui")]
@@ -477,22 +362,46 @@ async fn test_llm_connection(
 
 #[cfg(feature = "gui")]
 #[tauri::command]
-async fn apply_ai_patch(operations: Vec<serde_json::Value>) -> Result<String, String> {
-    let current_path = storage::get_current_config_path().map_err(|e| e.to_string())?;
+async fn apply_ai_patch(
+    target: Option<String>,
+    operations: Vec<serde_json::Value>,
+) -> Result<String, String> {
+    let target = target.unwrap_or_else(|| "current".to_string());
+    let target_path = match target.as_str() {
+        "basic" => storage::get_basic_config_path(),
+        "groups" => storage::get_groups_config_path(),
+        "local" => rules::ensure_rules_overlay_exists(),
+        _ => storage::get_current_config_path(),
+    }
+    .map_err(|e| e.to_string())?;
 
-    if !current_path.exists() {
-        return Err("当前配置文件不存在".to_string());
+    if !target_path.exists() {
+        return Err(format!("目标配置文件不存在: {:?}", target_path));
     }
 
-    let current_content = std::fs::read_to_string(&current_path).map_err(|e| e.to_string())?;
+    let content = std::fs::read_to_string(&target_path).map_err(|e| e.to_string())?;
 
     let patched_content =
-        ai::apply_patch_to_config(&current_content, &operations).map_err(|e| e.to_string())?;
-
-    version::save_version("current", "auto_before_ai_patch", &current_content)
-        .map_err(|e| e.to_string())?;
-
-    std::fs::write(&current_path, patched_content).map_err(|e| e.to_string())?;
+        ai::apply_patch_to_config(&content, &operations).map_err(|e| e.to_string())?;
+
+    version::save_version(
+        &target,
+        types::VersionSource::Ai,
+        "Before AI patch",
+        &content,
+    )
+    .map_err(|e| e.to_string())?;
+
+    storage::write_atomic(&target_path, patched_content).map_err(|e| e.to_string())?;
+    git_history::record(types::VersionSource::Ai, "AI patch");
+
+    // Base files and the rules overlay only reach current.yaml through a merge
+    if matches!(target.as_str(), "basic" | "groups" | "local") {
+        proxy::regenerate_current_config()
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok("✅ AI 修改已应用，已创建备份快照并重新生成配置".to_string());
+    }
 
     Ok("✅ AI 修改已应用并创建了备份快照".to_string())
 }
@@ -506,11 +415,18 @@ fn get_hangar_config() -> Result<types::HangarConfig, String> {
 
 #[cfg(feature = "gui")]
 #[tauri::command]
-fn save_hangar_config(config: types::HangarConfig) -> Result<String, String> {
-    storage::save_hangar_config(&config).map_err(|e| format!("Failed to save config: {}", e))?;
+fn save_hangar_config(config: serde_json::Value) -> Result<String, String> {
+    // Merge into the stored config so fields the settings page doesn't know about survive
+    storage::update_hangar_config(config).map_err(|e| format!("Failed to save config: {}", e))?;
     Ok("配置已保存".to_string())
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn list_config_templates() -> Vec<templates::BaseTemplate> {
+    templates::list_templates()
+}
+
 // Version Commands
 #[cfg(feature = "gui")]
 #[tauri::command]
@@ -527,38 +443,29 @@ fn get_version_content(id: String) -> Result<String, String> {
 #[cfg(feature = "gui")]
 #[tauri::command]
 fn create_manual_snapshot(description: String) -> Result<types::ConfigVersion, String> {
-    let current_path = storage::get_current_config_path().map_err(|e| e.to_string())?;
-
-    let current_content = std::fs::read_to_string(&current_path).map_err(|e| e.to_string())?;
-
-    version::save_version("manual", &description, &current_content).map_err(|e| e.to_string())
+    version::snapshot_current(&description).map_err(|e| e.to_string())
 }
 
 #[cfg(feature = "gui")]
 #[tauri::command]
-fn get_versions_diff(id1: String, id2: Option<String>) -> Result<Vec<version::DiffLine>, String> {
-    let content1 = version::get_version_content(&id1).map_err(|e| e.to_string())?;
-
-    let content2 = if let Some(id) = id2 {
-        version::get_version_content(&id).map_err(|e| e.to_string())?
-    } else {
-        let current_path =
-            storage::get_current_config_path_for_diff().map_err(|e| e.to_string())?;
-        if current_path.exists() {
-            std::fs::read_to_string(&current_path).map_err(|e| e.to_string())?
-        } else {
-            String::new()
-        }
-    };
-
-    Ok(version::diff_configs(&content1, &content2))
+fn get_versions_diff(id1: String, id2: Option<String>) -> Result<version::VersionDiff, String> {
+    version::diff_versions(&id1, id2.as_deref()).map_err(|e| e.to_string())
 }
 
 #[cfg(feature = "gui")]
 #[tauri::command]
-fn rollback_version(id: String) -> Result<String, String> {
-    version::rollback_to_version(&id).map_err(|e| format!("Failed to rollback: {}", e))?;
-    Ok("已回退到指定版本".to_string())
+async fn rollback_version(id: String) -> Result<String, String> {
+    let outcome = version::rollback_to_version(&id)
+        .await
+        .map_err(|e| format!("Failed to rollback: {}", e))?;
+    if outcome.remerged {
+        Ok(format!(
+            "已回退 {:?} 并重新生成配置",
+            outcome.path.file_name().unwrap_or_default()
+        ))
+    } else {
+        Ok("已回退到指定版本".to_string())
+    }
 }
 
 #[cfg(feature = "gui")]
@@ -568,6 +475,24 @@ fn delete_version(id: String) -> Result<String, String> {
     Ok("版本已删除".to_string())
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn tag_version(id: String, tag: String, force: bool) -> Result<types::ConfigVersion, String> {
+    version::tag_version(&id, &tag, force).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn untag_version(tag: String) -> Result<types::ConfigVersion, String> {
+    version::untag_version(&tag).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn set_version_note(id: String, note: Option<String>) -> Result<types::ConfigVersion, String> {
+    version::set_version_note(&id, note.as_deref()).map_err(|e| e.to_string())
+}
+
 // Rules Commands
 #[cfg(feature = "gui")]
 #[tauri::command]
@@ -607,8 +532,20 @@ fn get_rule_sources() -> Result<Vec<rules::RuleSource>, String> {
 
 #[cfg(feature = "gui")]
 #[tauri::command]
-fn add_rule_source(name: String, url: String) -> Result<rules::RuleSource, String> {
-    rules::add_rule_source(name, url).map_err(|e| format!("Failed to add rule source: {}", e))
+fn add_rule_source(
+    name: String,
+    url: String,
+    interval: Option<u64>,
+) -> Result<rules::RuleSource, String> {
+    rules::add_rule_source(name, url, interval)
+        .map_err(|e| format!("Failed to add rule source: {}", e))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+fn set_rule_source_enabled(id: String, enabled: bool) -> Result<rules::RuleSource, String> {
+    rules::set_rule_source_enabled(&id, enabled)
+        .map_err(|e| format!("Failed to update rule source: {}", e))
 }
 
 #[cfg(feature = "gui")]
@@ -627,9 +564,18 @@ async fn refresh_rules() -> Result<String, String> {
     Ok("规则刷新完成".to_string())
 }
 
+#[cfg(feature = "gui")]
+#[tauri::command]
+async fn refresh_rule_source(id: String) -> Result<rules::RuleSource, String> {
+    rules::refresh_rule_source(&id)
+        .await
+        .map_err(|e| format!("Failed to refresh rule source: {}", e))
+}
+
 #[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_cli::init())
         .plugin(tauri_plugin_opener::init())
@@ -647,6 +593,8 @@ pub fn run() {
             batch_toggle_subscriptions,
             export_subscriptions,
             import_subscriptions,
+            export_backup,
+            import_backup,
             get_notifications,
             mark_notification_read,
             clear_notifications,
@@ -657,6 +605,7 @@ pub fn run() {
             // Config commands
             get_hangar_config,
             save_hangar_config,
+            list_config_templates,
             // Version commands
             list_versions,
             get_version_content,
@@ -664,12 +613,17 @@ pub fn run() {
             get_versions_diff,
             rollback_version,
             delete_version,
+            tag_version,
+            untag_version,
+            set_version_note,
             // Rules commands
             get_builtin_rules,
             get_rule_sources,
             add_rule_source,
             remove_rule_source,
+            set_rule_source_enabled,
             refresh_rules,
+            refresh_rule_source,
             open_config_in_editor,
             open_data_directory
         ])
@@ -0,0 +1,488 @@
+use crate::types::LogConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Deserialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Set by `serve --daemon` on the server it spawns, whose stderr is not read by anyone
+pub const DAEMON_ENV: &str = "HANGAR_DAEMON";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Log to stderr only (CLI commands and the GUI). `RUST_LOG` overrides the level.
+pub fn init() {
+    let _ = tracing_subscriber::registry()
+        .with(vec![stderr_layer()])
+        .with(filter("info"))
+        .try_init();
+}
+
+/// Server logging: JSON lines in `~/.hangar/logs/hangar.log`, rotated by size and day,
+/// plus stderr unless `stderr` is false. Panics are logged too.
+pub fn init_server(config: &LogConfig, stderr: bool) -> Result<()> {
+    let writer = RotatingWriter::open(
+        &crate::storage::get_logs_dir()?,
+        config.max_size_mb.saturating_mul(1024 * 1024),
+        config.max_files,
+    )?;
+
+    let mut layers = vec![tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .boxed()];
+    if stderr {
+        layers.push(stderr_layer());
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter(&config.level))
+        .try_init()
+        .context("Failed to initialise logging")?;
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("💥 {}", info);
+        default_hook(info);
+    }));
+    Ok(())
+}
+
+fn filter(default: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(default))
+        .unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// `<priority>message` lines when stderr is the journal, timestamped lines otherwise
+fn stderr_layer() -> BoxedLayer {
+    let layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    if stderr_is_journal() {
+        layer.with_ansi(false).event_format(JournaldFormat).boxed()
+    } else {
+        layer
+            .with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr()))
+            .boxed()
+    }
+}
+
+/// systemd sets `JOURNAL_STREAM` to the device and inode of the journal socket on stderr
+fn stderr_is_journal() -> bool {
+    let Ok(stream) = std::env::var("JOURNAL_STREAM") else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(libc::STDERR_FILENO, &mut stat) } != 0 {
+            return false;
+        }
+        stream == format!("{}:{}", stat.st_dev, stat.st_ino)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+/// syslog priority prefix understood by journald (`SyslogLevelPrefix=`)
+fn priority(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// `<priority>message`, without the timestamp and level journald records itself
+pub struct JournaldFormat;
+
+impl<S, N> FormatEvent<S, N> for JournaldFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        write!(writer, "<{}>", priority(event.metadata().level()))?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// Appends to `hangar.log`, moving it to `hangar.log.1` (and older files up to `.N`)
+/// when it would exceed `max_size` or was started on an earlier day
+#[derive(Clone)]
+pub struct RotatingWriter(Arc<Mutex<RotatingFile>>);
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingWriter {
+    pub fn open(dir: &Path, max_size: u64, max_files: usize) -> Result<Self> {
+        let path = dir.join("hangar.log");
+        let file = open_append(&path)?;
+        let metadata = file.metadata()?;
+        // A log left over from yesterday is rotated on the first write
+        let opened_on = metadata
+            .modified()
+            .map(|t| DateTime::<Local>::from(t).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+
+        Ok(Self(Arc::new(Mutex::new(RotatingFile {
+            path,
+            file,
+            size: metadata.len(),
+            opened_on,
+            max_size,
+            max_files,
+        }))))
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {:?}", path))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+impl RotatingFile {
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = open_append(&self.path).map_err(std::io::Error::other)?;
+        self.size = 0;
+        self.opened_on = Local::now().date_naive();
+        Ok(())
+    }
+}
+
+impl Write for RotatingWriter {
+    /// The fmt layer writes each event with a single call, so files rotate between lines
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let today = Local::now().date_naive();
+        if inner.size > 0
+            && (inner.size + buf.len() as u64 > inner.max_size || inner.opened_on != today)
+        {
+            inner.rotate()?;
+        }
+        inner.file.write_all(buf)?;
+        inner.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .file
+            .flush()
+    }
+}
+
+/// One line of `hangar.log`
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogRecord {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    #[serde(default)]
+    pub message: String,
+    /// Other structured fields of the event
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl LogRecord {
+    pub fn parse(line: &str) -> Option<Self> {
+        serde_json::from_str(line).ok()
+    }
+
+    /// `2026-01-02 15:04:05 WARN  server: message key=value`, in local time
+    pub fn display(&self) -> String {
+        let time = DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|t| {
+                t.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| self.timestamp.clone());
+        let target = self
+            .target
+            .strip_prefix("hangar_lib::")
+            .unwrap_or(&self.target);
+
+        let mut line = format!("{} {:<5} {}: {}", time, self.level, target, self.message);
+        for (key, value) in &self.fields {
+            match value {
+                serde_json::Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+                other => line.push_str(&format!(" {}={}", key, other)),
+            }
+        }
+        line
+    }
+}
+
+/// `hangar logs` filters: a minimum level and a subsystem (module path segment, e.g. `server`)
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub level: Option<Level>,
+    pub target: Option<String>,
+}
+
+impl LogFilter {
+    pub fn new(level: Option<&str>, target: Option<String>) -> Result<Self> {
+        let level = level
+            .map(|l| Level::from_str(l).map_err(|_| anyhow::anyhow!("Unknown log level: {}", l)))
+            .transpose()?;
+        Ok(Self { level, target })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.level.is_none() && self.target.is_none()
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if let Some(min) = self.level {
+            // Levels order by verbosity: ERROR < WARN < INFO < DEBUG < TRACE
+            match Level::from_str(&record.level) {
+                Ok(level) if level <= min => {}
+                _ => return false,
+            }
+        }
+        if let Some(target) = &self.target {
+            if record.target != *target
+                && !record.target.split("::").any(|segment| segment == target)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The line to print for `line`, or None if it is filtered out.
+    /// Lines that aren't JSON records are shown as-is when no filter is set.
+    pub fn render(&self, line: &str) -> Option<String> {
+        match LogRecord::parse(line) {
+            Some(record) => self.matches(&record).then(|| record.display()),
+            None => (self.is_empty() && !line.trim().is_empty()).then(|| line.to_string()),
+        }
+    }
+}
+
+/// The last `count` matching lines of the log at `path`
+pub fn tail(path: &Path, filter: &LogFilter, count: usize) -> Result<Vec<String>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut lines: std::collections::VecDeque<String> = std::collections::VecDeque::new();
+    for line in std::io::BufReader::new(file).lines() {
+        if let Some(rendered) = filter.render(&line?) {
+            if lines.len() == count {
+                lines.pop_front();
+            }
+            lines.push_back(rendered);
+        }
+    }
+    Ok(lines.into())
+}
+
+/// Remove the complete lines from `pending`. Bytes after the last newline stay, since a
+/// write may have stopped in the middle of a multi-byte character.
+fn take_complete_lines(pending: &mut Vec<u8>) -> Vec<String> {
+    let Some(last) = pending.iter().rposition(|&b| b == b'\n') else {
+        return Vec::new();
+    };
+    let complete: Vec<u8> = pending.drain(..=last).collect();
+    complete
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect()
+}
+
+/// Print matching lines as they are appended, following the log across rotations
+pub async fn follow(path: &Path, filter: &LogFilter) -> Result<()> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut position = file.seek(SeekFrom::End(0))?;
+    let mut pending = Vec::new();
+
+    loop {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        // Rotation replaces hangar.log with a new, shorter file
+        let len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if len < position {
+            file = File::open(path)?;
+            position = 0;
+            pending.clear();
+        }
+
+        file.seek(SeekFrom::Start(position))?;
+        position += file.read_to_end(&mut pending)? as u64;
+
+        for line in take_complete_lines(&mut pending) {
+            if let Some(rendered) = filter.render(line.trim_end()) {
+                println!("{}", rendered);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_journald_format() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .event_format(JournaldFormat)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("server started");
+            tracing::warn!("no tokens");
+            tracing::error!("bind failed: {}", "in use");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "<6>server started\n<4>no tokens\n<3>bind failed: in use\n"
+        );
+    }
+
+    #[test]
+    fn test_rotation_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = RotatingWriter::open(dir.path(), 20, 2).unwrap();
+
+        for line in [
+            "first line 0001\n",
+            "second line 002\n",
+            "third line 0003\n",
+            "fourth line 004\n",
+        ] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("hangar.log"), "fourth line 004\n");
+        assert_eq!(read("hangar.log.1"), "third line 0003\n");
+        assert_eq!(read("hangar.log.2"), "second line 002\n");
+        assert!(!dir.path().join("hangar.log.3").exists());
+    }
+
+    #[test]
+    fn test_json_records_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = RotatingWriter::open(dir.path(), 1024 * 1024, 1).unwrap();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "hangar_lib::server", "listening");
+            tracing::warn!(target: "hangar_lib::subscription", name = "home", "refresh failed");
+            tracing::error!(target: "hangar_lib::server", "bind failed");
+        });
+
+        let path = dir.path().join("hangar.log");
+        let warnings = LogFilter::new(Some("warn"), None).unwrap();
+        let lines = tail(&path, &warnings, 10).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("WARN  subscription: refresh failed name=home"));
+        assert!(lines[1].contains("ERROR server: bind failed"));
+
+        let server = LogFilter::new(None, Some("server".to_string())).unwrap();
+        let lines = tail(&path, &server, 1).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("server: bind failed"));
+
+        assert!(LogFilter::new(Some("loud"), None).is_err());
+    }
+
+    #[test]
+    fn test_take_complete_lines_keeps_split_characters() {
+        let line = "{\"message\":\"订阅已更新\"}\n".as_bytes();
+        // Cut inside the first multi-byte character
+        let cut = line.iter().position(|&b| b >= 0x80).unwrap() + 1;
+
+        let mut pending = line[..cut].to_vec();
+        assert!(take_complete_lines(&mut pending).is_empty());
+
+        pending.extend_from_slice(&line[cut..]);
+        pending.extend_from_slice(b"second\nthi");
+        assert_eq!(
+            take_complete_lines(&mut pending),
+            vec!["{\"message\":\"订阅已更新\"}", "second"]
+        );
+        assert_eq!(pending, b"thi");
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::Context;
-use axum::response::IntoResponse;
 use clap::{Parser, Subcommand};
-use hangar_lib::{ai, proxy, server, storage, subscription, types, version};
+use hangar_lib::{
+    ai, auth, backup, client_import, config_diff, daemon, git_history, logging, merge3, profile,
+    proxy, rules, server, service, storage, subscription, templates, tls, types, version,
+};
 
 #[derive(Parser)]
 #[command(name = "hangar")]
@@ -13,6 +15,33 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Initialize basic.yml and groups.yml from a built-in template
+    Init {
+        /// Template name (see --list)
+        #[arg(short, long, default_value = "fake-ip")]
+        template: String,
+        /// List available templates
+        #[arg(long, default_value_t = false)]
+        list: bool,
+        /// Mixed (HTTP/SOCKS) proxy port
+        #[arg(long)]
+        port: Option<u16>,
+        /// External controller port
+        #[arg(long)]
+        controller_port: Option<u16>,
+        /// External controller secret
+        #[arg(long)]
+        secret: Option<String>,
+        /// Allow connections from the LAN
+        #[arg(long)]
+        allow_lan: Option<bool>,
+        /// Use defaults for parameters not given instead of prompting
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+        /// Overwrite locally edited basic.yml/groups.yml (they are snapshotted first)
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
     /// Subscription management
     Sub {
         #[command(subcommand)]
@@ -35,18 +64,73 @@ enum Commands {
         /// Run server in daemon mode (background)
         #[arg(short, long, default_value_t = false)]
         daemon: bool,
+        /// PEM certificate for HTTPS (overrides server.tls in config.json)
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<String>,
+        /// PEM private key for HTTPS
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<String>,
+        /// Listen on a non-loopback address even when no access token exists
+        #[arg(long, default_value_t = false)]
+        allow_unauthenticated: bool,
+    },
+    /// TLS certificates for the config server
+    Cert {
+        #[command(subcommand)]
+        subcommand: CertCommands,
     },
     /// Stop the background server
     Stop,
     /// Restart the background server
     Restart,
+    /// Re-read subscriptions and re-merge in the background server (SIGHUP)
+    Reload,
+    /// Run the server as a systemd user service
+    Service {
+        #[command(subcommand)]
+        subcommand: ServiceCommands,
+    },
     /// Check server status
-    Status,
+    Status {
+        /// Port the server listens on (default: from the last `serve`)
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// Host the server is bound to (default: from the last `serve`)
+        #[arg(long)]
+        host: Option<String>,
+        /// Admin token, needed once tokens exist (default: $HANGAR_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+    },
     /// Show server logs
     Logs {
         /// Follow logs in real-time
         #[arg(short, long, default_value_t = false)]
         follow: bool,
+        /// Only show entries at this level or more severe (error, warn, info, debug, trace)
+        #[arg(short, long)]
+        level: Option<String>,
+        /// Only show entries from this subsystem (e.g. server, subscription, proxy, daemon)
+        #[arg(short, long)]
+        target: Option<String>,
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
+    /// Rule source management
+    Rules {
+        #[command(subcommand)]
+        subcommand: RulesCommands,
+    },
+    /// Access tokens for the config server
+    Token {
+        #[command(subcommand)]
+        subcommand: TokenCommands,
+    },
+    /// Profiles (subsets of subscriptions) served to tokens
+    Profile {
+        #[command(subcommand)]
+        subcommand: ProfileCommands,
     },
     /// AI-powered configuration modification
     Ai {
@@ -58,6 +142,25 @@ enum Commands {
         #[command(subcommand)]
         subcommand: Option<HistoryCommands>,
     },
+    /// Export or import a backup bundle of ~/.hangar
+    Backup {
+        #[command(subcommand)]
+        subcommand: BackupCommands,
+    },
+    /// Import profiles from Clash Verge Rev, mihomo party or a Clash YAML file
+    Import {
+        /// Client data directory or Clash YAML file (default: detected client directories)
+        path: Option<std::path::PathBuf>,
+        /// Profile (name or ID) whose rules and groups are offered as basic.yml/groups.yml
+        #[arg(long)]
+        profile: Option<String>,
+        /// Replace basic.yml and groups.yml with the profile's settings, rules and groups
+        #[arg(long)]
+        base: bool,
+        /// Show what would be imported without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
     /// Application configuration
     Config {
         /// Set LLM API Key
@@ -69,11 +172,21 @@ enum Commands {
         /// Set LLM Model
         #[arg(long)]
         model: Option<String>,
+        /// Set the server log level (error, warn, info, debug, trace)
+        #[arg(long)]
+        log_level: Option<String>,
     },
     /// Open config in default editor
     Editor,
-    /// Force update basic and groups config from built-in resources
-    ForceUpdateConfig,
+    /// Update basic and groups config from built-in resources, keeping local edits (three-way merge)
+    ForceUpdateConfig {
+        /// Resolve conflicts in favour of the bundled template instead of local edits
+        #[arg(long, default_value_t = false)]
+        upstream: bool,
+        /// Show what would change without writing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -110,14 +223,159 @@ enum SubCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum RulesCommands {
+    /// List all rule sources
+    List,
+    /// Add a new rule source
+    Add {
+        /// Rule file URL
+        url: String,
+        /// Optional name for the rule source
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Refresh interval in seconds (at least 300)
+        #[arg(
+            short,
+            long,
+            default_value_t = rules::DEFAULT_RULE_INTERVAL,
+            value_parser = clap::value_parser!(u64).range(rules::MIN_RULE_INTERVAL..)
+        )]
+        interval: u64,
+    },
+    /// Remove a rule source
+    Remove {
+        /// The name, ID or index of the rule source
+        id: String,
+    },
+    /// Enable a rule source
+    Enable {
+        /// The name, ID or index of the rule source
+        id: String,
+    },
+    /// Disable a rule source
+    Disable {
+        /// The name, ID or index of the rule source
+        id: String,
+    },
+    /// Refresh one rule source, or all rules (builtin + sources) if omitted
+    Refresh {
+        /// The name, ID or index of the rule source
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CertCommands {
+    /// Write a local CA and a server certificate signed by it, and enable TLS
+    SelfSigned {
+        /// Host names / IPs the certificate is valid for (default: localhost and the LAN IP)
+        #[arg(long = "host")]
+        hosts: Vec<String>,
+        /// Output directory (default: ~/.hangar/tls)
+        #[arg(long)]
+        dir: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Write ~/.config/systemd/user/hangar.service, then enable and start it
+    Install {
+        /// Port to listen on (default: from the last `serve`, else 8080)
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// Host to bind to (default: from the last `serve`, else 127.0.0.1)
+        #[arg(long)]
+        host: Option<String>,
+        /// Auto-update interval in seconds (default: from the last `serve`, else 0)
+        #[arg(short, long)]
+        interval: Option<u64>,
+        /// Listen on a non-loopback address even when no access token exists
+        /// (default: from the last `serve`)
+        #[arg(long, default_value_t = false)]
+        allow_unauthenticated: bool,
+    },
+    /// Stop and disable the service and remove the unit file
+    Uninstall,
+    /// Show the service status from systemd
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TokenCommands {
+    /// List all tokens
+    List,
+    /// Create a token (the secret is printed once)
+    Add {
+        /// Token name, e.g. the device using it
+        name: String,
+        /// Profile served to this token
+        #[arg(short, long, default_value = "default")]
+        profile: String,
+        /// Expiry: duration (30d, 12h), date (2026-12-31) or unix timestamp
+        #[arg(short, long)]
+        expires: Option<String>,
+        /// Also allow the /api management endpoints
+        #[arg(long, default_value_t = false)]
+        admin: bool,
+    },
+    /// Revoke a token
+    Revoke {
+        /// The name or ID of the token
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List all profiles
+    List,
+    /// Add a profile limited to some subscriptions
+    Add {
+        /// Profile name
+        name: String,
+        /// Subscription names or IDs included in the profile
+        #[arg(short, long = "subscription", required = true)]
+        subscriptions: Vec<String>,
+    },
+    /// Remove a profile
+    Remove {
+        /// Profile name
+        name: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum HistoryCommands {
     /// List all snapshots
     List,
     /// Rollback to a specific version (restores file based on version type)
     Rollback {
-        /// Version ID
+        /// Version ID, alias (v3) or tag
+        id: String,
+    },
+    /// Tag a version so it can be restored by name; tagged versions are never pruned
+    Tag {
+        /// Version ID, alias or tag
+        id: String,
+        /// Tag name
+        tag: String,
+        /// Move the tag if another version has it
+        #[arg(short, long, default_value_t = false)]
+        force: bool,
+    },
+    /// Remove a tag
+    Untag {
+        /// Tag name
+        tag: String,
+    },
+    /// Annotate a version (without text, the note is cleared)
+    Note {
+        /// Version ID, alias or tag
         id: String,
+        /// Note text
+        text: Option<String>,
     },
     /// Show diff between two versions
     Diff {
@@ -125,6 +383,78 @@ enum HistoryCommands {
         v1: String,
         /// Second version ID (defaults to current)
         v2: Option<String>,
+        /// Compare proxies, groups, rules and settings instead of lines
+        #[arg(long, default_value_t = false)]
+        semantic: bool,
+    },
+    /// Git repository of the config files in ~/.hangar/history
+    Git {
+        #[command(subcommand)]
+        subcommand: HistoryGitCommands,
+    },
+    /// Delete versions outside the retention policy (manual and tagged snapshots are kept)
+    Prune {
+        /// Only list the versions that would be deleted
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Versions of each target to keep (default: history.keep_last)
+        #[arg(long)]
+        keep_last: Option<usize>,
+        /// Keep one version per target per day for this many days (default: history.keep_daily_days)
+        #[arg(long)]
+        keep_daily: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Write subscriptions, rule sources, config.json and config files to a .tar.gz bundle
+    Export {
+        /// Bundle path, e.g. hangar-backup.tar.gz
+        path: std::path::PathBuf,
+        /// Encrypt the secrets of config.json with a passphrase
+        #[arg(long, default_value_t = false)]
+        encrypt: bool,
+        /// Include the secrets unencrypted
+        #[arg(long, default_value_t = false, conflicts_with = "encrypt")]
+        plaintext_secrets: bool,
+        /// Include the version history
+        #[arg(long, default_value_t = false)]
+        history: bool,
+    },
+    /// Restore a bundle, merging it into the local configuration
+    Import {
+        /// Bundle path
+        path: std::path::PathBuf,
+        /// Replace the local configuration instead of merging
+        #[arg(long, default_value_t = false)]
+        replace: bool,
+        /// Resolve merge conflicts in favour of the bundle
+        #[arg(long, default_value_t = false)]
+        prefer_backup: bool,
+        /// Show what would change without writing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryGitCommands {
+    /// Commit config files to ~/.hangar/history on every merge, AI patch, rollback and edit
+    Enable,
+    /// Stop committing (the repository is kept)
+    Disable,
+    /// Commit the current config files, e.g. after editing them by hand
+    Commit {
+        /// Commit message
+        #[arg(short, long, default_value = "Manual edit")]
+        message: String,
+    },
+    /// Show recent commits
+    Log {
+        /// Number of commits to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
     },
 }
 
@@ -146,19 +476,537 @@ fn main() -> anyhow::Result<()> {
     rt.block_on(async { async_main().await })
 }
 
-async fn async_main() -> anyhow::Result<()> {
-    // Ensure basic config exists in user directory
-    storage::ensure_basic_config_exists()?;
-    storage::ensure_groups_config_exists()?;
+/// Print a structural diff: `+` added, `-` removed, `~` changed, `↕` moved
+fn print_semantic_diff(diff: &config_diff::SemanticDiff) {
+    if diff.is_empty() {
+        println!("No changes found.");
+        return;
+    }
+    let added = |text: String| println!("\x1b[32m  + {}\x1b[0m", text);
+    let removed = |text: String| println!("\x1b[31m  - {}\x1b[0m", text);
+    let changed = |text: String| println!("\x1b[33m  ~ {}\x1b[0m", text);
+
+    if !diff.proxies_added.is_empty()
+        || !diff.proxies_removed.is_empty()
+        || !diff.proxies_changed.is_empty()
+    {
+        println!("\nProxies:");
+        diff.proxies_added.iter().for_each(|p| added(p.clone()));
+        diff.proxies_removed.iter().for_each(|p| removed(p.clone()));
+        for proxy in &diff.proxies_changed {
+            changed(format!("{} ({})", proxy.name, proxy.fields.join(", ")));
+        }
+    }
+
+    if !diff.groups_added.is_empty()
+        || !diff.groups_removed.is_empty()
+        || !diff.groups_changed.is_empty()
+    {
+        println!("\nProxy groups:");
+        diff.groups_added.iter().for_each(|g| added(g.clone()));
+        diff.groups_removed.iter().for_each(|g| removed(g.clone()));
+        for group in &diff.groups_changed {
+            let mut details: Vec<String> = Vec::new();
+            details.extend(group.members_added.iter().map(|m| format!("+{}", m)));
+            details.extend(group.members_removed.iter().map(|m| format!("-{}", m)));
+            if group.reordered {
+                details.push("reordered".to_string());
+            }
+            if !group.fields.is_empty() {
+                details.push(group.fields.join(", "));
+            }
+            changed(format!("{}: {}", group.name, details.join(" ")));
+        }
+    }
+
+    if !diff.rules_added.is_empty()
+        || !diff.rules_removed.is_empty()
+        || !diff.rules_moved.is_empty()
+    {
+        println!("\nRules:");
+        for rule in &diff.rules_added {
+            added(format!("[{}] {}", rule.index, rule.rule));
+        }
+        for rule in &diff.rules_removed {
+            removed(format!("[{}] {}", rule.index, rule.rule));
+        }
+        for rule in &diff.rules_moved {
+            println!(
+                "\x1b[36m  ↕ [{} → {}] {}\x1b[0m",
+                rule.from, rule.to, rule.rule
+            );
+        }
+    }
+
+    if !diff.settings_changed.is_empty() {
+        println!("\nSettings:");
+        for setting in &diff.settings_changed {
+            match (&setting.old, &setting.new) {
+                (None, Some(new)) => added(format!(
+                    "{}: {}",
+                    setting.key,
+                    config_diff::format_value(new)
+                )),
+                (Some(old), None) => removed(format!(
+                    "{}: {}",
+                    setting.key,
+                    config_diff::format_value(old)
+                )),
+                (Some(old), Some(new)) => changed(format!(
+                    "{}: {} → {}",
+                    setting.key,
+                    config_diff::format_value(old),
+                    config_diff::format_value(new)
+                )),
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+/// Stop the background server, waiting up to 5 seconds for a graceful shutdown
+async fn stop_server() -> anyhow::Result<()> {
+    let pid_path = storage::get_server_pid_path()?;
+    let Some(pid) = daemon::running_pid(&pid_path)? else {
+        println!("❌ Server is not running");
+        return Ok(());
+    };
+
+    println!("🛑 Stopping server (PID: {})...", pid);
+
+    #[cfg(unix)]
+    {
+        use std::time::{Duration, Instant};
+        if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+            println!("❌ Failed to stop server: process not found or no permission");
+            return Ok(());
+        }
+
+        // The lock is released when the process exits
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if daemon::running_pid(&pid_path)?.is_none() {
+                println!("✅ Server stopped");
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
 
+        println!("⚠️ Server didn't stop gracefully, force killing...");
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        println!("✅ Server stopped");
+    }
+
+    #[cfg(not(unix))]
+    {
+        println!("❌ Stop command is currently only supported on Unix-like systems.");
+    }
+
+    Ok(())
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Print the JSON from a running server's /status
+fn print_server_status(status: &server::ServerStatus) {
+    let uptime = status.uptime_seconds;
+    println!(
+        "✅ Server is healthy (v{}, up {}h {}m)",
+        status.version,
+        uptime / 3600,
+        uptime % 3600 / 60
+    );
+    if let Some(address) = &status.address {
+        println!("   Address: {}/config", address);
+    }
+    println!("   Profiles: {}", status.profiles.join(", "));
+    println!(
+        "   Config: {} proxies, {} groups, {} rules (hash {}, modified {})",
+        status.config.proxies,
+        status.config.proxy_groups,
+        status.config.rules,
+        &status.config.hash[..12.min(status.config.hash.len())],
+        format_timestamp(status.config.last_modified)
+    );
+
+    if !status.subscriptions.is_empty() {
+        println!("\n📡 Subscriptions:");
+        for sub in &status.subscriptions {
+            let icon = if !sub.enabled {
+                "⏸️"
+            } else if sub.last_error.is_some() {
+                "❌"
+            } else {
+                "✓"
+            };
+            println!(
+                "   {} {} ({} nodes, updated {})",
+                icon,
+                sub.name,
+                sub.node_count
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "?".to_string()),
+                sub.last_updated.as_deref().unwrap_or("never")
+            );
+            if let Some(error) = &sub.last_error {
+                println!("      {}", error);
+            }
+        }
+    }
+
+    match &status.last_merge {
+        Some(merge) => {
+            println!(
+                "\n🔀 Last merge: {} ({} ms, {} warning(s))",
+                format_timestamp(merge.finished_at),
+                merge.duration_ms,
+                merge.warnings.len()
+            );
+            for warning in &merge.warnings {
+                println!("   ⚠️ {}", warning);
+            }
+        }
+        None => println!("\n🔀 No merge since the server started"),
+    }
+}
+
+/// Backup passphrase from HANGAR_BACKUP_PASSPHRASE, or prompted for without echo.
+/// None when neither is available.
+fn backup_passphrase(confirm: bool) -> anyhow::Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(backup::PASSPHRASE_ENV) {
+        return Ok(Some(passphrase));
+    }
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return Ok(None);
+    }
+    let passphrase = rpassword::prompt_password("🔑 Backup passphrase: ")?;
+    if confirm && rpassword::prompt_password("🔑 Repeat passphrase: ")? != passphrase {
+        anyhow::bail!("Passphrases don't match");
+    }
+    Ok(Some(passphrase))
+}
+
+/// Print what `hangar backup import` changed, or would change
+fn print_import_report(report: &backup::ImportReport, dry_run: bool) {
+    let created = chrono::DateTime::from_timestamp(report.manifest.created_at, 0)
+        .unwrap_or_default()
+        .with_timezone(&chrono::Local);
+    println!(
+        "📦 Backup from {} (Hangar {})",
+        created.format("%Y-%m-%d %H:%M:%S"),
+        report.manifest.hangar_version
+    );
+    for item in &report.added {
+        println!("\x1b[32m  + {}\x1b[0m", item);
+    }
+    for item in &report.updated {
+        println!("\x1b[33m  ~ {}\x1b[0m", item);
+    }
+    for item in &report.removed {
+        println!("\x1b[31m  - {}\x1b[0m", item);
+    }
+    if let Some(versions) = &report.versions {
+        println!(
+            "  🕘 {} version(s) imported, {} already present",
+            versions.added, versions.skipped
+        );
+    }
+    if report.secrets_restored {
+        println!("  🔑 Secrets restored");
+    }
+    if !report.conflicts.is_empty() {
+        println!("\n⚠️ {} conflict(s):", report.conflicts.len());
+        for conflict in &report.conflicts {
+            let kept = match conflict.kept {
+                backup::Kept::Local => "kept local",
+                backup::Kept::Backup => "took backup",
+            };
+            println!("   {}: {} ({})", conflict.item, conflict.detail, kept);
+        }
+    }
+    for warning in &report.warnings {
+        println!("⚠️ {}", warning);
+    }
+
+    let nothing = report.added.is_empty()
+        && report.updated.is_empty()
+        && report.removed.is_empty()
+        && report.versions.as_ref().is_none_or(|v| v.added == 0);
+    if nothing {
+        println!("✨ Nothing to import.");
+    } else if dry_run {
+        println!("\n🔍 Dry run: nothing was written.");
+    } else {
+        println!("\n✅ Backup imported. Run `hangar sub update <name>` for new subscriptions, then `hangar merge`.");
+    }
+}
+
+/// Print what `hangar import` found in a client's profiles
+fn print_client_import_plan(plan: &client_import::ClientImportPlan, base: bool) {
+    println!("📥 {} at {:?}", plan.client, plan.source);
+    for sub in &plan.subscriptions {
+        let source = sub.url.as_deref().unwrap_or("local proxies");
+        let nodes = if sub.nodes > 0 {
+            format!(" ({} nodes)", sub.nodes)
+        } else {
+            String::new()
+        };
+        match &sub.skipped {
+            None => println!("\x1b[32m  + {}{}\x1b[0m  {}", sub.name, nodes, source),
+            Some(reason) => println!("  · {}: skipped, {}", sub.name, reason),
+        }
+    }
+    for item in &plan.ignored {
+        println!("  · {}: not imported", item);
+    }
+
+    let Some(profile) = &plan.base else {
+        return;
+    };
+    println!(
+        "\n📄 Profile '{}': {} rule(s), {} group(s), DNS {}",
+        profile.profile,
+        profile.rule_count,
+        profile.group_count,
+        if profile.has_dns { "✓" } else { "✗" }
+    );
+    if profile.dropped_members > 0 {
+        println!(
+            "   {} node reference(s) removed from groups; Hangar adds region groups to each group",
+            profile.dropped_members
+        );
+    }
+    if !profile.kept_settings.is_empty() {
+        println!(
+            "   Kept from current basic.yml: {}",
+            profile.kept_settings.join(", ")
+        );
+    }
+    for warning in &profile.warnings {
+        println!("⚠️ {}", warning);
+    }
+    if !base {
+        println!("   Use --base to replace basic.yml and groups.yml with it.");
+    }
+}
+
+/// Prompt for a value on stdin, returning `default` on empty input
+fn prompt<T>(question: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr + std::fmt::Display,
+{
+    use std::io::Write;
+
+    loop {
+        print!("{} [{}]: ", question, default);
+        std::io::stdout().flush()?;
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(default);
+        }
+        match input.parse::<T>() {
+            Ok(value) => return Ok(value),
+            Err(_) => println!("⚠️ Invalid value: {}", input),
+        }
+    }
+}
+
+async fn async_main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    match &cli.command {
+        Commands::Serve { daemon: false, .. } => {
+            let log_config = storage::load_hangar_config().unwrap_or_default().log;
+            let to_stderr = std::env::var_os(logging::DAEMON_ENV).is_none();
+            if let Err(e) = logging::init_server(&log_config, to_stderr) {
+                logging::init();
+                tracing::warn!("⚠️ 无法写入日志文件: {}", e);
+            }
+        }
+        _ => logging::init(),
+    }
+
+    // Ensure basic config exists in user directory (init installs its own template)
+    if !matches!(cli.command, Commands::Init { .. }) {
+        storage::ensure_basic_config_exists()?;
+        storage::ensure_groups_config_exists()?;
+    }
 
     match cli.command {
+        Commands::Init {
+            template,
+            list,
+            port,
+            controller_port,
+            secret,
+            allow_lan,
+            yes,
+            force,
+        } => {
+            if list {
+                println!("{:<12} Description", "Name");
+                for t in templates::list_templates() {
+                    println!("{:<12} {}", t.name, t.description);
+                }
+                return Ok(());
+            }
+
+            if !templates::list_templates()
+                .iter()
+                .any(|t| t.name == template)
+            {
+                println!(
+                    "❌ Unknown template '{}'. Run 'hangar init --list'.",
+                    template
+                );
+                return Ok(());
+            }
+
+            use std::io::IsTerminal;
+            let interactive = !yes && std::io::stdin().is_terminal();
+            let defaults = types::TemplateParams::default();
+
+            // The router template exposes the controller to the LAN, so it gets a secret by default
+            let default_secret = if template == "router" {
+                uuid::Uuid::new_v4().simple().to_string()
+            } else {
+                String::new()
+            };
+
+            let params = types::TemplateParams {
+                mixed_port: match port {
+                    Some(p) => p,
+                    None if interactive => prompt("Mixed port", defaults.mixed_port)?,
+                    None => defaults.mixed_port,
+                },
+                controller_port: match controller_port {
+                    Some(p) => p,
+                    None if interactive => {
+                        prompt("External controller port", defaults.controller_port)?
+                    }
+                    None => defaults.controller_port,
+                },
+                secret: Some(match secret {
+                    Some(s) => s,
+                    None if interactive => {
+                        prompt("External controller secret ('-' for none)", default_secret)?
+                    }
+                    None => default_secret,
+                })
+                .filter(|s| !s.is_empty() && s != "-"),
+                allow_lan: match allow_lan {
+                    Some(b) => b,
+                    None if interactive => prompt("Allow LAN (true/false)", defaults.allow_lan)?,
+                    None => defaults.allow_lan,
+                },
+            };
+
+            let selection = types::TemplateSelection {
+                name: template,
+                params,
+            };
+            let rendered = templates::render(&selection)?;
+
+            let files = vec![
+                (
+                    "basic",
+                    "basic.yml",
+                    rendered.basic,
+                    storage::get_basic_config_path()?,
+                ),
+                (
+                    "groups",
+                    "groups.yml",
+                    rendered.groups,
+                    storage::get_groups_config_path()?,
+                ),
+            ];
+
+            // Refuse to clobber local edits unless forced
+            if !force {
+                for (_, file_name, content, path) in &files {
+                    if path.exists() {
+                        let local = std::fs::read_to_string(path)?;
+                        let installed = storage::load_installed_template(file_name)?;
+                        match installed {
+                            Some(installed) if installed == local => {}
+                            Some(_) => {
+                                println!("❌ {} has local edits.", file_name);
+                                println!("   Re-run with --force (a snapshot is taken first), or keep them with 'hangar force-update-config'.");
+                                return Ok(());
+                            }
+                            // Without a record nothing is known to be edited, but it can't be ruled out either
+                            None if &local != content => {
+                                println!(
+                                    "❌ {} has no installed-template record, so local edits can't be detected.",
+                                    file_name
+                                );
+                                println!("   Re-run with --force (a snapshot is taken first), or merge with 'hangar force-update-config'.");
+                                return Ok(());
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+
+            for (file_type, file_name, content, path) in &files {
+                if path.exists() {
+                    let local = std::fs::read_to_string(path)?;
+                    if &local == content {
+                        storage::save_installed_template(file_name, content)?;
+                        continue;
+                    }
+                    version::save_version(
+                        file_type,
+                        types::VersionSource::Template,
+                        "Before hangar init",
+                        &local,
+                    )?;
+                }
+                storage::write_atomic(path, content)?;
+                storage::save_installed_template(file_name, content)?;
+                println!("✅ Wrote {:?}", path);
+            }
+            git_history::record(
+                types::VersionSource::Template,
+                &format!("hangar init --template {}", selection.name),
+            );
+
+            println!("✅ Initialized from template '{}'", selection.name);
+            if let Some(secret) = &selection.params.secret {
+                println!("   External controller secret: {}", secret);
+            }
+            storage::modify_hangar_config(|config| {
+                config.template = selection;
+                Ok(())
+            })?;
+
+            println!("🔄 Merging configuration...");
+            let subs = storage::load_subscriptions().unwrap_or_default();
+            match proxy::merge_configs(&subs).await {
+                Ok(merged) => {
+                    let output_path = proxy::write_current_config(&merged)?;
+                    println!("✅ Config generated and saved to {:?}", output_path);
+                }
+                Err(e) => println!("❌ Merge failed: {}", e),
+            }
+        }
         Commands::Sub { subcommand } => {
             match subcommand {
                 SubCommands::Add { url, name } => {
                     let name = name.unwrap_or_else(|| "Untitled".to_string());
-                    let mut subs = storage::load_subscriptions().unwrap_or_default();
                     let id = uuid::Uuid::new_v4().to_string();
                     let current_time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
                     let mut new_sub = types::Subscription {
@@ -168,6 +1016,7 @@ async fn async_main() -> anyhow::Result<()> {
                         enabled: true,
                         last_updated: Some(current_time),
                         node_count: None,
+                        last_error: None,
                     };
 
                     // Immediately download
@@ -190,8 +1039,10 @@ async fn async_main() -> anyhow::Result<()> {
                         Err(e) => println!("⚠️ Failed to download: {}", e),
                     }
 
-                    subs.push(new_sub);
-                    storage::save_subscriptions(&subs)?;
+                    storage::modify_subscriptions(|subs| {
+                        subs.push(new_sub);
+                        Ok(())
+                    })?;
                     println!("✅ Added subscription: {} ({})", name, id);
                 }
                 SubCommands::List => {
@@ -216,95 +1067,94 @@ async fn async_main() -> anyhow::Result<()> {
                     }
                 }
                 SubCommands::Remove { id } => {
-                    let mut subs = storage::load_subscriptions().unwrap_or_default();
-                    // Logic to remove by ID or index...
-                    let mut to_remove_id = None;
-                    if let Ok(idx) = id.parse::<usize>() {
-                        if idx < subs.len() {
-                            to_remove_id = Some(subs[idx].id.clone());
+                    let removed = storage::modify_subscriptions(|subs| {
+                        // Logic to remove by ID or index...
+                        let mut to_remove_id = None;
+                        if let Ok(idx) = id.parse::<usize>() {
+                            if idx < subs.len() {
+                                to_remove_id = Some(subs[idx].id.clone());
+                            }
+                        } else {
+                            to_remove_id = Some(id.clone());
                         }
-                    } else {
-                        to_remove_id = Some(id.clone());
-                    }
 
-                    if let Some(rid) = to_remove_id {
                         let len_before = subs.len();
-                        subs.retain(|s| s.id != rid);
-                        if subs.len() < len_before {
-                            storage::save_subscriptions(&subs)?;
-                            println!("✅ Removed subscription: {}", rid);
-
-                            // Remove cache file
-                            match storage::get_subscription_cache_path(&rid) {
-                                Ok(path) => {
-                                    if path.exists() {
-                                        match std::fs::remove_file(&path) {
-                                            Ok(_) => println!("🗑️ Removed cache file: {:?}", path),
-                                            Err(e) => {
-                                                println!("⚠️ Failed to remove cache file: {}", e)
-                                            }
+                        if let Some(rid) = &to_remove_id {
+                            subs.retain(|s| &s.id != rid);
+                        }
+                        Ok(to_remove_id.filter(|_| subs.len() < len_before))
+                    })?;
+
+                    if let Some(rid) = removed {
+                        println!("✅ Removed subscription: {}", rid);
+
+                        // Remove cache file
+                        match storage::get_subscription_cache_path(&rid) {
+                            Ok(path) => {
+                                if path.exists() {
+                                    match std::fs::remove_file(&path) {
+                                        Ok(_) => println!("🗑️ Removed cache file: {:?}", path),
+                                        Err(e) => {
+                                            println!("⚠️ Failed to remove cache file: {}", e)
                                         }
                                     }
                                 }
-                                Err(e) => println!("⚠️ Failed to get cache path: {}", e),
                             }
-                        } else {
-                            println!("❌ Subscription not found.");
+                            Err(e) => println!("⚠️ Failed to get cache path: {}", e),
                         }
+                    } else {
+                        println!("❌ Subscription not found.");
                     }
                 }
                 SubCommands::Enable { id } => {
-                    let mut subs = storage::load_subscriptions().unwrap_or_default();
-                    let mut found = false;
-
-                    // Try to find subscription by name first (most user-friendly)
-                    for sub in &mut subs {
-                        if sub.name == id {
-                            sub.enabled = true;
-                            found = true;
-                            println!("✅ Enabled subscription: {} ({})", sub.name, sub.id);
-                            break;
-                        }
-                    }
+                    let (found, subs) = storage::modify_subscriptions(|subs| {
+                        let mut found = false;
 
-                    // If not found by name, try by ID
-                    if !found {
-                        for sub in &mut subs {
-                            if sub.id == id {
+                        // Try to find subscription by name first (most user-friendly)
+                        for sub in subs.iter_mut() {
+                            if sub.name == id {
                                 sub.enabled = true;
                                 found = true;
                                 println!("✅ Enabled subscription: {} ({})", sub.name, sub.id);
                                 break;
                             }
                         }
-                    }
 
-                    // If still not found, try by index
-                    if !found {
-                        if let Ok(idx) = id.parse::<usize>() {
-                            if idx < subs.len() {
-                                subs[idx].enabled = true;
-                                found = true;
-                                println!(
-                                    "✅ Enabled subscription: {} ({})",
-                                    subs[idx].name, subs[idx].id
-                                );
+                        // If not found by name, try by ID
+                        if !found {
+                            for sub in subs.iter_mut() {
+                                if sub.id == id {
+                                    sub.enabled = true;
+                                    found = true;
+                                    println!("✅ Enabled subscription: {} ({})", sub.name, sub.id);
+                                    break;
+                                }
                             }
                         }
-                    }
 
-                    if found {
-                        storage::save_subscriptions(&subs)?;
+                        // If still not found, try by index
+                        if !found {
+                            if let Ok(idx) = id.parse::<usize>() {
+                                if idx < subs.len() {
+                                    subs[idx].enabled = true;
+                                    found = true;
+                                    println!(
+                                        "✅ Enabled subscription: {} ({})",
+                                        subs[idx].name, subs[idx].id
+                                    );
+                                }
+                            }
+                        }
+
+                        Ok((found, subs.clone()))
+                    })?;
 
+                    if found {
                         // Automatically run merge
                         println!("🔄 Triggering merge after enabling subscription...");
                         match proxy::merge_configs(&subs).await {
                             Ok(merged) => {
-                                let output_path = storage::get_current_config_path()?;
-                                hangar_lib::config::save_config(
-                                    &merged,
-                                    output_path.to_str().unwrap(),
-                                )?;
+                                proxy::write_current_config(&merged)?;
                                 println!("✅ Config regenerated.");
                             }
                             Err(e) => println!("❌ Auto-merge failed: {}", e),
@@ -315,57 +1165,54 @@ async fn async_main() -> anyhow::Result<()> {
                     }
                 }
                 SubCommands::Disable { id } => {
-                    let mut subs = storage::load_subscriptions().unwrap_or_default();
-                    let mut found = false;
-
-                    // Try to find subscription by name first (most user-friendly)
-                    for sub in &mut subs {
-                        if sub.name == id {
-                            sub.enabled = false;
-                            found = true;
-                            println!("✅ Disabled subscription: {} ({})", sub.name, sub.id);
-                            break;
-                        }
-                    }
+                    let (found, subs) = storage::modify_subscriptions(|subs| {
+                        let mut found = false;
 
-                    // If not found by name, try by ID
-                    if !found {
-                        for sub in &mut subs {
-                            if sub.id == id {
+                        // Try to find subscription by name first (most user-friendly)
+                        for sub in subs.iter_mut() {
+                            if sub.name == id {
                                 sub.enabled = false;
                                 found = true;
                                 println!("✅ Disabled subscription: {} ({})", sub.name, sub.id);
                                 break;
                             }
                         }
-                    }
 
-                    // If still not found, try by index
-                    if !found {
-                        if let Ok(idx) = id.parse::<usize>() {
-                            if idx < subs.len() {
-                                subs[idx].enabled = false;
-                                found = true;
-                                println!(
-                                    "✅ Disabled subscription: {} ({})",
-                                    subs[idx].name, subs[idx].id
-                                );
+                        // If not found by name, try by ID
+                        if !found {
+                            for sub in subs.iter_mut() {
+                                if sub.id == id {
+                                    sub.enabled = false;
+                                    found = true;
+                                    println!("✅ Disabled subscription: {} ({})", sub.name, sub.id);
+                                    break;
+                                }
                             }
                         }
-                    }
 
-                    if found {
-                        storage::save_subscriptions(&subs)?;
+                        // If still not found, try by index
+                        if !found {
+                            if let Ok(idx) = id.parse::<usize>() {
+                                if idx < subs.len() {
+                                    subs[idx].enabled = false;
+                                    found = true;
+                                    println!(
+                                        "✅ Disabled subscription: {} ({})",
+                                        subs[idx].name, subs[idx].id
+                                    );
+                                }
+                            }
+                        }
+
+                        Ok((found, subs.clone()))
+                    })?;
 
+                    if found {
                         // Automatically run merge
                         println!("🔄 Triggering merge after disabling subscription...");
                         match proxy::merge_configs(&subs).await {
                             Ok(merged) => {
-                                let output_path = storage::get_current_config_path()?;
-                                hangar_lib::config::save_config(
-                                    &merged,
-                                    output_path.to_str().unwrap(),
-                                )?;
+                                proxy::write_current_config(&merged)?;
                                 println!("✅ Config regenerated.");
                             }
                             Err(e) => println!("❌ Auto-merge failed: {}", e),
@@ -376,7 +1223,7 @@ async fn async_main() -> anyhow::Result<()> {
                     }
                 }
                 SubCommands::Update { name_or_id } => {
-                    let mut subs = storage::load_subscriptions().unwrap_or_default();
+                    let subs = storage::load_subscriptions().unwrap_or_default();
                     let mut found_sub: Option<types::Subscription> = None;
 
                     // Try to find subscription by name first
@@ -419,16 +1266,16 @@ async fn async_main() -> anyhow::Result<()> {
                                             println!("   - {}", name);
                                         }
 
-                                        // Update the subscription in the list
-                                        for s in &mut subs {
-                                            if s.id == sub.id {
+                                        // Update the subscription in the list, reloaded since it may have changed during the download
+                                        storage::modify_subscriptions(|subs| {
+                                            if let Some(s) =
+                                                subs.iter_mut().find(|s| s.id == sub.id)
+                                            {
                                                 s.node_count = sub.node_count;
                                                 s.last_updated = sub.last_updated.clone();
-                                                break;
                                             }
-                                        }
-
-                                        storage::save_subscriptions(&subs)?;
+                                            Ok(())
+                                        })?;
                                         println!("✅ Subscription updated successfully");
                                     }
                                     Err(e) => {
@@ -462,8 +1309,7 @@ async fn async_main() -> anyhow::Result<()> {
 
             match proxy::merge_configs(&subs).await {
                 Ok(merged) => {
-                    let output_path = storage::get_current_config_path()?;
-                    hangar_lib::config::save_config(&merged, output_path.to_str().unwrap())?;
+                    let output_path = proxy::write_current_config(&merged)?;
                     println!("✅ Config generated and saved to {:?}", output_path);
                 }
                 Err(e) => println!("❌ Merge failed: {}", e),
@@ -474,378 +1320,481 @@ async fn async_main() -> anyhow::Result<()> {
             host,
             interval,
             daemon,
+            tls_cert,
+            tls_key,
+            allow_unauthenticated,
         } => {
+            let serve_args = daemon::ServeArgs {
+                host: host.clone(),
+                port,
+                interval,
+                tls_cert,
+                tls_key,
+                allow_unauthenticated,
+            };
+            let tls = serve_args.tls()?;
+            let scheme = server::scheme(&tls);
+            let pid_path = storage::get_server_pid_path()?;
+
+            if let Some(pid) = daemon::running_pid(&pid_path)? {
+                println!("❌ Server is already running with PID: {}", pid);
+                return Ok(());
+            }
+            // Checked here as well, so a daemon start fails in the foreground
+            server::check_unprotected(&host, allow_unauthenticated)?;
+            // Remembered for `hangar restart` and `hangar status`
+            daemon::save_serve_args(&serve_args)?;
+
             // Handle daemon mode
             if daemon {
-                let pid_path = storage::get_server_pid_path()?;
-                if pid_path.exists() {
-                    let pid_str = std::fs::read_to_string(&pid_path)?;
-                    if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                        // Check if process exists (cross-platform is hard, but simple check for unix)
-                        #[cfg(unix)]
-                        if unsafe { libc::kill(pid, 0) == 0 } {
-                            println!("❌ Server is already running with PID: {}", pid);
-                            return Ok(());
-                        }
-                    }
-                }
-
                 println!("🔄 Starting server in daemon mode...");
 
                 // Get the current executable path
                 let exe = std::env::current_exe()?;
 
-                // Build arguments without the --daemon flag
-                let mut args: Vec<String> = std::env::args().collect();
-                args.retain(|arg| arg != "--daemon" && arg != "-d");
-
-                // Get the log file path
+                // The server writes its own rotated log; raw output only catches what
+                // happens before logging starts
                 let log_path = storage::get_server_log_path()?;
+                let output_path = storage::get_daemon_output_path()?;
                 println!("📝 Logs will be written to: {:?}", log_path);
 
-                // Spawn the process in the background
-                let child = std::process::Command::new(&exe)
-                    .args(&args[1..]) // Skip the executable name
+                // Spawn the process in the background; it takes the PID lock itself
+                let output = std::fs::File::create(&output_path)?;
+                let mut child = std::process::Command::new(&exe)
+                    .args(serve_args.to_cli_args())
+                    .env(logging::DAEMON_ENV, "1")
                     .stdin(std::process::Stdio::null())
-                    .stdout(std::process::Stdio::from(
-                        std::fs::OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open(&log_path)?,
-                    ))
-                    .stderr(std::process::Stdio::from(
-                        std::fs::OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open(&log_path)?,
-                    ))
+                    .stdout(std::process::Stdio::from(output.try_clone()?))
+                    .stderr(std::process::Stdio::from(output))
                     .spawn()?;
 
+                // Wait until the server holds the lock, or report an early exit
+                let started = std::time::Instant::now();
+                while started.elapsed() < std::time::Duration::from_secs(5) {
+                    if daemon::running_pid(&pid_path)? == Some(child.id() as i32) {
+                        break;
+                    }
+                    if let Some(status) = child.try_wait()? {
+                        println!("❌ Server exited during startup ({})", status);
+                        println!("   See {:?} and {:?}", log_path, output_path);
+                        return Ok(());
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                }
+
                 println!("✅ Server started in background with PID: {}", child.id());
-                println!("   Address: http://{}:{}/config", host, port);
+                println!("   Address: {}://{}:{}/config", scheme, host, port);
                 println!("   Log file: {:?}", log_path);
-                println!("\nTo stop the server, run: hangar stop");
-
-                // Save PID to file for easy management
-                std::fs::write(&pid_path, child.id().to_string())?;
                 println!("   PID file: {:?}", pid_path);
+                println!("\nTo stop the server, run: hangar stop");
 
                 return Ok(());
             }
 
-            println!("🚀 Starting server at http://{}:{}/config", host, port);
-
-            // Initial load
-            let subs = storage::load_subscriptions().unwrap_or_default();
-            let merged = proxy::merge_configs(&subs).await?;
-
-            // Save initial config to current.yaml
-            let current_config_path = storage::get_current_config_path()?;
-            hangar_lib::config::save_config(&merged, current_config_path.to_str().unwrap())?;
-
-            let (_tx, rx) = tokio::sync::oneshot::channel::<()>();
-
-            // Create shared state for server
-            let state = server::AppState {
-                config: std::sync::Arc::new(tokio::sync::RwLock::new(merged)),
-            };
-
-            // Clone state for file watcher
-            let watcher_state = state.clone();
-            let watch_path = current_config_path.clone();
-
-            // Spawn file watcher task
-            tokio::task::spawn_blocking(move || {
-                use notify::{Event, EventKind, RecursiveMode, Watcher};
-                use std::sync::{Arc, Mutex};
-                use std::time::{Duration, Instant};
-
-                let (tx_notify, rx_notify) =
-                    std::sync::mpsc::channel::<Result<Event, notify::Error>>();
-
-                let mut watcher =
-                    notify::recommended_watcher(tx_notify).expect("Failed to create file watcher");
-
-                watcher
-                    .watch(&watch_path, RecursiveMode::NonRecursive)
-                    .expect("Failed to watch current.yaml");
-
-                println!("👀 Watching for changes to {:?}", watch_path);
-
-                let runtime = tokio::runtime::Handle::current();
-
-                // Debounce mechanism to prevent duplicate events
-                let last_reload = Arc::new(Mutex::new(Instant::now()));
-                let debounce_duration = Duration::from_millis(100);
-
-                for res in rx_notify {
-                    match res {
-                        Ok(event) => {
-                            // Check if it's a modify event with data changes
-                            if matches!(
-                                event.kind,
-                                EventKind::Modify(notify::event::ModifyKind::Data(_))
-                            ) {
-                                // Debounce: only reload if enough time has passed since last reload
-                                let mut last = last_reload.lock().unwrap();
-                                let now = Instant::now();
-
-                                if now.duration_since(*last) > debounce_duration {
-                                    *last = now;
-                                    drop(last); // Release lock before async operation
-
-                                    println!("📝 Detected change in current.yaml, reloading...");
-
-                                    let state = watcher_state.clone();
-                                    let path = watch_path.clone();
+            let _pid_lock = daemon::PidLock::acquire(&pid_path)?;
+            let signals = daemon::ServerSignals::register()?;
+
+            println!(
+                "🚀 Starting server at {}://{}:{}/config",
+                scheme, host, port
+            );
+
+            let state = server::AppState::load_initial().await?;
+            let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+            signals.spawn(state.clone(), tx);
+
+            server::start_server(
+                state,
+                server::ServeOptions {
+                    host,
+                    port,
+                    interval,
+                    tls,
+                    allow_unauthenticated,
+                },
+                rx,
+            )
+            .await?;
+        }
+        Commands::Cert { subcommand } => match subcommand {
+            CertCommands::SelfSigned { hosts, dir } => {
+                let dir = match dir {
+                    Some(dir) => dir,
+                    None => storage::get_tls_dir()?,
+                };
+                let hosts = if hosts.is_empty() {
+                    tls::default_hosts()
+                } else {
+                    hosts
+                };
 
-                                    runtime.spawn(async move {
-                                        match state.reload_from_file(&path).await {
-                                            Ok(_) => println!("✅ Config reloaded successfully"),
-                                            Err(e) => {
-                                                eprintln!("❌ Failed to reload config: {}", e)
-                                            }
-                                        }
-                                    });
-                                }
-                            }
-                        }
-                        Err(e) => eprintln!("⚠️ Watch error: {}", e),
-                    }
+                let files = tls::generate_self_signed(&dir, &hosts)?;
+                if files.reused_ca {
+                    println!("🔐 Reused existing CA: {:?}", files.ca_cert);
+                } else {
+                    println!("🔐 Created local CA: {:?}", files.ca_cert);
                 }
-            });
-
-            // If interval > 0, spawn update task
-            if interval > 0 {
-                let duration = std::time::Duration::from_secs(interval);
-                let _update_state = state.clone();
-
-                tokio::spawn(async move {
-                    loop {
-                        tokio::time::sleep(duration).await;
-                        println!("⏰ Auto-updating subscriptions...");
-                        // Reload subs in case they changed
-                        let mut subs = storage::load_subscriptions().unwrap_or_default();
-                        let mut any_updated = false;
-
-                        for sub in &mut subs {
-                            if sub.enabled {
-                                match subscription::download_subscription(sub).await {
-                                    Ok(_) => {
-                                        sub.last_updated = Some(
-                                            chrono::Local::now()
-                                                .format("%Y-%m-%d %H:%M:%S")
-                                                .to_string(),
-                                        );
-                                        any_updated = true;
-                                    }
-                                    Err(e) => {
-                                        println!(
-                                            "⚠️ Failed to update subscription {}: {}",
-                                            sub.name, e
-                                        )
-                                    }
-                                }
-                            }
-                        }
-
-                        if any_updated {
-                            if let Err(e) = storage::save_subscriptions(&subs) {
-                                println!("❌ Failed to save updated subscriptions: {}", e);
-                            }
-                        }
+                println!("✅ Certificate for {} written:", hosts.join(", "));
+                println!("   Cert: {:?}", files.cert);
+                println!("   Key:  {:?}", files.key);
 
-                        // Re-merge
-                        match proxy::merge_configs(&subs).await {
-                            Ok(new_config) => {
-                                // Save to current.yaml
-                                let _ = storage::get_current_config_path().map(|p| {
-                                    hangar_lib::config::save_config(
-                                        &new_config,
-                                        p.to_str().unwrap(),
-                                    )
-                                });
-                                println!("✅ Auto-merge complete.");
-                                // The file watcher will automatically reload the config
-                            }
-                            Err(e) => println!("❌ Auto-merge failed: {}", e),
-                        }
-                    }
-                });
+                let tls_config = types::TlsConfig {
+                    cert_path: files.cert.to_string_lossy().to_string(),
+                    key_path: files.key.to_string_lossy().to_string(),
+                };
+                storage::update_hangar_config(serde_json::json!({
+                    "server": { "tls": tls_config }
+                }))?;
+                println!("✅ TLS enabled for `hangar serve` (server.tls in config.json)");
+                println!(
+                    "\n📱 Install and trust {:?} on your devices to use https:// subscription links",
+                    files.ca_cert
+                );
             }
+        },
+        Commands::Stop => {
+            stop_server().await?;
+        }
+        Commands::Restart => {
+            stop_server().await?;
 
-            // Start server with the initial state
-            let addr = format!("{}:{}", host, port);
-            let listener = tokio::net::TcpListener::bind(&addr).await?;
-
-            println!("\n🌐 Server started successfully");
-            println!("   Address: http://{}", addr);
-            println!("   Config URL: http://{}/config", addr);
-            println!("\n✨ Waiting for requests...\n");
-
-            let app =
-                axum::Router::new()
-                    .route(
-                        "/config",
-                        axum::routing::get(
-                            |axum::extract::State(state): axum::extract::State<
-                                server::AppState,
-                            >,
-                             axum::extract::Query(query): axum::extract::Query<
-                                server::ConfigQuery,
-                            >| async move {
-                                if let Some(true) = query.refresh {
-                                    if let Err(e) = state.refresh().await {
-                                        return (
-                                            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                                            format!("Failed to refresh config: {}", e),
-                                        )
-                                            .into_response();
-                                    }
-                                }
+            let args = daemon::load_serve_args()?;
+            let mut cli_args = match &args {
+                Some(args) => args.to_cli_args(),
+                None => vec!["serve".to_string()],
+            };
+            cli_args.push("--daemon".to_string());
 
-                                let config = state.config.read().await;
-                                match serde_yaml::to_string(&*config) {
-                                    Ok(yaml) => (axum::http::StatusCode::OK, yaml).into_response(),
-                                    Err(e) => (
-                                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                                        format!("Failed to serialize config: {}", e),
-                                    )
-                                        .into_response(),
-                                }
-                            },
-                        ),
-                    )
-                    .route("/health", axum::routing::get(|| async { "OK" }))
-                    .with_state(state);
-
-            axum::serve(listener, app)
-                .with_graceful_shutdown(async move {
-                    let _ = rx.await;
-                    eprintln!("🛑 Shutting down server...");
-                })
-                .await?;
+            println!("🚀 Restarting server...");
+            if args.is_none() {
+                println!("   No saved `serve` arguments, using defaults");
+            }
+            let exe = std::env::current_exe()?;
+            std::process::Command::new(&exe).args(&cli_args).status()?;
         }
-        Commands::Stop => {
-            let pid_path = storage::get_server_pid_path()?;
-            if !pid_path.exists() {
-                println!("❌ Server is not running (no PID file found)");
+        Commands::Reload => {
+            let Some(pid) = daemon::running_pid(&storage::get_server_pid_path()?)? else {
+                println!("❌ Server is not running");
                 return Ok(());
-            }
-
-            let pid_str = std::fs::read_to_string(&pid_path)?;
-            let pid = pid_str
-                .trim()
-                .parse::<i32>()
-                .context("Failed to parse PID")?;
-
-            println!("🛑 Stopping server (PID: {})...", pid);
+            };
 
             #[cfg(unix)]
             {
-                use std::time::{Duration, Instant};
-                let res = unsafe { libc::kill(pid, 15) }; // SIGTERM
-                if res == 0 {
-                    // Wait for it to stop
-                    let start = Instant::now();
-                    while start.elapsed() < Duration::from_secs(5) {
-                        if unsafe { libc::kill(pid, 0) } != 0 {
-                            break;
-                        }
-                        tokio::time::sleep(Duration::from_millis(200)).await;
-                    }
-
-                    if unsafe { libc::kill(pid, 0) } == 0 {
-                        println!("⚠️ Server didn't stop gracefully, force killing...");
-                        unsafe { libc::kill(pid, 9) }; // SIGKILL
-                    }
-
-                    let _ = std::fs::remove_file(&pid_path);
-                    println!("✅ Server stopped");
+                if unsafe { libc::kill(pid, libc::SIGHUP) } == 0 {
+                    println!(
+                        "🔁 Asked the server (PID: {}) to re-read subscriptions and re-merge",
+                        pid
+                    );
                 } else {
-                    println!("❌ Failed to stop server: process not found or no permission");
-                    let _ = std::fs::remove_file(&pid_path);
+                    println!("❌ Failed to signal server: process not found or no permission");
                 }
             }
 
             #[cfg(not(unix))]
             {
-                println!("❌ Stop command is currently only supported on Unix-like systems.");
+                let _ = pid;
+                println!("❌ Reload command is currently only supported on Unix-like systems.");
             }
         }
-        Commands::Restart => {
-            // Stop
-            let pid_path = storage::get_server_pid_path()?;
-            if pid_path.exists() {
-                let pid_str = std::fs::read_to_string(&pid_path)?;
-                if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                    println!("🛑 Stopping server (PID: {})...", pid);
-                    #[cfg(unix)]
-                    {
-                        unsafe { libc::kill(pid, 15) };
-                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                    }
+        Commands::Service { subcommand } => match subcommand {
+            ServiceCommands::Install {
+                port,
+                host,
+                interval,
+                allow_unauthenticated,
+            } => {
+                if !cfg!(target_os = "linux") {
+                    println!("❌ systemd services are only supported on Linux");
+                    return Ok(());
+                }
+                if let Some(pid) = daemon::running_pid(&storage::get_server_pid_path()?)? {
+                    println!(
+                        "❌ A server is already running (PID: {}), stop it first with: hangar stop",
+                        pid
+                    );
+                    return Ok(());
                 }
-                let _ = std::fs::remove_file(&pid_path);
-            }
 
-            // Start - we need to know the default port/host or previous ones?
-            // For simplicity, we just run 'serve --daemon' with defaults or we could try to persist last used args.
-            // But usually defaults are fine or user can just run start again.
-            // Let's just trigger the same logic as Serve but with default args.
-            println!("🚀 Restarting server...");
-            let exe = std::env::current_exe()?;
-            std::process::Command::new(&exe)
-                .arg("serve")
-                .arg("--daemon")
-                .spawn()?;
-            println!("✅ Restart initiated.");
-        }
-        Commands::Status => {
+                let saved = daemon::load_serve_args()?;
+                let args = daemon::ServeArgs {
+                    host: host
+                        .or(saved.as_ref().map(|a| a.host.clone()))
+                        .unwrap_or_else(|| "127.0.0.1".to_string()),
+                    port: port.or(saved.as_ref().map(|a| a.port)).unwrap_or(8080),
+                    interval: interval.or(saved.as_ref().map(|a| a.interval)).unwrap_or(0),
+                    tls_cert: saved.as_ref().and_then(|a| a.tls_cert.clone()),
+                    tls_key: saved.as_ref().and_then(|a| a.tls_key.clone()),
+                    allow_unauthenticated: allow_unauthenticated
+                        || saved.as_ref().is_some_and(|a| a.allow_unauthenticated),
+                };
+                server::check_unprotected(&args.host, args.allow_unauthenticated)?;
+
+                let exe = std::env::current_exe()?;
+                let path = service::install(&exe, &args)?;
+                println!("✅ Installed and started {}", service::UNIT_NAME);
+                println!("   Unit file: {:?}", path);
+                println!(
+                    "   Address: {}://{}:{}/config",
+                    server::scheme(&args.tls()?),
+                    args.host,
+                    args.port
+                );
+                println!("\n📝 Logs: journalctl --user -u hangar -f");
+                println!("   Start at boot without logging in: loginctl enable-linger $USER");
+            }
+            ServiceCommands::Uninstall => {
+                if service::uninstall()? {
+                    println!("✅ Removed {}", service::UNIT_NAME);
+                } else {
+                    println!("❌ {} is not installed", service::UNIT_NAME);
+                }
+            }
+            ServiceCommands::Status => {
+                println!("Unit file: {:?}", service::unit_path()?);
+                service::status()?;
+            }
+        },
+        Commands::Status { port, host, token } => {
             let pid_path = storage::get_server_pid_path()?;
             let log_path = storage::get_server_log_path()?;
 
-            if !pid_path.exists() {
-                println!("❌ Server is not running");
-                return Ok(());
+            let pid = daemon::running_pid(&pid_path)?;
+            if let Some(pid) = pid {
+                println!("✅ Server process is running (PID: {})", pid);
+                println!("   Log file: {:?}", log_path);
             }
 
-            let pid_str = std::fs::read_to_string(&pid_path)?;
-            if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                #[cfg(unix)]
-                if unsafe { libc::kill(pid, 0) } == 0 {
-                    println!("✅ Server is running (PID: {})", pid);
-                    println!("   Log file: {:?}", log_path);
-                    return Ok(());
+            // Default to where the last `serve` listened
+            let saved = daemon::load_serve_args()?;
+            let port = port.or(saved.as_ref().map(|a| a.port)).unwrap_or(8080);
+            let host = host
+                .or(saved.as_ref().map(|a| a.host.clone()))
+                .unwrap_or_else(|| "127.0.0.1".to_string());
+            let tls = saved.as_ref().is_some_and(|a| a.tls_cert.is_some())
+                || storage::load_hangar_config()?.server.tls.is_some();
+
+            let token = token.or_else(|| std::env::var("HANGAR_TOKEN").ok());
+            match server::fetch_status(&host, port, tls, token.as_deref()).await {
+                Ok(status) => print_server_status(&status),
+                Err(e) if pid.is_some() => {
+                    println!("⚠️ Server is not answering on {}:{}: {}", host, port, e);
                 }
-                println!("❌ Server is not running (stale PID file)");
-                let _ = std::fs::remove_file(&pid_path);
-            } else {
-                println!("❌ Invalid PID file content");
+                Err(_) => println!("❌ Server is not running"),
             }
         }
-        Commands::Logs { follow } => {
+        Commands::Logs {
+            follow,
+            level,
+            target,
+            lines,
+        } => {
             let log_path = storage::get_server_log_path()?;
             if !log_path.exists() {
                 println!("❌ Log file not found: {:?}", log_path);
                 return Ok(());
             }
 
+            let filter = logging::LogFilter::new(level.as_deref(), target)?;
+            for line in logging::tail(&log_path, &filter, lines)? {
+                println!("{}", line);
+            }
             if follow {
-                let mut child = std::process::Command::new("tail")
-                    .arg("-f")
-                    .arg(&log_path)
-                    .spawn()?;
-                child.wait()?;
-            } else {
-                let output = std::process::Command::new("tail")
-                    .arg("-n")
-                    .arg("50")
-                    .arg(&log_path)
-                    .output()?;
-                println!("{}", String::from_utf8_lossy(&output.stdout));
+                logging::follow(&log_path, &filter).await?;
+            }
+        }
+        Commands::Rules { subcommand } => match subcommand {
+            RulesCommands::List => {
+                let sources = rules::load_rule_sources()?;
+                if sources.is_empty() {
+                    println!("No rule sources found.");
+                } else {
+                    println!(
+                        "{:<36} {:<20} {:<8} {:<8} {:<10} {:<26} Last Error",
+                        "ID", "Name", "Enabled", "Entries", "Interval", "Last Updated"
+                    );
+                    for source in sources {
+                        println!(
+                            "{:<36} {:<20} {:<8} {:<8} {:<10} {:<26} {}",
+                            source.id,
+                            source.name,
+                            if source.enabled { "✓" } else { "✗" },
+                            source
+                                .entry_count
+                                .map_or_else(|| "-".to_string(), |c| c.to_string()),
+                            format!("{}s", source.interval),
+                            source.last_updated.unwrap_or_else(|| "N/A".to_string()),
+                            source.last_error.unwrap_or_default()
+                        );
+                    }
+                }
+            }
+            RulesCommands::Add {
+                url,
+                name,
+                interval,
+            } => {
+                let name = name.unwrap_or_else(|| "Untitled".to_string());
+                let source = rules::add_rule_source(name, url, Some(interval))?;
+                println!("✅ Added rule source: {} ({})", source.name, source.id);
+
+                println!("📥 Downloading rule source: {}...", source.name);
+                match rules::refresh_rule_source(&source.id).await {
+                    Ok(s) => println!("✅ Found {} entries", s.entry_count.unwrap_or(0)),
+                    Err(e) => println!("⚠️ Failed to download: {}", e),
+                }
+            }
+            RulesCommands::Remove { id } => match rules::resolve_rule_source(&id) {
+                Ok(source) => {
+                    rules::remove_rule_source(&source.id)?;
+                    println!("✅ Removed rule source: {} ({})", source.name, source.id);
+
+                    let cache_path =
+                        rules::get_remote_rules_cache_dir()?.join(format!("{}.txt", source.id));
+                    if cache_path.exists() {
+                        match std::fs::remove_file(&cache_path) {
+                            Ok(_) => println!("🗑️ Removed cache file: {:?}", cache_path),
+                            Err(e) => println!("⚠️ Failed to remove cache file: {}", e),
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("❌ {}", e);
+                    println!("   Try using the name, ID, or index from 'hangar rules list'");
+                }
+            },
+            RulesCommands::Enable { id } => match rules::set_rule_source_enabled(&id, true) {
+                Ok(source) => println!("✅ Enabled rule source: {} ({})", source.name, source.id),
+                Err(e) => println!("❌ {}", e),
+            },
+            RulesCommands::Disable { id } => match rules::set_rule_source_enabled(&id, false) {
+                Ok(source) => println!("✅ Disabled rule source: {} ({})", source.name, source.id),
+                Err(e) => println!("❌ {}", e),
+            },
+            RulesCommands::Refresh { id } => match id {
+                Some(id) => {
+                    println!("📥 Refreshing rule source: {}...", id);
+                    match rules::refresh_rule_source(&id).await {
+                        Ok(source) => println!(
+                            "✅ Refreshed {} ({} entries)",
+                            source.name,
+                            source.entry_count.unwrap_or(0)
+                        ),
+                        Err(e) => println!("❌ Refresh failed: {}", e),
+                    }
+                }
+                None => {
+                    println!("📥 Refreshing builtin rules and all rule sources...");
+                    rules::refresh_all_rules().await?;
+                    let sources = rules::load_rule_sources()?;
+                    let failed: Vec<_> = sources
+                        .iter()
+                        .filter(|s| s.enabled && s.last_error.is_some())
+                        .collect();
+                    for source in &failed {
+                        println!(
+                            "⚠️ {}: {}",
+                            source.name,
+                            source.last_error.as_deref().unwrap_or_default()
+                        );
+                    }
+                    println!("✅ Rules refreshed ({} sources failed)", failed.len());
+                }
+            },
+        },
+        Commands::Token { subcommand } => {
+            match subcommand {
+                TokenCommands::List => {
+                    let config = storage::load_hangar_config()?;
+                    if config.server.tokens.is_empty() {
+                        println!("No tokens found. The config server is open to anyone who can reach it.");
+                    } else {
+                        let now = chrono::Utc::now().timestamp();
+                        println!(
+                            "{:<36} {:<20} {:<16} {:<6} {:<20} Created",
+                            "ID", "Name", "Profile", "Admin", "Expires"
+                        );
+                        for token in config.server.tokens {
+                            let format_ts = |ts: i64| {
+                                chrono::DateTime::from_timestamp(ts, 0)
+                                    .map(|t| {
+                                        t.with_timezone(&chrono::Local)
+                                            .format("%Y-%m-%d %H:%M")
+                                            .to_string()
+                                    })
+                                    .unwrap_or_else(|| ts.to_string())
+                            };
+                            let expires = match token.expires_at {
+                                Some(ts) if ts <= now => format!("{} ✗", format_ts(ts)),
+                                Some(ts) => format_ts(ts),
+                                None => "never".to_string(),
+                            };
+                            println!(
+                                "{:<36} {:<20} {:<16} {:<6} {:<20} {}",
+                                token.id,
+                                token.name,
+                                token.profile,
+                                if token.admin { "✓" } else { "" },
+                                expires,
+                                format_ts(token.created_at)
+                            );
+                        }
+                    }
+                }
+                TokenCommands::Add {
+                    name,
+                    profile,
+                    expires,
+                    admin,
+                } => {
+                    let expires_at = expires
+                        .map(|e| auth::parse_expiry(&e, chrono::Utc::now().timestamp()))
+                        .transpose()?;
+                    match auth::create_token(&name, &profile, expires_at, admin) {
+                        Ok((secret, token)) => {
+                            println!("✅ Created token: {} ({})", token.name, token.id);
+                            println!("   Profile: {}", token.profile);
+                            if token.admin {
+                                println!("   Admin: /api management endpoints allowed");
+                            }
+                            println!("\n🔑 {}", secret);
+                            println!("\nThis secret is shown only once. Use it as:");
+                            println!("   http://<host>:<port>/config?token={}", secret);
+                            println!("   or the header 'Authorization: Bearer {}'", secret);
+                        }
+                        Err(e) => println!("❌ {}", e),
+                    }
+                }
+                TokenCommands::Revoke { id } => match auth::revoke_token(&id) {
+                    Ok(token) => println!("✅ Revoked token: {} ({})", token.name, token.id),
+                    Err(e) => println!("❌ {}", e),
+                },
             }
         }
+        Commands::Profile { subcommand } => match subcommand {
+            ProfileCommands::List => {
+                let config = storage::load_hangar_config()?;
+                println!("{:<20} Subscriptions", "Name");
+                println!("{:<20} (all)", "default");
+                for profile in config.profiles {
+                    println!("{:<20} {}", profile.name, profile.subscriptions.join(", "));
+                }
+            }
+            ProfileCommands::Add {
+                name,
+                subscriptions,
+            } => match profile::add_profile(&name, subscriptions) {
+                Ok(profile) => println!(
+                    "✅ Added profile: {} ({})",
+                    profile.name,
+                    profile.subscriptions.join(", ")
+                ),
+                Err(e) => println!("❌ {}", e),
+            },
+            ProfileCommands::Remove { name } => match profile::remove_profile(&name) {
+                Ok(_) => println!("✅ Removed profile: {}", name),
+                Err(e) => println!("❌ {}", e),
+            },
+        },
         Commands::Ai { prompt } => {
             println!("🤖 Processing AI request: \"{}\"", prompt);
             let result = ai::generate_config_patch(&prompt).await?;
@@ -855,26 +1804,27 @@ async fn async_main() -> anyhow::Result<()> {
             let target_file = match result.target.as_str() {
                 "basic" => storage::get_basic_config_path()?,
                 "groups" => storage::get_groups_config_path()?,
+                "local" => rules::ensure_rules_overlay_exists()?,
                 _ => storage::get_current_config_path()?,
             };
 
             if target_file.exists() {
                 let content = std::fs::read_to_string(&target_file)?;
                 let patched = ai::apply_patch_to_config(&content, &result.operations)?;
-                version::save_version(&result.target, &prompt, &content)?;
-                std::fs::write(&target_file, patched)?;
+                version::save_version(&result.target, types::VersionSource::Ai, &prompt, &content)?;
+                storage::write_atomic(&target_file, patched)?;
+                git_history::record(types::VersionSource::Ai, &prompt);
                 println!(
                     "✅ Applied changes to {:?} and created backup.",
                     target_file
                 );
 
-                // If we modified basic or groups, we should re-merge
-                if result.target == "basic" || result.target == "groups" {
+                // If we modified basic, groups or the rules overlay, we should re-merge
+                if matches!(result.target.as_str(), "basic" | "groups" | "local") {
                     println!("🔄 Triggering merge after base config change...");
                     let subs = storage::load_subscriptions().unwrap_or_default();
                     let merged = proxy::merge_configs(&subs).await?;
-                    let output_path = storage::get_current_config_path()?;
-                    hangar_lib::config::save_config(&merged, output_path.to_str().unwrap())?;
+                    proxy::write_current_config(&merged)?;
                     println!("✅ Config regenerated.");
                 }
             } else {
@@ -884,26 +1834,152 @@ async fn async_main() -> anyhow::Result<()> {
         Commands::History { subcommand } => match subcommand.unwrap_or(HistoryCommands::List) {
             HistoryCommands::List => {
                 let versions = version::list_versions()?;
-                println!("{:<45} {:<20} {}", "ID", "Time", "Description");
+                println!(
+                    "{:<8} {:<20} {:<8} {:<9} Description",
+                    "Version", "Time", "Target", "Source"
+                );
                 for v in versions {
                     let dt = chrono::DateTime::from_timestamp(v.timestamp, 0)
                         .unwrap_or_default()
                         .with_timezone(&chrono::Local);
                     let time_str = dt.format("%Y-%m-%d %H:%M:%S").to_string();
-                    println!("{:<45} {:<20} {}", v.id, time_str, v.description);
+                    let tags = if v.tags.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", v.tags.join(", "))
+                    };
+                    println!(
+                        "{:<8} {:<20} {:<8} {:<9} {}{}",
+                        format!("v{}", v.number),
+                        time_str,
+                        v.target,
+                        v.source.to_string(),
+                        v.description,
+                        tags
+                    );
+                    if let Some(note) = &v.note {
+                        println!("{:<8} 📝 {}", "", note);
+                    }
+                }
+            }
+            HistoryCommands::Tag { id, tag, force } => {
+                let v = version::tag_version(&id, &tag, force)?;
+                println!("🏷️ Tagged v{} as '{}'", v.number, tag);
+            }
+            HistoryCommands::Untag { tag } => {
+                let v = version::untag_version(&tag)?;
+                println!("✅ Removed tag '{}' from v{}", tag, v.number);
+            }
+            HistoryCommands::Note { id, text } => {
+                let v = version::set_version_note(&id, text.as_deref())?;
+                match &v.note {
+                    Some(_) => println!("📝 Saved note on v{}", v.number),
+                    None => println!("✅ Cleared note on v{}", v.number),
                 }
             }
             HistoryCommands::Rollback { id } => {
                 // Resolve alias first (though rollback_to_version does it too, explicit feedback is nice)
                 match version::resolve_version_id(&id) {
-                    Ok(resolved) => match version::rollback_to_version(&resolved) {
-                        Ok(_) => println!("✅ Rolled back to {} ({})", id, resolved),
+                    Ok(resolved) => match version::rollback_to_version(&resolved).await {
+                        Ok(outcome) => {
+                            println!("✅ Rolled back {:?} to {} ({})", outcome.path, id, resolved);
+                            if outcome.remerged {
+                                println!("🔄 Regenerated current.yaml from the restored file");
+                            }
+                        }
                         Err(e) => println!("❌ Rollback failed: {}", e),
                     },
                     Err(e) => println!("❌ Invalid version ID or alias: {}", e),
                 }
             }
-            HistoryCommands::Diff { v1, v2 } => {
+            HistoryCommands::Git { subcommand } => match subcommand {
+                HistoryGitCommands::Enable => {
+                    storage::modify_hangar_config(|config| {
+                        config.history.git = true;
+                        Ok(())
+                    })?;
+                    git_history::commit_current(types::VersionSource::Manual, "Start git history")?;
+                    let dir = git_history::repo_dir()?;
+                    println!("✅ Git history enabled: {:?}", dir);
+                    println!("   subscriptions.json is committed with URLs redacted.");
+                    println!(
+                        "   current.yaml is committed with proxy passwords, UUIDs, keys and the controller secret redacted."
+                    );
+                    println!(
+                        "   Push it to your own remote with: git -C {:?} remote add origin <url>",
+                        dir
+                    );
+                }
+                HistoryGitCommands::Disable => {
+                    storage::modify_hangar_config(|config| {
+                        config.history.git = false;
+                        Ok(())
+                    })?;
+                    println!("✅ Git history disabled (the repository is kept).");
+                }
+                HistoryGitCommands::Commit { message } => {
+                    match git_history::commit_current(types::VersionSource::Manual, &message)? {
+                        Some(oid) => println!("✅ Committed {}", &oid.to_string()[..8]),
+                        None => println!("✨ Nothing changed."),
+                    }
+                }
+                HistoryGitCommands::Log { limit } => {
+                    let repo = git_history::HistoryRepo::open_or_init(&git_history::repo_dir()?)?;
+                    for commit in repo.log(limit)? {
+                        let time_str = chrono::DateTime::from_timestamp(commit.timestamp, 0)
+                            .unwrap_or_default()
+                            .with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M:%S");
+                        println!(
+                            "{} {} {:<9} {} ({})",
+                            &commit.id[..8],
+                            time_str,
+                            commit.source.unwrap_or_default(),
+                            commit.summary,
+                            commit.files.join(", ")
+                        );
+                    }
+                }
+            },
+            HistoryCommands::Prune {
+                dry_run,
+                keep_last,
+                keep_daily,
+            } => {
+                let mut policy = storage::load_hangar_config()?.history;
+                if let Some(n) = keep_last {
+                    policy.keep_last = n;
+                }
+                if let Some(d) = keep_daily {
+                    policy.keep_daily_days = d;
+                }
+
+                let pruned = version::prune(&policy, dry_run)?;
+                if pruned.is_empty() {
+                    println!("✨ Nothing to prune.");
+                    return Ok(());
+                }
+                for v in &pruned {
+                    let time_str = chrono::DateTime::from_timestamp(v.timestamp, 0)
+                        .unwrap_or_default()
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M:%S");
+                    println!(
+                        "   v{:<6} {} {:<8} {:<9} {}",
+                        v.number,
+                        time_str,
+                        v.target,
+                        v.source.to_string(),
+                        v.description
+                    );
+                }
+                if dry_run {
+                    println!("🔍 {} version(s) would be pruned.", pruned.len());
+                } else {
+                    println!("🗑️ Pruned {} version(s).", pruned.len());
+                }
+            }
+            HistoryCommands::Diff { v1, v2, semantic } => {
                 let resolved_v1 = match version::resolve_version_id(&v1) {
                     Ok(v) => v,
                     Err(e) => {
@@ -943,6 +2019,11 @@ async fn async_main() -> anyhow::Result<()> {
 
                 println!("📊 Diffing {} vs {}...", resolved_v1, resolved_v2);
 
+                if semantic {
+                    print_semantic_diff(&config_diff::diff_yaml(&content1, &content2)?);
+                    return Ok(());
+                }
+
                 let diff = version::diff_configs(&content1, &content2);
                 let has_changes = diff.iter().any(|l| l.line_type != "unchanged");
 
@@ -968,71 +2049,290 @@ async fn async_main() -> anyhow::Result<()> {
                 .context("Failed to open editor")?
                 .wait()
                 .context("Failed to wait for editor")?;
+            git_history::record(types::VersionSource::Manual, "Edited current.yaml");
             println!("✅ Edit closed.");
         }
-        Commands::ForceUpdateConfig => {
-            println!("🔄 Force updating configuration from built-in resources...");
+        Commands::ForceUpdateConfig { upstream, dry_run } => {
+            println!("🔄 Updating configuration from built-in resources...");
 
-            // Hardcode the built-in config content using include_str!
-            // This ensures the config is embedded in the binary
-            let basic_builtin = include_str!("../resources/basic.yml");
-            let groups_builtin = include_str!("../resources/groups.yml");
+            // Render the template chosen with `hangar init` (embedded in the binary); a
+            // corrupt config.json must not fall back to the default template
+            let config = storage::load_hangar_config()?;
+            let rendered = templates::render(&config.template)?;
+            let basic_builtin = rendered.basic.as_str();
+            let groups_builtin = rendered.groups.as_str();
 
             let resources = vec![
-                (basic_builtin, storage::get_basic_config_path()?),
-                (groups_builtin, storage::get_groups_config_path()?),
+                (
+                    "basic",
+                    "basic.yml",
+                    basic_builtin,
+                    storage::get_basic_config_path()?,
+                ),
+                (
+                    "groups",
+                    "groups.yml",
+                    groups_builtin,
+                    storage::get_groups_config_path()?,
+                ),
             ];
 
-            for (content, dest_path) in resources {
-                // Create backup of old config
-                if dest_path.exists() {
-                    let backup_path = dest_path.with_extension("yml.bak");
-                    std::fs::copy(&dest_path, &backup_path)?;
+            let mut changed = false;
+            for (file_type, file_name, builtin, dest_path) in resources {
+                if !dest_path.exists() {
+                    if !dry_run {
+                        storage::write_atomic(&dest_path, builtin)?;
+                        storage::save_installed_template(file_name, builtin)?;
+                    }
+                    println!("✅ Installed {:?}", dest_path);
+                    changed = true;
+                    continue;
+                }
+
+                let local = std::fs::read_to_string(&dest_path)?;
+                let base = storage::load_installed_template(file_name)?;
+                if base.is_none() {
                     println!(
-                        "📦 Created backup of {:?} to {:?}",
-                        dest_path.file_name().unwrap(),
-                        backup_path
+                        "⚠️ No record of the template installed for {}, every difference is a conflict",
+                        file_name
                     );
                 }
 
-                std::fs::write(&dest_path, content)?;
+                let outcome = merge3::merge_three_way(base.as_deref(), &local, builtin, upstream);
+
+                for conflict in &outcome.conflicts {
+                    println!("\n⚔️  Conflict in {} at line {}:", file_name, conflict.line);
+                    for line in &conflict.local {
+                        println!("\x1b[31m- {}\x1b[0m", line); // Red: local
+                    }
+                    for line in &conflict.upstream {
+                        println!("\x1b[32m+ {}\x1b[0m", line); // Green: upstream
+                    }
+                }
+                if !outcome.conflicts.is_empty() {
+                    println!(
+                        "\n   {} conflict(s) resolved in favour of {}",
+                        outcome.conflicts.len(),
+                        if upstream {
+                            "the bundled template"
+                        } else {
+                            "local edits"
+                        }
+                    );
+                }
+
+                // Upstream hunks dropped in favour of local edits are not merged yet, so the
+                // new template must not become the base of the next update
+                let unresolved = !upstream && !outcome.conflicts.is_empty();
+                if unresolved {
+                    println!(
+                        "⚠️ {} keeps {} upstream change(s) unmerged, rerun with --upstream to take the bundled template",
+                        file_name,
+                        outcome.conflicts.len()
+                    );
+                }
+
+                if outcome.content == local {
+                    if !unresolved {
+                        println!("✅ {} is up to date", file_name);
+                        if !dry_run {
+                            storage::save_installed_template(file_name, builtin)?;
+                        }
+                    }
+                    continue;
+                }
+
+                // Never write a file the merge turned into invalid YAML
+                let valid = match file_type {
+                    "basic" => {
+                        serde_yaml::from_str::<types::ClashConfig>(&outcome.content).map(|_| ())
+                    }
+                    _ => {
+                        serde_yaml::from_str::<Vec<types::ProxyGroup>>(&outcome.content).map(|_| ())
+                    }
+                };
+                if let Err(e) = valid {
+                    println!(
+                        "❌ Merged {} is not valid YAML ({}), leaving it unchanged",
+                        file_name, e
+                    );
+                    continue;
+                }
+
+                println!(
+                    "📝 {}: {} upstream change(s) applied, {} conflict(s)",
+                    file_name,
+                    outcome.upstream_changes,
+                    outcome.conflicts.len()
+                );
+                if dry_run {
+                    continue;
+                }
+
+                // Snapshot the user's file before overwriting it
+                version::save_version(
+                    file_type,
+                    types::VersionSource::Template,
+                    "Before force-update-config",
+                    &local,
+                )?;
+                storage::write_atomic(&dest_path, &outcome.content)?;
+                if !unresolved {
+                    storage::save_installed_template(file_name, builtin)?;
+                }
                 println!("✅ Successfully updated {:?}", dest_path);
+                changed = true;
+            }
+
+            if dry_run {
+                println!("\n🔍 Dry run, nothing was written.");
+                return Ok(());
+            }
+            if !changed {
+                return Ok(());
             }
+            git_history::record(types::VersionSource::Template, "force-update-config");
 
             // Trigger a merge to apply the new base configs
             println!("🔄 Re-merging with new base configurations...");
             let subs = storage::load_subscriptions().unwrap_or_default();
             match proxy::merge_configs(&subs).await {
                 Ok(merged) => {
-                    let output_path = storage::get_current_config_path()?;
-                    hangar_lib::config::save_config(&merged, output_path.to_str().unwrap())?;
+                    proxy::write_current_config(&merged)?;
                     println!("✨ All set! Configuration updated and regenerated.");
                 }
                 Err(e) => println!("❌ Merge failed after update: {}", e),
             }
         }
+        Commands::Backup { subcommand } => match subcommand {
+            BackupCommands::Export {
+                path,
+                encrypt,
+                plaintext_secrets,
+                history,
+            } => {
+                let passphrase = if encrypt {
+                    Some(backup_passphrase(true)?.ok_or_else(|| {
+                        anyhow::anyhow!("--encrypt needs a terminal or {}", backup::PASSPHRASE_ENV)
+                    })?)
+                } else {
+                    None
+                };
+                let manifest = backup::export(
+                    &path,
+                    &backup::ExportOptions {
+                        passphrase,
+                        plaintext_secrets,
+                        include_history: history,
+                    },
+                )?;
+                println!("✅ Exported {} file(s) to {:?}", manifest.files.len(), path);
+                match manifest.secrets {
+                    backup::SecretsMode::Encrypted => println!("🔒 Secrets are encrypted."),
+                    backup::SecretsMode::Plaintext => {
+                        println!("⚠️ Secrets are included unencrypted; keep the bundle private.")
+                    }
+                    backup::SecretsMode::Omitted => {
+                        println!("ℹ️ Secrets (LLM API key, controller secret) are not included; use --encrypt to include them.")
+                    }
+                }
+                println!("⚠️ Subscription URLs contain access tokens; keep the bundle private.");
+            }
+            BackupCommands::Import {
+                path,
+                replace,
+                prefer_backup,
+                dry_run,
+            } => {
+                let manifest = backup::read_manifest(&path)?;
+                let passphrase = if manifest.secrets == backup::SecretsMode::Encrypted {
+                    backup_passphrase(false)?
+                } else {
+                    None
+                };
+                let report = backup::import(
+                    &path,
+                    &backup::ImportOptions {
+                        mode: if replace {
+                            backup::ImportMode::Replace
+                        } else {
+                            backup::ImportMode::Merge
+                        },
+                        prefer_backup,
+                        passphrase,
+                        dry_run,
+                    },
+                )?;
+                print_import_report(&report, dry_run);
+            }
+        },
+        Commands::Import {
+            path,
+            profile,
+            base,
+            dry_run,
+        } => {
+            let path = match path {
+                Some(path) => path,
+                None => {
+                    let found = client_import::default_client_dirs();
+                    match found.as_slice() {
+                        [dir] => dir.clone(),
+                        [] => anyhow::bail!(
+                            "No Clash Verge Rev or mihomo party directory found; pass a path"
+                        ),
+                        _ => anyhow::bail!(
+                            "Several clients found, pass one of: {}",
+                            found
+                                .iter()
+                                .map(|d| d.display().to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    }
+                }
+            };
+            let plan = client_import::plan_import(&path, profile.as_deref())?;
+            print_client_import_plan(&plan, base);
+            if dry_run {
+                println!("\n🔍 Dry run: nothing was written.");
+                return Ok(());
+            }
+            let created = client_import::apply_import(&plan, base)?;
+            println!("\n✅ Created {} subscription(s).", created.len());
+            if base && plan.base.is_some() {
+                println!("✅ basic.yml and groups.yml replaced (previous versions snapshotted).");
+            }
+            println!("💡 Run `hangar merge` to build the config.");
+        }
         Commands::Config {
             api_key,
             base_url,
             model,
+            log_level,
         } => {
-            let mut config = storage::load_hangar_config().unwrap_or_default();
-
-            if api_key.is_none() && base_url.is_none() && model.is_none() {
+            if api_key.is_none() && base_url.is_none() && model.is_none() && log_level.is_none() {
+                let config = storage::load_hangar_config().unwrap_or_default();
                 println!("{}", serde_json::to_string_pretty(&config)?);
                 return Ok(());
             }
 
-            if let Some(k) = api_key {
-                config.llm.api_key = k;
-            }
-            if let Some(u) = base_url {
-                config.llm.base_url = u;
-            }
-            if let Some(m) = model {
-                config.llm.model = m;
-            }
-            storage::save_hangar_config(&config)?;
+            storage::modify_hangar_config(|config| {
+                if let Some(k) = api_key {
+                    config.llm.api_key = k;
+                }
+                if let Some(u) = base_url {
+                    config.llm.base_url = u;
+                }
+                if let Some(m) = model {
+                    config.llm.model = m;
+                }
+                if let Some(level) = log_level {
+                    logging::LogFilter::new(Some(&level), None)?;
+                    config.log.level = level.to_lowercase();
+                }
+                Ok(())
+            })?;
             println!("✅ Configuration updated.");
         }
     }
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// A hunk changed differently on both sides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeConflict {
+    /// 1-based line in the merged output where the conflict starts
+    pub line: usize,
+    pub base: Vec<String>,
+    pub local: Vec<String>,
+    pub upstream: Vec<String>,
+}
+
+/// Result of merging local edits with an upstream template update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeOutcome {
+    pub content: String,
+    /// Number of upstream hunks applied automatically
+    pub upstream_changes: usize,
+    /// Conflicting hunks, resolved according to `prefer_upstream`
+    pub conflicts: Vec<MergeConflict>,
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Map every line of `a` to its matching line in `b` (if it is part of the LCS)
+fn match_lines(a: &[&str], b: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; a.len()];
+    for op in capture_diff_slices(Algorithm::Myers, a, b) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for k in 0..len {
+                matches[old_index + k] = Some(new_index + k);
+            }
+        }
+    }
+    matches
+}
+
+fn to_strings(lines: &[&str]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|l| l.trim_end_matches('\n').to_string())
+        .collect()
+}
+
+/// Three-way merge of line-based text (diff3).
+///
+/// `base` is the template Hangar last installed, `local` the user's current file and
+/// `upstream` the new bundled template. Hunks changed on one side only are taken from
+/// that side; hunks changed on both sides are conflicts, resolved in favour of `local`
+/// unless `prefer_upstream` is set. Without a `base`, every differing hunk is a conflict.
+pub fn merge_three_way(
+    base: Option<&str>,
+    local: &str,
+    upstream: &str,
+    prefer_upstream: bool,
+) -> MergeOutcome {
+    let base_known = base.is_some();
+    let base_lines = split_lines(base.unwrap_or(local));
+    let local_lines = split_lines(local);
+    let upstream_lines = split_lines(upstream);
+
+    let to_local = match_lines(&base_lines, &local_lines);
+    let to_upstream = match_lines(&base_lines, &upstream_lines);
+
+    let mut output: Vec<&str> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut upstream_changes = 0;
+
+    let (mut b0, mut l0, mut u0) = (0, 0, 0);
+
+    // Sync points are base lines matched on both sides; merge the chunks in between
+    let sync_points = (0..base_lines.len())
+        .filter_map(|i| Some((i, to_local[i]?, to_upstream[i]?)))
+        .chain(std::iter::once((
+            base_lines.len(),
+            local_lines.len(),
+            upstream_lines.len(),
+        )));
+
+    for (b1, l1, u1) in sync_points {
+        if b1 < b0 || l1 < l0 || u1 < u0 {
+            continue;
+        }
+
+        let base_chunk = &base_lines[b0..b1];
+        let local_chunk = &local_lines[l0..l1];
+        let upstream_chunk = &upstream_lines[u0..u1];
+
+        if local_chunk == upstream_chunk {
+            output.extend_from_slice(local_chunk);
+        } else if base_known && local_chunk == base_chunk {
+            upstream_changes += 1;
+            output.extend_from_slice(upstream_chunk);
+        } else if base_known && upstream_chunk == base_chunk {
+            output.extend_from_slice(local_chunk);
+        } else {
+            conflicts.push(MergeConflict {
+                line: output.len() + 1,
+                base: if base_known {
+                    to_strings(base_chunk)
+                } else {
+                    vec![]
+                },
+                local: to_strings(local_chunk),
+                upstream: to_strings(upstream_chunk),
+            });
+            if prefer_upstream {
+                output.extend_from_slice(upstream_chunk);
+            } else {
+                output.extend_from_slice(local_chunk);
+            }
+        }
+
+        if b1 < base_lines.len() {
+            output.push(local_lines[l1]);
+        }
+        (b0, l0, u0) = (b1 + 1, l1 + 1, u1 + 1);
+    }
+
+    MergeOutcome {
+        content: output.concat(),
+        upstream_changes,
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_applies_non_conflicting_changes() {
+        let base = "mixed-port: 7890\nmode: rule\nlog-level: info\nrules:\n- GEOIP,CN,DIRECT\n";
+        let local = "mixed-port: 7891\nmode: rule\nlog-level: info\nrules:\n- GEOIP,CN,DIRECT\n";
+        let upstream =
+            "mixed-port: 7890\nmode: rule\nlog-level: warning\nrules:\n- GEOIP,CN,DIRECT\n- MATCH,Proxy\n";
+
+        let outcome = merge_three_way(Some(base), local, upstream, false);
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(outcome.upstream_changes, 2);
+        assert_eq!(
+            outcome.content,
+            "mixed-port: 7891\nmode: rule\nlog-level: warning\nrules:\n- GEOIP,CN,DIRECT\n- MATCH,Proxy\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_conflicts() {
+        let base = "a: 1\nb: 2\nc: 3\n";
+        let local = "a: 1\nb: local\nc: 3\n";
+        let upstream = "a: 1\nb: upstream\nc: 3\n";
+
+        let outcome = merge_three_way(Some(base), local, upstream, false);
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.conflicts[0].line, 2);
+        assert_eq!(outcome.conflicts[0].local, vec!["b: local"]);
+        assert_eq!(outcome.conflicts[0].upstream, vec!["b: upstream"]);
+        assert_eq!(outcome.content, local);
+
+        let outcome = merge_three_way(Some(base), local, upstream, true);
+        assert_eq!(outcome.content, upstream);
+    }
+
+    #[test]
+    fn test_merge_without_base_conflicts_on_every_difference() {
+        let local = "a: 1\nb: 2\n";
+        let upstream = "a: 1\nb: 3\n";
+
+        let outcome = merge_three_way(None, local, upstream, false);
+        assert_eq!(outcome.conflicts.len(), 1);
+        assert_eq!(outcome.upstream_changes, 0);
+        assert_eq!(outcome.content, local);
+    }
+}
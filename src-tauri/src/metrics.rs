@@ -0,0 +1,281 @@
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static! {
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+/// Daemon counters and gauges, rendered in the Prometheus text format on `/metrics`
+#[derive(Debug, Default)]
+struct Metrics {
+    /// (subscription, "success" | "failure") -> count
+    downloads: BTreeMap<(String, &'static str), u64>,
+    last_success: BTreeMap<String, i64>,
+    subscription_nodes: BTreeMap<String, usize>,
+    /// (subscription, region) -> nodes
+    region_nodes: BTreeMap<(String, String), usize>,
+    merges: u64,
+    merge_seconds_sum: f64,
+    last_merge_seconds: f64,
+    /// (client, status) -> count
+    config_requests: BTreeMap<(String, u16), u64>,
+    /// "success" | "failure" -> count
+    reloads: BTreeMap<&'static str, u64>,
+}
+
+fn result_label(success: bool) -> &'static str {
+    if success {
+        "success"
+    } else {
+        "failure"
+    }
+}
+
+/// Count a subscription download
+pub fn record_download(subscription: &str, success: bool) {
+    let mut m = METRICS.lock().unwrap();
+    *m.downloads
+        .entry((subscription.to_string(), result_label(success)))
+        .or_default() += 1;
+    if success {
+        m.last_success
+            .insert(subscription.to_string(), chrono::Utc::now().timestamp());
+    }
+}
+
+/// Record a merge: its duration and the nodes each enabled subscription contributed.
+/// Subscriptions missing from the maps (removed or disabled) disappear from the gauges.
+pub fn record_merge(
+    duration: Duration,
+    subscription_nodes: BTreeMap<String, usize>,
+    region_nodes: BTreeMap<(String, String), usize>,
+) {
+    let mut m = METRICS.lock().unwrap();
+    m.merges += 1;
+    m.merge_seconds_sum += duration.as_secs_f64();
+    m.last_merge_seconds = duration.as_secs_f64();
+    m.subscription_nodes = subscription_nodes;
+    m.region_nodes = region_nodes;
+}
+
+/// Count a `/config` request by client and response status
+pub fn record_config_request(user_agent: Option<&str>, status: u16) {
+    let mut m = METRICS.lock().unwrap();
+    *m.config_requests
+        .entry((client_name(user_agent), status))
+        .or_default() += 1;
+}
+
+/// Count a reload of current.yaml triggered by the file watcher
+pub fn record_reload(success: bool) {
+    let mut m = METRICS.lock().unwrap();
+    *m.reloads.entry(result_label(success)).or_default() += 1;
+}
+
+/// Client products reported as their own `client` label; any other user agent is "other",
+/// since anyone reaching /config can choose the header
+const KNOWN_CLIENTS: &[&str] = &[
+    "clash-verge",
+    "clash.meta",
+    "mihomo",
+    "mihomo-party",
+    "ClashX",
+    "ClashforWindows",
+    "ClashMetaForAndroid",
+    "FlClash",
+    "Stash",
+    "Shadowrocket",
+    "Surge",
+    "Quantumult",
+    "sing-box",
+    "curl",
+    "Wget",
+    "Mozilla",
+];
+
+/// Product name of a user agent (`clash-verge/v2.4.5` -> `clash-verge`), bucketed into
+/// `KNOWN_CLIENTS`, "other" or "unknown" to keep label cardinality bounded
+fn client_name(user_agent: Option<&str>) -> String {
+    let product = user_agent
+        .and_then(|ua| ua.split_whitespace().next())
+        .and_then(|token| token.split('/').next())
+        .unwrap_or("");
+    if product.is_empty() {
+        return "unknown".to_string();
+    }
+    KNOWN_CLIENTS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(product))
+        .map_or("other", |known| known)
+        .to_string()
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    let m = METRICS.lock().unwrap();
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "hangar_subscription_downloads_total",
+        "counter",
+        "Subscription downloads by result",
+    );
+    for ((sub, result), count) in &m.downloads {
+        let _ = writeln!(
+            out,
+            "hangar_subscription_downloads_total{{subscription=\"{}\",result=\"{}\"}} {}",
+            escape_label(sub),
+            result,
+            count
+        );
+    }
+
+    header(
+        &mut out,
+        "hangar_subscription_last_success_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful download",
+    );
+    for (sub, ts) in &m.last_success {
+        let _ = writeln!(
+            out,
+            "hangar_subscription_last_success_timestamp_seconds{{subscription=\"{}\"}} {}",
+            escape_label(sub),
+            ts
+        );
+    }
+
+    header(
+        &mut out,
+        "hangar_subscription_nodes",
+        "gauge",
+        "Nodes per enabled subscription in the last merge",
+    );
+    for (sub, nodes) in &m.subscription_nodes {
+        let _ = writeln!(
+            out,
+            "hangar_subscription_nodes{{subscription=\"{}\"}} {}",
+            escape_label(sub),
+            nodes
+        );
+    }
+
+    header(
+        &mut out,
+        "hangar_region_nodes",
+        "gauge",
+        "Nodes per subscription and region in the last merge",
+    );
+    for ((sub, region), nodes) in &m.region_nodes {
+        let _ = writeln!(
+            out,
+            "hangar_region_nodes{{subscription=\"{}\",region=\"{}\"}} {}",
+            escape_label(sub),
+            escape_label(region),
+            nodes
+        );
+    }
+
+    header(
+        &mut out,
+        "hangar_merge_duration_seconds",
+        "summary",
+        "Time spent merging subscriptions into the config",
+    );
+    let _ = writeln!(
+        out,
+        "hangar_merge_duration_seconds_sum {}",
+        m.merge_seconds_sum
+    );
+    let _ = writeln!(out, "hangar_merge_duration_seconds_count {}", m.merges);
+
+    header(
+        &mut out,
+        "hangar_last_merge_duration_seconds",
+        "gauge",
+        "Duration of the most recent merge",
+    );
+    let _ = writeln!(
+        out,
+        "hangar_last_merge_duration_seconds {}",
+        m.last_merge_seconds
+    );
+
+    header(
+        &mut out,
+        "hangar_config_requests_total",
+        "counter",
+        "/config requests by client and status",
+    );
+    for ((client, status), count) in &m.config_requests {
+        let _ = writeln!(
+            out,
+            "hangar_config_requests_total{{client=\"{}\",status=\"{}\"}} {}",
+            escape_label(client),
+            status,
+            count
+        );
+    }
+
+    header(
+        &mut out,
+        "hangar_config_reloads_total",
+        "counter",
+        "Reloads of current.yaml by the file watcher, by result",
+    );
+    for (result, count) in &m.reloads {
+        let _ = writeln!(
+            out,
+            "hangar_config_reloads_total{{result=\"{}\"}} {}",
+            result, count
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_name_and_escaping() {
+        assert_eq!(client_name(Some("clash-verge/v2.4.5")), "clash-verge");
+        assert_eq!(client_name(Some("Mozilla/5.0 (X11; Linux)")), "Mozilla");
+        assert_eq!(client_name(Some("ClashX/1.0")), "ClashX");
+        assert_eq!(client_name(Some("random-probe-1234/1.0")), "other");
+        assert_eq!(client_name(None), "unknown");
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+
+    #[test]
+    fn test_render_merge_gauges() {
+        let mut nodes = BTreeMap::new();
+        nodes.insert("Home".to_string(), 0);
+        let mut regions = BTreeMap::new();
+        regions.insert(("Work".to_string(), "HK".to_string()), 3);
+        record_merge(Duration::from_millis(250), nodes, regions);
+
+        let text = render();
+        assert!(text.contains("# TYPE hangar_subscription_nodes gauge"));
+        assert!(text.contains("hangar_subscription_nodes{subscription=\"Home\"} 0"));
+        assert!(text.contains("hangar_region_nodes{subscription=\"Work\",region=\"HK\"} 3"));
+        assert!(text.contains("hangar_last_merge_duration_seconds 0.25"));
+    }
+}
@@ -10,6 +10,12 @@ lazy_static! {
 }
 
 pub fn add_notification(title: &str, message: &str, severity: &str) {
+    crate::events::publish(crate::events::DaemonEvent::Notification {
+        title: title.to_string(),
+        message: message.to_string(),
+        severity: severity.to_string(),
+    });
+
     let mut notifications = NOTIFICATIONS.lock().unwrap();
     notifications.push(Notification {
         id: Uuid::new_v4().to_string(),
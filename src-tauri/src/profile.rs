@@ -0,0 +1,188 @@
+use crate::types::{ClashConfig, HangarConfig, Profile, Subscription};
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+/// Built-in policies that are always valid group members and rule targets
+pub const BUILTIN_POLICIES: &[&str] = &["DIRECT", "REJECT", "REJECT-DROP", "PASS", "COMPATIBLE"];
+
+/// Find a profile by name ("default" is always the full config)
+pub fn find_profile<'a>(config: &'a HangarConfig, name: &str) -> Option<&'a Profile> {
+    config.profiles.iter().find(|p| p.name == name)
+}
+
+/// Restrict a merged config to the nodes of the profile's subscriptions.
+///
+/// Nodes carry a `[subscription]-` prefix; nodes of other subscriptions are removed,
+/// then group members that no longer exist, groups left empty by that and rules
+/// pointing at removed groups. Nodes without a subscription prefix (chains) are kept.
+pub fn apply_profile(
+    config: &ClashConfig,
+    profile: &Profile,
+    subscriptions: &[Subscription],
+) -> ClashConfig {
+    let mut result = config.clone();
+    if profile.subscriptions.is_empty() {
+        return result;
+    }
+
+    let (allowed, denied): (Vec<&Subscription>, Vec<&Subscription>) =
+        subscriptions.iter().partition(|s| {
+            profile
+                .subscriptions
+                .iter()
+                .any(|key| key == &s.name || key == &s.id)
+        });
+    let allowed_prefixes: Vec<String> = allowed.iter().map(|s| format!("[{}]-", s.name)).collect();
+    let denied_prefixes: Vec<String> = denied.iter().map(|s| format!("[{}]-", s.name)).collect();
+
+    result.proxies.retain(|p| {
+        allowed_prefixes
+            .iter()
+            .any(|prefix| p.name.starts_with(prefix))
+            || !denied_prefixes
+                .iter()
+                .any(|prefix| p.name.starts_with(prefix))
+    });
+
+    // Removing members can empty a group, which in turn is a member of other groups
+    loop {
+        let known: HashSet<String> = result
+            .proxies
+            .iter()
+            .map(|p| p.name.clone())
+            .chain(result.proxy_groups.iter().map(|g| g.name.clone()))
+            .collect();
+
+        let mut emptied = false;
+        result.proxy_groups.retain_mut(|group| {
+            let before = group.proxies.len();
+            group
+                .proxies
+                .retain(|m| known.contains(m) || BUILTIN_POLICIES.contains(&m.as_str()));
+            // Groups that were empty to begin with (e.g. provider-backed) are left alone
+            let now_empty = before > 0 && group.proxies.is_empty();
+            emptied |= now_empty;
+            !now_empty
+        });
+
+        if !emptied {
+            break;
+        }
+    }
+
+    let targets: HashSet<&str> = result
+        .proxy_groups
+        .iter()
+        .map(|g| g.name.as_str())
+        .chain(result.proxies.iter().map(|p| p.name.as_str()))
+        .chain(BUILTIN_POLICIES.iter().copied())
+        .collect();
+    result
+        .rules
+        .retain(|rule| match crate::rules::rule_target(rule) {
+            Some(target) => targets.contains(target.as_str()),
+            None => true,
+        });
+
+    result
+}
+
+/// Add a profile limited to the given subscriptions
+pub fn add_profile(name: &str, subscriptions: Vec<String>) -> Result<Profile> {
+    if name == "default" {
+        anyhow::bail!("'default' is reserved for the full config");
+    }
+
+    let subs = crate::storage::load_subscriptions()?;
+    for key in &subscriptions {
+        if !subs.iter().any(|s| &s.name == key || &s.id == key) {
+            anyhow::bail!("Subscription not found: '{}'", key);
+        }
+    }
+
+    crate::storage::modify_hangar_config(|config| {
+        if find_profile(config, name).is_some() {
+            anyhow::bail!("Profile '{}' already exists", name);
+        }
+
+        let profile = Profile {
+            name: name.to_string(),
+            subscriptions,
+        };
+        config.profiles.push(profile.clone());
+        Ok(profile)
+    })
+}
+
+/// Remove a profile; tokens still using it must be revoked first
+pub fn remove_profile(name: &str) -> Result<()> {
+    crate::storage::modify_hangar_config(|config| {
+        if let Some(token) = config.server.tokens.iter().find(|t| t.profile == name) {
+            anyhow::bail!(
+                "Profile '{}' is used by token '{}', revoke it first",
+                name,
+                token.name
+            );
+        }
+
+        let idx = config
+            .profiles
+            .iter()
+            .position(|p| p.name == name)
+            .with_context(|| format!("Profile not found: '{}'", name))?;
+        config.profiles.remove(idx);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(name: &str) -> Subscription {
+        Subscription {
+            id: format!("id-{}", name),
+            name: name.to_string(),
+            url: String::new(),
+            enabled: true,
+            last_updated: None,
+            node_count: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_profile_filters_nodes_groups_and_rules() {
+        let config: ClashConfig = serde_yaml::from_str(
+            r#"
+proxies:
+  - {name: "[A]-HK 01", type: ss, server: a.example.com, port: 1}
+  - {name: "[B]-US 01", type: ss, server: b.example.com, port: 2}
+proxy-groups:
+  - {name: "[B]-US-地区", type: url-test, proxies: ["[B]-US 01"]}
+  - {name: "US-Only", type: select, proxies: ["[B]-US-地区"]}
+  - {name: "Proxy", type: select, proxies: ["[A]-HK 01", "[B]-US-地区", DIRECT]}
+rules:
+  - DOMAIN-SUFFIX,netflix.com,US-Only
+  - MATCH,Proxy
+"#,
+        )
+        .unwrap();
+        let profile = Profile {
+            name: "kids".to_string(),
+            subscriptions: vec!["A".to_string()],
+        };
+
+        let filtered = apply_profile(&config, &profile, &[sub("A"), sub("B")]);
+
+        assert_eq!(filtered.proxies.len(), 1);
+        assert_eq!(filtered.proxies[0].name, "[A]-HK 01");
+        let group_names: Vec<_> = filtered.proxy_groups.iter().map(|g| &g.name).collect();
+        assert_eq!(group_names, vec!["Proxy"]);
+        assert_eq!(
+            filtered.proxy_groups[0].proxies,
+            vec!["[A]-HK 01", "DIRECT"]
+        );
+        assert_eq!(filtered.rules, vec!["MATCH,Proxy"]);
+    }
+}
@@ -1,8 +1,17 @@
+use crate::events::{self, DaemonEvent};
+use crate::profile::BUILTIN_POLICIES;
 use crate::subscription::extract_region;
-use crate::types::{ClashConfig, ProxyGroup, ProxyNode, Subscription};
+use crate::types::{ClashConfig, ProxyGroup, ProxyNode, Subscription, VersionSource};
 use anyhow::{Context, Result};
 use indexmap::IndexMap;
-use std::collections::HashSet;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// groups.yml 中只包含 AI 支持地区的服务分组（含 english 模板中的名称）
+const AI_GROUP_NAMES: &[&str] = &["自动选择", "AI-专用", "Auto", "AI"];
 
 /// 创建地区分组（包含机场层级）
 /// 返回: (机场地区分组列表, 地区分组列表)
@@ -126,11 +135,42 @@ fn is_emoji_char(c: char) -> bool {
     (0x1F900..=0x1F9FF).contains(&u) // Supplemental Symbols and Pictographs
 }
 
+/// 合并已保存的订阅并写入 current.yaml
+pub async fn regenerate_current_config() -> Result<ClashConfig> {
+    let subs = crate::storage::load_subscriptions()?;
+    let merged = merge_configs(&subs).await?;
+    write_current_config(&merged)?;
+    Ok(merged)
+}
+
+/// 写入合并结果到 current.yaml，内容变化时自动创建版本快照
+pub fn write_current_config(merged: &ClashConfig) -> Result<std::path::PathBuf> {
+    let current_path = crate::storage::get_current_config_path()?;
+    let content = serde_yaml::to_string(merged)?;
+    crate::storage::write_atomic(&current_path, &content)
+        .context("Failed to write current.yaml")?;
+
+    let description = format!(
+        "Merge: {} proxies, {} groups, {} rules",
+        merged.proxies.len(),
+        merged.proxy_groups.len(),
+        merged.rules.len()
+    );
+    if let Err(e) = crate::version::snapshot_merge(&content, &description) {
+        warn!("⚠️ Failed to snapshot current.yaml: {}", e);
+    }
+    crate::git_history::record(VersionSource::Merge, &description);
+
+    Ok(current_path)
+}
+
 /// 合并配置
 pub async fn merge_configs(
     subscriptions: &[Subscription],
     // basic_config is now loaded inside or passed in, plan said "Load basic.yml from src-tauri/resources/"
 ) -> Result<ClashConfig> {
+    let started = std::time::Instant::now();
+
     // 1. Load basic.yml
     let basic_path = crate::storage::get_basic_config_path()?;
     let basic_content = std::fs::read_to_string(&basic_path).context("Failed to read basic.yml")?;
@@ -151,8 +191,11 @@ pub async fn merge_configs(
 
     let mut all_proxies = basic_config.proxies.clone();
     let mut regions: HashSet<String> = HashSet::new();
+    let mut warnings: Vec<String> = Vec::new();
+    let mut subscription_nodes: BTreeMap<String, usize> = BTreeMap::new();
+    let mut region_nodes: BTreeMap<(String, String), usize> = BTreeMap::new();
 
-    println!("🚀 Merging configuration from local cache...\n");
+    info!("🚀 Merging configuration from local cache...");
 
     // 3. Process Subscriptions (from cache)
     for sub in subscriptions {
@@ -162,23 +205,28 @@ pub async fn merge_configs(
 
         match parse_cached_subscription(sub) {
             Ok(mut proxies) => {
-                println!("   ✓ Loaded {} proxies from {}", proxies.len(), sub.name);
+                info!("✓ Loaded {} proxies from {}", proxies.len(), sub.name);
                 // 提取地区信息
                 for proxy in &mut proxies {
                     if let Some(region) = extract_region(&proxy.name) {
                         proxy.region = Some(region.clone());
                         regions.insert(region);
                     }
+                    let region = proxy.region.clone().unwrap_or_else(|| "other".to_string());
+                    *region_nodes.entry((sub.name.clone(), region)).or_default() += 1;
                 }
+                subscription_nodes.insert(sub.name.clone(), proxies.len());
                 all_proxies.extend(proxies);
             }
             Err(e) => {
-                println!("   ⚠️ Failed to load cache for {}: {}", sub.name, e);
+                subscription_nodes.insert(sub.name.clone(), 0);
+                warn!("⚠️ Failed to load cache for {}: {}", sub.name, e);
+                warnings.push(format!("Failed to load cache for {}: {}", sub.name, e));
             }
         }
     }
 
-    println!("\n📊 Total proxies: {}", all_proxies.len());
+    info!("📊 Total proxies: {}", all_proxies.len());
 
     // 4. Create Groups
     // Ignore basic.yml proxy groups, start fresh
@@ -232,7 +280,7 @@ pub async fn merge_configs(
 
     // Modify extra_groups (from groups.yml) to include auto-generated region groups in their proxies
     for group in &mut extra_groups {
-        if group.name == "自动选择" || group.name == "AI-专用" {
+        if AI_GROUP_NAMES.contains(&group.name.as_str()) {
             // 服务类型分组：添加 AI 支持的机场地区分组 + 地区分组
             group
                 .proxies
@@ -256,11 +304,119 @@ pub async fn merge_configs(
     proxy_groups.extend(airport_region_groups);
     proxy_groups.extend(region_groups);
 
-    Ok(ClashConfig {
+    // Chained proxies (dialer-proxy / relay) declared in config.json
+    let chains = crate::storage::load_hangar_config()?.chains;
+    if !chains.is_empty() {
+        warnings.extend(crate::chain::apply_chains(
+            &chains,
+            subscriptions,
+            &mut all_proxies,
+            &mut proxy_groups,
+        ));
+    }
+
+    // 5. Layer personal rules (rules.local.yml) on top of basic.yml rules
+    let overlay = crate::rules::load_rules_overlay()?;
+    if !overlay.is_empty() {
+        info!(
+            "✓ Applied rules.local.yml (+{} prepended, +{} appended, {} target overrides)",
+            overlay.prepend.len(),
+            overlay.append.len(),
+            overlay.overrides.len()
+        );
+    }
+    let rules = overlay.apply(basic_config.rules);
+
+    let merged = ClashConfig {
         base_config: basic_config.base_config,
         proxies: all_proxies,
         proxy_groups,
-        rules: basic_config.rules,
+        rules,
         rule_providers: basic_config.rule_providers,
-    })
+    };
+
+    let problems = validate_config(&merged);
+    for problem in &problems {
+        warn!("⚠️ {}", problem);
+    }
+    warnings.extend(problems);
+
+    for message in &warnings {
+        events::publish(DaemonEvent::ValidationWarning {
+            message: message.clone(),
+        });
+    }
+    crate::metrics::record_merge(started.elapsed(), subscription_nodes, region_nodes);
+    events::publish(DaemonEvent::MergeFinished {
+        proxies: merged.proxies.len(),
+        proxy_groups: merged.proxy_groups.len(),
+        rules: merged.rules.len(),
+        warnings: warnings.len(),
+    });
+    *LAST_MERGE.lock().unwrap() = Some(MergeReport {
+        finished_at: chrono::Utc::now().timestamp(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        proxies: merged.proxies.len(),
+        proxy_groups: merged.proxy_groups.len(),
+        rules: merged.rules.len(),
+        warnings,
+    });
+
+    Ok(merged)
+}
+
+/// Outcome of the most recent merge in this process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Unix timestamp
+    pub finished_at: i64,
+    pub duration_ms: u64,
+    pub proxies: usize,
+    pub proxy_groups: usize,
+    pub rules: usize,
+    pub warnings: Vec<String>,
+}
+
+lazy_static! {
+    static ref LAST_MERGE: Mutex<Option<MergeReport>> = Mutex::new(None);
+}
+
+/// The most recent merge in this process, if any
+pub fn last_merge() -> Option<MergeReport> {
+    LAST_MERGE.lock().unwrap().clone()
+}
+
+/// References that Clash would reject: group members and rule targets
+/// that are neither a proxy, a group nor a built-in policy
+pub fn validate_config(config: &ClashConfig) -> Vec<String> {
+    let known: HashSet<&str> = config
+        .proxy_groups
+        .iter()
+        .map(|g| g.name.as_str())
+        .chain(config.proxies.iter().map(|p| p.name.as_str()))
+        .chain(BUILTIN_POLICIES.iter().copied())
+        .collect();
+
+    let mut problems = Vec::new();
+    for group in &config.proxy_groups {
+        for member in &group.proxies {
+            if !known.contains(member.as_str()) {
+                problems.push(format!(
+                    "Group '{}' references unknown proxy '{}'",
+                    group.name, member
+                ));
+            }
+        }
+    }
+    for rule in &config.rules {
+        if let Some(target) = crate::rules::rule_target(rule) {
+            if !known.contains(target.as_str()) {
+                problems.push(format!(
+                    "Rule '{}' targets unknown policy '{}'",
+                    rule, target
+                ));
+            }
+        }
+    }
+    problems
 }
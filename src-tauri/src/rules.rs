@@ -1,8 +1,10 @@
 use anyhow::{Context, Result};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
 
 use crate::storage::get_hangar_dir;
 
@@ -23,6 +25,74 @@ pub struct RuleSource {
     pub url: String,
     pub enabled: bool,
     pub last_updated: Option<String>,
+    /// Refresh interval in seconds
+    #[serde(default = "default_rule_interval")]
+    pub interval: u64,
+    /// Error of the last failed refresh (cleared on success)
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Number of entries in the last downloaded rule file
+    #[serde(default)]
+    pub entry_count: Option<usize>,
+    /// Time of the last refresh attempt, successful or not
+    #[serde(default)]
+    pub last_attempt: Option<String>,
+}
+
+/// Default rule source refresh interval (1 day)
+pub const DEFAULT_RULE_INTERVAL: u64 = 86400;
+
+fn default_rule_interval() -> u64 {
+    DEFAULT_RULE_INTERVAL
+}
+
+/// Delay before retrying a source whose last refresh failed (capped by its interval)
+pub const FAILED_RULE_RETRY: u64 = 3600;
+
+/// Shortest accepted refresh interval (5 minutes); the scheduler ticks every minute
+pub const MIN_RULE_INTERVAL: u64 = 300;
+
+/// Reject refresh intervals below `MIN_RULE_INTERVAL`
+pub fn validate_rule_interval(interval: u64) -> Result<()> {
+    if interval < MIN_RULE_INTERVAL {
+        anyhow::bail!(
+            "Rule source interval must be at least {} seconds, got {}",
+            MIN_RULE_INTERVAL,
+            interval
+        );
+    }
+    Ok(())
+}
+
+fn seconds_since(time: Option<&str>, now: chrono::DateTime<chrono::Utc>) -> Option<i64> {
+    let time = chrono::DateTime::parse_from_rfc3339(time?).ok()?;
+    Some((now - time.with_timezone(&chrono::Utc)).num_seconds())
+}
+
+impl RuleSource {
+    /// Refresh interval, raised to `MIN_RULE_INTERVAL` for hand-edited sources
+    pub fn refresh_interval(&self) -> u64 {
+        self.interval.max(MIN_RULE_INTERVAL)
+    }
+
+    /// Whether the source is enabled and its refresh interval has elapsed
+    pub fn is_due(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let interval = self.refresh_interval();
+
+        // Back off after a failed attempt instead of retrying on every scheduler tick
+        let retry = interval.min(FAILED_RULE_RETRY) as i64;
+        if seconds_since(self.last_attempt.as_deref(), now).is_some_and(|s| s < retry) {
+            return false;
+        }
+
+        match seconds_since(self.last_updated.as_deref(), now) {
+            Some(elapsed) => elapsed >= interval as i64,
+            None => true,
+        }
+    }
 }
 
 /// Default built-in rules from Loyalsoldier/clash-rules
@@ -137,55 +207,104 @@ pub fn load_rule_sources() -> Result<Vec<RuleSource>> {
     Ok(sources)
 }
 
-/// Save rule sources to disk
-pub fn save_rule_sources(sources: &[RuleSource]) -> Result<()> {
-    let path = get_rule_sources_path()?;
+fn write_rule_sources(path: &Path, sources: &[RuleSource]) -> Result<()> {
     let content =
         serde_json::to_string_pretty(sources).context("Failed to serialize rule sources")?;
-    fs::write(&path, content).context("Failed to write rule_sources.json")?;
-    Ok(())
+    crate::storage::write_atomic(path, content).context("Failed to write rule_sources.json")
 }
 
-/// Add a new rule source
-pub fn add_rule_source(name: String, url: String) -> Result<RuleSource> {
-    let mut sources = load_rule_sources()?;
+/// Save rule sources to disk, replacing them as a whole
+pub fn save_rule_sources(sources: &[RuleSource]) -> Result<()> {
+    let path = get_rule_sources_path()?;
+    let _lock = crate::storage::lock_file(&path)?;
+    write_rule_sources(&path, sources)
+}
 
-    // Check for duplicate URL
-    if sources.iter().any(|s| s.url == url) {
-        anyhow::bail!("Rule source with this URL already exists");
+/// Load, change and save rule_sources.json while holding its lock. Nothing is written
+/// if `f` leaves the sources unchanged or fails.
+pub fn modify_rule_sources<T>(f: impl FnOnce(&mut Vec<RuleSource>) -> Result<T>) -> Result<T> {
+    let path = get_rule_sources_path()?;
+    let _lock = crate::storage::lock_file(&path)?;
+    let mut sources = load_rule_sources()?;
+    let before = serde_json::to_value(&sources)?;
+    let result = f(&mut sources)?;
+    if serde_json::to_value(&sources)? != before {
+        write_rule_sources(&path, &sources)?;
     }
+    Ok(result)
+}
 
-    let source = RuleSource {
-        id: uuid::Uuid::new_v4().to_string(),
-        name,
-        url,
-        enabled: true,
-        last_updated: None,
-    };
+/// Add a new rule source
+pub fn add_rule_source(name: String, url: String, interval: Option<u64>) -> Result<RuleSource> {
+    let interval = interval.unwrap_or(DEFAULT_RULE_INTERVAL);
+    validate_rule_interval(interval)?;
+    modify_rule_sources(|sources| {
+        // Check for duplicate URL
+        if sources.iter().any(|s| s.url == url) {
+            anyhow::bail!("Rule source with this URL already exists");
+        }
 
-    sources.push(source.clone());
-    save_rule_sources(&sources)?;
+        let source = RuleSource {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            url,
+            enabled: true,
+            last_updated: None,
+            interval,
+            last_error: None,
+            entry_count: None,
+            last_attempt: None,
+        };
 
-    Ok(source)
+        sources.push(source.clone());
+        Ok(source)
+    })
+}
+
+/// Find a rule source by name, ID or index (same lookup order as subscriptions)
+pub fn find_rule_source(sources: &[RuleSource], key: &str) -> Option<usize> {
+    sources
+        .iter()
+        .position(|s| s.name == key)
+        .or_else(|| sources.iter().position(|s| s.id == key))
+        .or_else(|| key.parse::<usize>().ok().filter(|&idx| idx < sources.len()))
+}
+
+/// Resolve a rule source by name, ID or index
+pub fn resolve_rule_source(key: &str) -> Result<RuleSource> {
+    let sources = load_rule_sources()?;
+    find_rule_source(&sources, key)
+        .map(|idx| sources[idx].clone())
+        .with_context(|| format!("Rule source not found: '{}'", key))
+}
+
+/// Enable or disable a rule source by name, ID or index
+pub fn set_rule_source_enabled(key: &str, enabled: bool) -> Result<RuleSource> {
+    modify_rule_sources(|sources| {
+        let idx = find_rule_source(sources, key)
+            .with_context(|| format!("Rule source not found: '{}'", key))?;
+
+        sources[idx].enabled = enabled;
+        Ok(sources[idx].clone())
+    })
 }
 
 /// Remove a rule source by ID
 pub fn remove_rule_source(id: &str) -> Result<()> {
-    let mut sources = load_rule_sources()?;
-    let initial_len = sources.len();
-    sources.retain(|s| s.id != id);
-
-    if sources.len() == initial_len {
-        anyhow::bail!("Rule source not found");
-    }
+    modify_rule_sources(|sources| {
+        let initial_len = sources.len();
+        sources.retain(|s| s.id != id);
 
-    save_rule_sources(&sources)?;
-    Ok(())
+        if sources.len() == initial_len {
+            anyhow::bail!("Rule source not found");
+        }
+        Ok(())
+    })
 }
 
-/// Download and cache a rule file
-async fn download_rule(url: &str, cache_path: &PathBuf) -> Result<()> {
-    eprintln!("Downloading rule from: {}", url);
+/// Download and cache a rule file, returning its content
+async fn download_rule(url: &str, cache_path: &PathBuf) -> Result<String> {
+    info!("Downloading rule from: {}", url);
 
     let client = reqwest::Client::new();
     let response = client
@@ -200,10 +319,25 @@ async fn download_rule(url: &str, cache_path: &PathBuf) -> Result<()> {
     }
 
     let content = response.text().await.context("Failed to read response")?;
-    fs::write(cache_path, &content).context("Failed to write cache file")?;
+    crate::storage::write_atomic(cache_path, &content).context("Failed to write cache file")?;
 
-    eprintln!("Rule cached to: {:?}", cache_path);
-    Ok(())
+    debug!("Rule cached to: {:?}", cache_path);
+    Ok(content)
+}
+
+/// Count the entries of a rule file, either a `payload:` list or one rule per line
+pub fn count_rule_entries(content: &str) -> usize {
+    if let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(content) {
+        if let Some(payload) = value.get("payload").and_then(|p| p.as_sequence()) {
+            return payload.len();
+        }
+    }
+
+    content
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("//"))
+        .count()
 }
 
 /// Refresh all builtin rules
@@ -214,39 +348,87 @@ pub async fn refresh_builtin_rules() -> Result<()> {
     for rule in rules {
         let cache_path = cache_dir.join(format!("{}.txt", rule.name));
         if let Err(e) = download_rule(&rule.url, &cache_path).await {
-            eprintln!("Failed to download rule {}: {}", rule.name, e);
+            warn!("Failed to download rule {}: {}", rule.name, e);
         }
     }
 
     Ok(())
 }
 
-/// Refresh all custom rule sources
-pub async fn refresh_custom_rules() -> Result<()> {
-    let mut sources = load_rule_sources()?;
-    let cache_dir = get_remote_rules_cache_dir()?;
-    let now = chrono::Utc::now().to_rfc3339();
-
-    for source in &mut sources {
-        if !source.enabled {
-            continue;
+/// Download a single rule source, returning its entry count
+async fn download_source(source: &RuleSource) -> Result<usize> {
+    let cache_path = get_remote_rules_cache_dir()?.join(format!("{}.txt", source.id));
+    match download_rule(&source.url, &cache_path).await {
+        Ok(content) => Ok(count_rule_entries(&content)),
+        Err(e) => {
+            warn!("Failed to refresh rule source {}: {}", source.name, e);
+            Err(e)
         }
+    }
+}
 
-        let cache_path = cache_dir.join(format!("{}.txt", source.id));
-        match download_rule(&source.url, &cache_path).await {
-            Ok(_) => {
-                source.last_updated = Some(now.clone());
-            }
-            Err(e) => {
-                eprintln!("Failed to refresh rule source {}: {}", source.name, e);
+/// Record download outcomes on the stored sources. The list is reloaded under its lock,
+/// since it may have changed during the downloads.
+fn record_downloads(outcomes: &[(String, Result<usize>)]) -> Result<Option<RuleSource>> {
+    modify_rule_sources(|sources| {
+        let mut last = None;
+        for (id, outcome) in outcomes {
+            let Some(source) = sources.iter_mut().find(|s| &s.id == id) else {
+                continue;
+            };
+            let now = chrono::Utc::now().to_rfc3339();
+            source.last_attempt = Some(now.clone());
+            match outcome {
+                Ok(entries) => {
+                    source.last_updated = Some(now);
+                    source.entry_count = Some(*entries);
+                    source.last_error = None;
+                }
+                Err(e) => source.last_error = Some(e.to_string()),
             }
+            last = Some(source.clone());
         }
+        Ok(last)
+    })
+}
+
+/// Refresh rule sources matching `filter`, returning the number refreshed successfully
+async fn refresh_sources_where(filter: impl Fn(&RuleSource) -> bool) -> Result<usize> {
+    let mut outcomes = Vec::new();
+    for source in load_rule_sources()?.iter().filter(|s| filter(s)) {
+        outcomes.push((source.id.clone(), download_source(source).await));
     }
 
-    save_rule_sources(&sources)?;
+    record_downloads(&outcomes)?;
+    Ok(outcomes
+        .iter()
+        .filter(|(_, outcome)| outcome.is_ok())
+        .count())
+}
+
+/// Refresh all custom rule sources
+pub async fn refresh_custom_rules() -> Result<()> {
+    refresh_sources_where(|s| s.enabled).await?;
     Ok(())
 }
 
+/// Refresh only the enabled rule sources whose interval has elapsed
+pub async fn refresh_due_rule_sources() -> Result<usize> {
+    let now = chrono::Utc::now();
+    refresh_sources_where(|s| s.is_due(now)).await
+}
+
+/// Refresh a single rule source by name, ID or index (even if disabled)
+pub async fn refresh_rule_source(key: &str) -> Result<RuleSource> {
+    let source = resolve_rule_source(key)?;
+    let outcomes = [(source.id.clone(), download_source(&source).await)];
+    let updated = record_downloads(&outcomes)?
+        .with_context(|| format!("Rule source not found: '{}'", key))?;
+    let [(_, outcome)] = outcomes;
+    outcome?;
+    Ok(updated)
+}
+
 /// Refresh all rules (builtin + custom)
 pub async fn refresh_all_rules() -> Result<()> {
     refresh_builtin_rules().await?;
@@ -291,7 +473,7 @@ pub fn generate_rule_providers() -> Result<HashMap<String, serde_yaml::Value>> {
             behavior: behavior.to_string(),
             url: source.url.clone(),
             path: format!("./ruleset/custom_{}.yaml", source.id),
-            interval: 86400,
+            interval: source.refresh_interval(),
         })
         .context("Failed to serialize custom rule provider")?;
 
@@ -326,6 +508,147 @@ pub fn generate_default_rules() -> Vec<String> {
     ]
 }
 
+/// Personal rule overlay (rules.local.yml), layered on top of basic.yml rules.
+/// Lives outside basic.yml so that force-updating the bundled template never clobbers it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RulesOverlay {
+    /// Rules inserted before all basic.yml rules
+    #[serde(default)]
+    pub prepend: Vec<String>,
+    /// Rules inserted after basic.yml rules (but before the final MATCH rule)
+    #[serde(default)]
+    pub append: Vec<String>,
+    /// Rule target overrides, e.g. `谷歌服务: AI-专用` routes every rule targeting 谷歌服务 to AI-专用
+    #[serde(default)]
+    pub overrides: IndexMap<String, String>,
+}
+
+impl RulesOverlay {
+    pub fn is_empty(&self) -> bool {
+        self.prepend.is_empty() && self.append.is_empty() && self.overrides.is_empty()
+    }
+
+    /// Apply the overlay to a list of base rules
+    pub fn apply(&self, base_rules: Vec<String>) -> Vec<String> {
+        let mut rules: Vec<String> =
+            Vec::with_capacity(self.prepend.len() + base_rules.len() + self.append.len());
+        rules.extend(self.prepend.iter().cloned());
+
+        let mut final_rule = None;
+        for rule in base_rules {
+            let rule = self.override_target(&rule).unwrap_or(rule);
+            if is_final_rule(&rule) {
+                // Anything after MATCH is unreachable, keep it for last
+                final_rule = Some(rule);
+                continue;
+            }
+            if !self.prepend.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        for rule in &self.append {
+            if !rules.contains(rule) {
+                rules.push(rule.clone());
+            }
+        }
+
+        if let Some(rule) = final_rule {
+            rules.push(rule);
+        }
+
+        rules
+    }
+
+    /// Rewrite the target of a rule if it has an override
+    fn override_target(&self, rule: &str) -> Option<String> {
+        retarget_rule(rule, &self.overrides)
+    }
+}
+
+/// Rewrite the target (policy) of a rule according to `targets`, if it has an entry
+pub fn retarget_rule(rule: &str, targets: &IndexMap<String, String>) -> Option<String> {
+    if targets.is_empty() {
+        return None;
+    }
+
+    let mut parts = split_rule(rule);
+    let idx = rule_target_index(&parts)?;
+    let new_target = targets.get(parts[idx].trim())?;
+    parts[idx] = new_target.clone();
+    Some(parts.join(","))
+}
+
+/// The target (policy) of a rule, if it has one
+pub fn rule_target(rule: &str) -> Option<String> {
+    let parts = split_rule(rule);
+    let idx = rule_target_index(&parts)?;
+    Some(parts[idx].trim().to_string())
+}
+
+/// Split a rule at top-level commas (commas inside parentheses of logic rules are kept)
+fn split_rule(rule: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+
+    for c in rule.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current);
+    parts
+}
+
+/// Index of the target (policy) part of a split rule
+fn rule_target_index(parts: &[String]) -> Option<usize> {
+    match parts.first().map(|t| t.trim()) {
+        Some("MATCH") | Some("FINAL") => (parts.len() > 1).then_some(1),
+        Some(_) => (parts.len() > 2).then_some(2),
+        None => None,
+    }
+}
+
+fn is_final_rule(rule: &str) -> bool {
+    let rule_type = rule.split(',').next().unwrap_or("").trim();
+    rule_type == "MATCH" || rule_type == "FINAL"
+}
+
+/// Load rules.local.yml (an empty overlay if it doesn't exist)
+pub fn load_rules_overlay() -> Result<RulesOverlay> {
+    let path = crate::storage::get_rules_overlay_path()?;
+
+    if !path.exists() {
+        return Ok(RulesOverlay::default());
+    }
+
+    let content = fs::read_to_string(&path).context("Failed to read rules.local.yml")?;
+    if content.trim().is_empty() {
+        return Ok(RulesOverlay::default());
+    }
+
+    serde_yaml::from_str(&content).context("Failed to parse rules.local.yml")
+}
+
+/// Ensure rules.local.yml exists so it can be edited or patched
+pub fn ensure_rules_overlay_exists() -> Result<PathBuf> {
+    let path = crate::storage::get_rules_overlay_path()?;
+    if !path.exists() {
+        let content = serde_yaml::to_string(&RulesOverlay::default())
+            .context("Failed to serialize rules overlay")?;
+        crate::storage::write_atomic(&path, content).context("Failed to write rules.local.yml")?;
+    }
+    Ok(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,4 +669,102 @@ mod tests {
         assert!(providers.contains_key("proxy"));
         assert!(providers.contains_key("direct"));
     }
+
+    #[test]
+    fn test_count_rule_entries() {
+        let payload = "payload:\n  - '+.google.com'\n  - '+.youtube.com'\n";
+        assert_eq!(count_rule_entries(payload), 2);
+
+        let plain = "# comment\nDOMAIN-SUFFIX,a.com\n\nDOMAIN,b.com\n";
+        assert_eq!(count_rule_entries(plain), 2);
+    }
+
+    #[test]
+    fn test_rule_source_is_due() {
+        let now = chrono::Utc::now();
+        let mut source = RuleSource {
+            id: "id".to_string(),
+            name: "test".to_string(),
+            url: "https://example.com/rules.txt".to_string(),
+            enabled: true,
+            last_updated: None,
+            interval: 3600,
+            last_error: None,
+            entry_count: None,
+            last_attempt: None,
+        };
+        assert!(source.is_due(now));
+
+        source.last_updated = Some((now - chrono::Duration::seconds(600)).to_rfc3339());
+        assert!(!source.is_due(now));
+
+        source.last_updated = Some((now - chrono::Duration::seconds(7200)).to_rfc3339());
+        assert!(source.is_due(now));
+
+        // A recent failed attempt defers the retry
+        source.last_attempt = Some((now - chrono::Duration::seconds(60)).to_rfc3339());
+        assert!(!source.is_due(now));
+
+        source.last_attempt = Some((now - chrono::Duration::seconds(3600)).to_rfc3339());
+        assert!(source.is_due(now));
+
+        // An interval of 0 in a hand-edited file doesn't make it due on every tick
+        source.interval = 0;
+        source.last_attempt = None;
+        source.last_updated = Some((now - chrono::Duration::seconds(60)).to_rfc3339());
+        assert!(!source.is_due(now));
+
+        source.enabled = false;
+        assert!(!source.is_due(now));
+    }
+
+    #[test]
+    fn test_validate_rule_interval() {
+        assert!(validate_rule_interval(0).is_err());
+        assert!(validate_rule_interval(MIN_RULE_INTERVAL - 1).is_err());
+        assert!(validate_rule_interval(MIN_RULE_INTERVAL).is_ok());
+    }
+
+    #[test]
+    fn test_rules_overlay_apply() {
+        let overlay: RulesOverlay = serde_yaml::from_str(
+            r#"
+prepend:
+  - DOMAIN-SUFFIX,corp.example.com,DIRECT
+append:
+  - DOMAIN,late.example.com,节点选择
+overrides:
+  谷歌服务: AI-专用
+"#,
+        )
+        .unwrap();
+
+        let base = vec![
+            "DOMAIN-SUFFIX,google.com,谷歌服务".to_string(),
+            "IP-CIDR,8.8.8.8/32,谷歌服务,no-resolve".to_string(),
+            "AND,((DOMAIN,a.com),(NETWORK,UDP)),谷歌服务".to_string(),
+            "GEOIP,CN,DIRECT".to_string(),
+            "MATCH,漏网之鱼".to_string(),
+        ];
+
+        let rules = overlay.apply(base);
+        assert_eq!(
+            rules,
+            vec![
+                "DOMAIN-SUFFIX,corp.example.com,DIRECT",
+                "DOMAIN-SUFFIX,google.com,AI-专用",
+                "IP-CIDR,8.8.8.8/32,AI-专用,no-resolve",
+                "AND,((DOMAIN,a.com),(NETWORK,UDP)),AI-专用",
+                "GEOIP,CN,DIRECT",
+                "DOMAIN,late.example.com,节点选择",
+                "MATCH,漏网之鱼",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_rules_overlay_is_noop() {
+        let base = vec!["GEOIP,CN,DIRECT".to_string(), "MATCH,Proxy".to_string()];
+        assert_eq!(RulesOverlay::default().apply(base.clone()), base);
+    }
 }
@@ -1,85 +1,273 @@
-use crate::types::ClashConfig;
+use crate::events::{self, DaemonEvent};
+use crate::types::{AccessToken, ClashConfig, HangarConfig, TlsConfig};
 use axum::{
-    extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::get,
-    Router,
+    Json, Router,
 };
-use chrono::Local;
-use serde::Deserialize;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{oneshot, RwLock};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tower_http::compression::CompressionLayer;
+use tracing::{error, info, warn};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<RwLock<ClashConfig>>,
+    /// YAML of `config`, re-rendered only when it changes
+    rendered: Arc<RwLock<RenderedConfig>>,
+    /// Sent as `profile-update-interval` (0 = not sent)
+    update_interval: u64,
+    started_at: DateTime<Utc>,
+    /// Listening address (`scheme://host:port`), reported on /status
+    address: Option<String>,
+}
+
+/// Serialized config with its cache validators
+#[derive(Debug, Clone)]
+pub struct RenderedConfig {
+    pub yaml: Arc<String>,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+impl RenderedConfig {
+    pub fn render(config: &ClashConfig) -> anyhow::Result<Self> {
+        let yaml = serde_yaml::to_string(config)?;
+        Ok(Self {
+            etag: content_etag(&yaml),
+            yaml: Arc::new(yaml),
+            last_modified: Utc::now(),
+        })
+    }
+}
+
+/// Entity tag derived from the content hash. Sent weak (`W/`), since the compression
+/// layer serves gzip and identity bodies of the same content under it.
+fn content_etag(content: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+    format!("\"{}\"", &hash[..32])
 }
 
 #[derive(Deserialize)]
 pub struct ConfigQuery {
     pub refresh: Option<bool>,
+    pub token: Option<String>,
 }
 
 impl AppState {
-    /// 从文件重新加载配置
-    pub async fn reload_from_file(&self, path: &std::path::Path) -> anyhow::Result<()> {
-        let content = std::fs::read_to_string(path)?;
-        let new_config: ClashConfig = serde_yaml::from_str(&content)?;
+    pub fn new(config: ClashConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            rendered: Arc::new(RwLock::new(RenderedConfig::render(&config)?)),
+            config: Arc::new(RwLock::new(config)),
+            update_interval: 0,
+            started_at: Utc::now(),
+            address: None,
+        })
+    }
+
+    /// Record the listening address for /status
+    pub fn with_address(mut self, address: String) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Advertise the auto-update interval (seconds) to clients
+    pub fn with_update_interval(mut self, interval: u64) -> Self {
+        self.update_interval = interval;
+        self
+    }
+
+    /// 替换内存中的配置；内容不变时保留原 ETag 和 Last-Modified。返回内容是否变化
+    pub async fn set_config(&self, new_config: ClashConfig) -> anyhow::Result<bool> {
+        let rendered = RenderedConfig::render(&new_config)?;
 
         let mut config = self.config.write().await;
+        let mut current = self.rendered.write().await;
+        let changed = current.etag != rendered.etag;
+        if changed {
+            *current = rendered;
+        }
         *config = new_config;
 
-        Ok(())
+        Ok(changed)
+    }
+
+    pub async fn rendered(&self) -> RenderedConfig {
+        self.rendered.read().await.clone()
+    }
+
+    /// 合并已保存的订阅，写入 current.yaml，并以结果创建状态
+    pub async fn load_initial() -> anyhow::Result<Self> {
+        Self::new(crate::proxy::regenerate_current_config().await?)
+    }
+
+    /// 从文件重新加载配置
+    pub async fn reload_from_file(&self, path: &std::path::Path) -> anyhow::Result<bool> {
+        let content = std::fs::read_to_string(path)?;
+        let new_config: ClashConfig = serde_yaml::from_str(&content)?;
+        self.set_config(new_config).await
     }
 
     /// 刷新配置（重新下载订阅并合并）
     pub async fn refresh(&self) -> anyhow::Result<()> {
-        eprintln!("🔄 手动刷新订阅...");
+        info!("🔄 手动刷新订阅...");
         // 1. 加载订阅
         let mut subs = crate::storage::load_subscriptions().unwrap_or_default();
-        let mut any_updated = false;
+        let mut any_changed = false;
 
         for sub in &mut subs {
             if sub.enabled {
+                events::publish(DaemonEvent::RefreshStarted {
+                    subscription: sub.name.clone(),
+                });
                 match crate::subscription::download_subscription(sub).await {
                     Ok(_) => {
                         sub.last_updated =
                             Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
-                        any_updated = true;
+                        sub.last_error = None;
+                        any_changed = true;
+                        crate::metrics::record_download(&sub.name, true);
+                        events::publish(DaemonEvent::RefreshFinished {
+                            subscription: sub.name.clone(),
+                            node_count: crate::proxy::parse_cached_subscription(sub)
+                                .ok()
+                                .map(|proxies| proxies.len()),
+                            error: None,
+                        });
                     }
                     Err(e) => {
-                        eprintln!("⚠️ 无法更新订阅 {}: {}", sub.name, e)
+                        warn!("⚠️ 无法更新订阅 {}: {}", sub.name, e);
+                        sub.last_error = Some(e.to_string());
+                        any_changed = true;
+                        crate::metrics::record_download(&sub.name, false);
+                        events::publish(DaemonEvent::RefreshFinished {
+                            subscription: sub.name.clone(),
+                            node_count: None,
+                            error: Some(e.to_string()),
+                        });
                     }
                 }
             }
         }
 
-        if any_updated {
-            crate::storage::save_subscriptions(&subs)?;
+        // 下载期间订阅列表可能已被修改，只在最新列表上记录刷新结果
+        if any_changed {
+            subs = crate::storage::modify_subscriptions(|stored| {
+                for sub in &subs {
+                    if let Some(s) = stored.iter_mut().find(|s| s.id == sub.id) {
+                        s.last_updated = sub.last_updated.clone();
+                        s.last_error = sub.last_error.clone();
+                    }
+                }
+                Ok(stored.clone())
+            })?;
         }
 
         // 2. 合并配置
         let merged = crate::proxy::merge_configs(&subs).await?;
 
         // 3. 保存到 current.yaml
-        let current_path = crate::storage::get_current_config_path()?;
-        crate::config::save_config(&merged, current_path.to_str().unwrap())?;
+        crate::proxy::write_current_config(&merged)?;
 
         // 4. 更新内存中的状态
-        let mut config = self.config.write().await;
-        *config = merged;
+        self.set_config(merged).await?;
 
-        eprintln!("✅ 刷新完成");
+        info!("✅ 刷新完成");
         Ok(())
     }
 }
 
+/// 校验请求携带的令牌；未配置令牌时返回 `Ok(None)`，拒绝时记录日志
+fn authorize(
+    hangar_config: &HangarConfig,
+    headers: &HeaderMap,
+    query_token: Option<&str>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    path: &str,
+) -> Result<Option<AccessToken>, StatusCode> {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    let presented = crate::auth::extract_token(authorization, query_token);
+
+    match crate::auth::authenticate(hangar_config, presented, chrono::Utc::now().timestamp()) {
+        crate::auth::AuthResult::Open => Ok(None),
+        crate::auth::AuthResult::Granted(token) => Ok(Some(token)),
+        crate::auth::AuthResult::Denied(reason) => {
+            let user_agent = headers
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-");
+            let addr = remote
+                .map(|ConnectInfo(addr)| addr.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            warn!(
+                "🚫 拒绝 {} 请求: {} (来自 {}, UA: {})",
+                path, reason, addr, user_agent
+            );
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
 /// 获取配置的处理器
-async fn get_config(
+pub async fn get_config(
     State(state): State<AppState>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     axum::extract::Query(query): axum::extract::Query<ConfigQuery>,
 ) -> Response {
+    let hangar_config = match crate::storage::load_hangar_config() {
+        Ok(c) => c,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to load config: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let profile = match authorize(
+        &hangar_config,
+        &headers,
+        query.token.as_deref(),
+        remote,
+        "/config",
+    ) {
+        Ok(Some(token)) if token.profile != "default" => {
+            // Never fall back to the full config for a token scoped to a missing profile
+            match crate::profile::find_profile(&hangar_config, &token.profile) {
+                Some(profile) => Some(profile.clone()),
+                None => {
+                    warn!(
+                        "Token '{}' uses unknown profile '{}'",
+                        token.name, token.profile
+                    );
+                    return (
+                        StatusCode::FORBIDDEN,
+                        format!("Profile not found: '{}'", token.profile),
+                    )
+                        .into_response();
+                }
+            }
+        }
+        Ok(_) => None,
+        Err(status) => return (status, "Unauthorized").into_response(),
+    };
+
     if let Some(true) = query.refresh {
         if let Err(e) = state.refresh().await {
             return (
@@ -90,13 +278,352 @@ async fn get_config(
         }
     }
 
-    let config = state.config.read().await;
+    let mut rendered = state.rendered().await;
+    if let Some(profile) = profile {
+        let subs = crate::storage::load_subscriptions().unwrap_or_default();
+        let config = state.config.read().await;
+        let view = crate::profile::apply_profile(&config, &profile, &subs);
+        match serde_yaml::to_string(&view) {
+            Ok(yaml) => {
+                // Derived from the full config, so it changes no later than it
+                rendered.etag = content_etag(&yaml);
+                rendered.yaml = Arc::new(yaml);
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to serialize config: {}", e),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    let mut response_headers = HeaderMap::new();
+    let last_modified = format_http_date(rendered.last_modified);
+    if let Ok(v) = HeaderValue::from_str(&format!("W/{}", rendered.etag)) {
+        response_headers.insert(header::ETAG, v);
+    }
+    // Also on 304s, which the compression layer leaves alone
+    response_headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    if let Ok(v) = HeaderValue::from_str(&last_modified) {
+        response_headers.insert(header::LAST_MODIFIED, v);
+    }
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if state.update_interval > 0 {
+        // Clash clients expect hours
+        let hours = state.update_interval.div_ceil(3600);
+        response_headers.insert("profile-update-interval", HeaderValue::from(hours));
+    }
 
-    match serde_yaml::to_string(&*config) {
-        Ok(yaml) => (StatusCode::OK, yaml).into_response(),
+    if is_not_modified(&headers, &rendered.etag, rendered.last_modified) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/yaml; charset=utf-8"),
+    );
+    (
+        StatusCode::OK,
+        response_headers,
+        rendered.yaml.as_ref().clone(),
+    )
+        .into_response()
+}
+
+fn format_http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value.trim(), "%a, %d %b %Y %H:%M:%S GMT")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Evaluate `If-None-Match` (takes precedence) or `If-Modified-Since`
+fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: DateTime<Utc>) -> bool {
+    if let Some(if_none_match) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    {
+        return if_none_match.split(',').map(|t| t.trim()).any(|t| {
+            // Weak comparison (RFC 9110 13.1.2)
+            t == "*" || t.trim_start_matches("W/") == etag
+        });
+    }
+
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_http_date)
+        .is_some_and(|since| last_modified.timestamp() <= since.timestamp())
+}
+
+/// Refuse to listen beyond this machine without any token, unless `allow_unauthenticated`
+/// is given; then only warn
+pub fn check_unprotected(host: &str, allow_unauthenticated: bool) -> anyhow::Result<()> {
+    let loopback = host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false);
+    let has_tokens = !crate::storage::load_hangar_config()?
+        .server
+        .tokens
+        .is_empty();
+
+    if loopback || has_tokens {
+        return Ok(());
+    }
+    if !allow_unauthenticated {
+        anyhow::bail!(
+            "服务器监听 {} 但未配置访问令牌；使用 `hangar token add <name>` 创建令牌，\
+             或加上 --allow-unauthenticated 允许无令牌访问",
+            host
+        );
+    }
+    warn!(
+        "⚠️ 服务器监听 {} 且未配置访问令牌，/config 对所有可访问该端口的人开放",
+        host
+    );
+    warn!("   使用 `hangar token add <name>` 创建令牌");
+    Ok(())
+}
+
+/// 需要管理员令牌的端点使用（与 /api 相同，未创建管理员令牌时始终拒绝）
+fn reject_non_admin(
+    headers: &HeaderMap,
+    query_token: Option<&str>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    path: &str,
+) -> Option<Response> {
+    let hangar_config = match crate::storage::load_hangar_config() {
+        Ok(c) => c,
+        Err(e) => {
+            return Some(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to load config: {}", e),
+                )
+                    .into_response(),
+            )
+        }
+    };
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    let presented = crate::auth::extract_token(authorization, query_token);
+
+    match crate::auth::authorize_admin(&hangar_config, presented, chrono::Utc::now().timestamp()) {
+        Ok(_) => None,
+        Err(reason) => {
+            let addr = remote
+                .map(|ConnectInfo(addr)| addr.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            warn!("🚫 拒绝 {} 请求: {} (来自 {})", path, reason, addr);
+            Some((StatusCode::UNAUTHORIZED, "Unauthorized").into_response())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    pub token: Option<String>,
+}
+
+/// 守护进程事件流（SSE），需要管理员令牌
+async fn events_stream(
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<TokenQuery>,
+) -> Response {
+    if let Some(rejection) = reject_non_admin(&headers, query.token.as_deref(), remote, "/events") {
+        return rejection;
+    }
+
+    // A subscriber that falls behind skips the events it missed
+    let stream = BroadcastStream::new(events::subscribe()).filter_map(|record| {
+        let record = record.ok()?;
+        Some(
+            Event::default()
+                .event(record.event.kind())
+                .json_data(&record),
+        )
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// 按客户端和状态码统计 /config 请求
+async fn count_config_requests(request: Request, next: Next) -> Response {
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let response = next.run(request).await;
+    crate::metrics::record_config_request(user_agent.as_deref(), response.status().as_u16());
+    response
+}
+
+/// Prometheus 指标：包含订阅名称和客户端分布，需要管理令牌（与 /events 相同，未配置时关闭）
+async fn metrics(
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<TokenQuery>,
+) -> Response {
+    if let Some(rejection) = reject_non_admin(&headers, query.token.as_deref(), remote, "/metrics")
+    {
+        return rejection;
+    }
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        )],
+        crate::metrics::render(),
+    )
+        .into_response()
+}
+
+/// /status 中的订阅状态
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionStatus {
+    pub name: String,
+    pub enabled: bool,
+    pub last_updated: Option<String>,
+    pub node_count: Option<usize>,
+    pub last_error: Option<String>,
+}
+
+/// /status 中当前提供的配置
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServedConfigStatus {
+    /// Same hash as the /config ETag
+    pub hash: String,
+    /// Unix timestamp, same as the /config Last-Modified
+    pub last_modified: i64,
+    pub proxies: usize,
+    pub proxy_groups: usize,
+    pub rules: usize,
+}
+
+/// /status 响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub version: String,
+    /// Unix timestamp
+    pub started_at: i64,
+    pub uptime_seconds: i64,
+    pub address: Option<String>,
+    pub profiles: Vec<String>,
+    pub subscriptions: Vec<SubscriptionStatus>,
+    pub config: ServedConfigStatus,
+    pub last_merge: Option<crate::proxy::MergeReport>,
+}
+
+impl AppState {
+    pub async fn status(&self) -> anyhow::Result<ServerStatus> {
+        let hangar_config = crate::storage::load_hangar_config()?;
+        let subscriptions = crate::storage::load_subscriptions()?
+            .into_iter()
+            .map(|sub| SubscriptionStatus {
+                name: sub.name,
+                enabled: sub.enabled,
+                last_updated: sub.last_updated,
+                node_count: sub.node_count,
+                last_error: sub
+                    .last_error
+                    .as_deref()
+                    .map(crate::subscription::redact_urls),
+            })
+            .collect();
+
+        let rendered = self.rendered().await;
+        let config = {
+            let config = self.config.read().await;
+            ServedConfigStatus {
+                hash: rendered.etag.trim_matches('"').to_string(),
+                last_modified: rendered.last_modified.timestamp(),
+                proxies: config.proxies.len(),
+                proxy_groups: config.proxy_groups.len(),
+                rules: config.rules.len(),
+            }
+        };
+
+        Ok(ServerStatus {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: self.started_at.timestamp(),
+            uptime_seconds: (Utc::now() - self.started_at).num_seconds(),
+            address: self.address.clone(),
+            profiles: std::iter::once("default".to_string())
+                .chain(hangar_config.profiles.into_iter().map(|p| p.name))
+                .collect(),
+            subscriptions,
+            config,
+            last_merge: crate::proxy::last_merge(),
+        })
+    }
+}
+
+/// 查询本机运行中服务器的 /status（`hangar status` 使用）
+pub async fn fetch_status(
+    host: &str,
+    port: u16,
+    tls: bool,
+    token: Option<&str>,
+) -> anyhow::Result<ServerStatus> {
+    // A wildcard bind is reachable through loopback
+    let host = match host {
+        "0.0.0.0" | "::" | "[::]" => "127.0.0.1",
+        host => host,
+    };
+    let scheme = if tls { "https" } else { "http" };
+    let url = format!("{}://{}:{}/status", scheme, host, port);
+
+    // Local query: the certificate may come from the self-signed local CA
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .danger_accept_invalid_certs(true)
+        .build()?;
+    let mut request = client.get(&url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        anyhow::bail!("/status needs an admin token (--token or HANGAR_TOKEN)");
+    }
+    Ok(response.error_for_status()?.json().await?)
+}
+
+/// 运行状态（JSON）。未创建令牌时开放，否则需要管理员令牌（含订阅名称和错误信息）
+async fn status(
+    State(state): State<AppState>,
+    remote: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    axum::extract::Query(query): axum::extract::Query<TokenQuery>,
+) -> Response {
+    let open = crate::storage::load_hangar_config()
+        .map(|c| c.server.tokens.is_empty())
+        .unwrap_or(false);
+    if !open {
+        if let Some(rejection) =
+            reject_non_admin(&headers, query.token.as_deref(), remote, "/status")
+        {
+            return rejection;
+        }
+    }
+
+    match state.status().await {
+        Ok(status) => Json(status).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to serialize config: {}", e),
+            format!("Failed to collect status: {}", e),
         )
             .into_response(),
     }
@@ -107,63 +634,220 @@ async fn health_check() -> &'static str {
     "OK"
 }
 
-/// 创建并启动 HTTP 服务器
-pub async fn start_server(
-    config: ClashConfig,
-    host: &str,
-    port: u16,
-    shutdown_rx: oneshot::Receiver<()>,
-) -> anyhow::Result<()> {
-    eprintln!("🔍 start_server 函数被调用: {}:{}", host, port);
+/// CLI 和 GUI 共用的路由
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/config",
+            get(get_config).layer(middleware::from_fn(count_config_requests)),
+        )
+        .route("/health", get(health_check))
+        .route("/status", get(status))
+        .route("/events", get(events_stream))
+        .route("/metrics", get(metrics))
+        .nest("/api", crate::api::router())
+        .layer(CompressionLayer::new())
+        .with_state(state)
+}
 
-    let state = AppState {
-        config: Arc::new(RwLock::new(config)),
-    };
+/// 监听 current.yaml，文件变化时重新加载到内存。
+/// 监听所在目录而非文件本身：原子写入会用重命名替换文件
+pub fn spawn_config_watcher(state: AppState, watch_path: PathBuf) {
+    let runtime = tokio::runtime::Handle::current();
 
-    eprintln!("✓ AppState 创建成功");
+    std::thread::spawn(move || {
+        use notify::{Event, EventKind, RecursiveMode, Watcher};
+        use std::time::{Duration, Instant};
 
-    let app = Router::new()
-        .route("/config", get(get_config))
-        .route("/health", get(health_check))
-        .with_state(state);
+        let (tx_notify, rx_notify) = std::sync::mpsc::channel::<Result<Event, notify::Error>>();
 
-    eprintln!("✓ Router 创建成功");
+        let mut watcher = match notify::recommended_watcher(tx_notify) {
+            Ok(w) => w,
+            Err(e) => {
+                warn!("⚠️ 无法创建文件监听: {}", e);
+                return;
+            }
+        };
+        let watch_dir = watch_path.parent().unwrap_or(&watch_path).to_path_buf();
+        if let Err(e) = watcher.watch(&watch_dir, RecursiveMode::NonRecursive) {
+            warn!("⚠️ 无法监听 {:?}: {}", watch_dir, e);
+            return;
+        }
 
-    let addr = format!("{}:{}", host, port);
-    eprintln!("🔍 尝试绑定地址: {}", addr);
+        info!("👀 Watching for changes to {:?}", watch_path);
 
-    let listener = match tokio::net::TcpListener::bind(&addr).await {
-        Ok(l) => {
-            eprintln!("✓ TcpListener 绑定成功");
-            l
-        }
-        Err(e) => {
-            eprintln!("❌ TcpListener 绑定失败: {}", e);
-            return Err(anyhow::anyhow!("无法绑定地址 {}: {}", addr, e));
+        // Debounce mechanism to prevent duplicate events
+        let debounce_duration = Duration::from_millis(100);
+        let mut last_reload = Instant::now();
+
+        for res in rx_notify {
+            match res {
+                Ok(event) => {
+                    // Data written to current.yaml, or a new file renamed over it
+                    let is_target = event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name() == watch_path.file_name());
+                    if is_target
+                        && matches!(
+                            event.kind,
+                            EventKind::Create(_)
+                                | EventKind::Modify(notify::event::ModifyKind::Data(_))
+                                | EventKind::Modify(notify::event::ModifyKind::Name(_))
+                        )
+                        && last_reload.elapsed() > debounce_duration
+                    {
+                        last_reload = Instant::now();
+                        info!("📝 Detected change in current.yaml, reloading...");
+
+                        let state = state.clone();
+                        let path = watch_path.clone();
+                        runtime.spawn(async move {
+                            match state.reload_from_file(&path).await {
+                                Ok(changed) => {
+                                    info!("✅ Config reloaded successfully");
+                                    crate::metrics::record_reload(true);
+                                    events::publish(DaemonEvent::ConfigReloaded { changed });
+                                }
+                                Err(e) => {
+                                    error!("❌ Failed to reload config: {}", e);
+                                    crate::metrics::record_reload(false);
+                                }
+                            }
+                        });
+                    }
+                }
+                Err(e) => warn!("⚠️ Watch error: {}", e),
+            }
         }
-    };
+    });
+}
 
-    eprintln!("\n🌐 正在启动 HTTP 服务器...");
-    eprintln!("   地址: http://{}", addr);
-    eprintln!("   订阅链接: http://{}/config", addr);
-    eprintln!("\n✨ 服务器已启动，等待请求...\n");
-
-    // 使用 with_graceful_shutdown 支持优雅关闭
-    match axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            // 等待关闭信号
-            let _ = shutdown_rx.await;
-            eprintln!("🛑 收到关闭信号，开始优雅关闭...");
-        })
-        .await
-    {
-        Ok(_) => {
-            eprintln!("✓ 服务器已优雅关闭");
-            Ok(())
+/// 定时更新订阅（每 `interval` 秒）并按各自间隔刷新规则源
+pub fn spawn_auto_update(state: AppState, interval: u64) {
+    if interval == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let tick = std::time::Duration::from_secs(60);
+        loop {
+            match crate::rules::refresh_due_rule_sources().await {
+                Ok(0) => {}
+                Ok(n) => info!("📏 Refreshed {} rule source(s)", n),
+                Err(e) => error!("❌ Failed to refresh rule sources: {}", e),
+            }
+            tokio::time::sleep(tick).await;
         }
-        Err(e) => {
-            eprintln!("❌ 服务器错误: {}", e);
-            Err(anyhow::anyhow!("服务器运行错误: {}", e))
+    });
+
+    tokio::spawn(async move {
+        let duration = std::time::Duration::from_secs(interval);
+        loop {
+            tokio::time::sleep(duration).await;
+            info!("⏰ Auto-updating subscriptions...");
+            if let Err(e) = state.refresh().await {
+                error!("❌ Auto-update failed: {}", e);
+            }
         }
+    });
+}
+
+/// 在监听地址上运行应用，配置了 TLS 时使用 HTTPS（证书文件变化时热加载）
+pub async fn serve_app(
+    app: Router,
+    listener: std::net::TcpListener,
+    tls: Option<TlsConfig>,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    listener.set_nonblocking(true)?;
+
+    let Some(tls) = tls else {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        axum::serve(listener, make_service)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        return Ok(());
+    };
+
+    let rustls_config = crate::tls::load_rustls_config(&tls).await?;
+    crate::tls::watch_and_reload(rustls_config.clone(), tls);
+
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(Some(std::time::Duration::from_secs(10)));
+    });
+
+    axum_server::from_tcp_rustls(listener, rustls_config)
+        .handle(handle)
+        .serve(make_service)
+        .await?;
+    Ok(())
+}
+
+/// 订阅链接的协议前缀
+pub fn scheme(tls: &Option<TlsConfig>) -> &'static str {
+    if tls.is_some() {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+/// 服务器启动参数
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    pub host: String,
+    pub port: u16,
+    /// 订阅自动更新间隔（秒），0 表示禁用
+    pub interval: u64,
+    pub tls: Option<TlsConfig>,
+    /// Listen beyond this machine even when no access token exists
+    pub allow_unauthenticated: bool,
+}
+
+/// 创建并启动 HTTP 服务器（CLI `serve` 和 GUI 共用）
+pub async fn start_server(
+    state: AppState,
+    options: ServeOptions,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
+    check_unprotected(&options.host, options.allow_unauthenticated)?;
+    let addr = format!("{}:{}", options.host, options.port);
+    let scheme = scheme(&options.tls);
+    let state = state
+        .with_update_interval(options.interval)
+        .with_address(format!("{}://{}", scheme, addr));
+    let listener = std::net::TcpListener::bind(&addr).map_err(|e| {
+        error!("❌ TcpListener 绑定失败: {}", e);
+        anyhow::anyhow!("无法绑定地址 {}: {}", addr, e)
+    })?;
+
+    spawn_config_watcher(state.clone(), crate::storage::get_current_config_path()?);
+    spawn_auto_update(state.clone(), options.interval);
+
+    info!("🌐 服务器已启动");
+    info!("   地址: {}://{}", scheme, addr);
+    info!("   订阅链接: {}://{}/config", scheme, addr);
+    if let Some(tls) = &options.tls {
+        info!("   证书: {} (文件变化时自动重新加载)", tls.cert_path);
     }
+    info!("✨ 等待请求...");
+    crate::service::notify_ready(&format!("Serving {}://{}", scheme, addr));
+
+    // 支持优雅关闭
+    let shutdown = async move {
+        // 等待关闭信号
+        let _ = shutdown_rx.await;
+        info!("🛑 收到关闭信号，开始优雅关闭...");
+        crate::service::notify_stopping();
+    };
+
+    serve_app(router(state), listener, options.tls, shutdown).await?;
+    info!("✓ 服务器已优雅关闭");
+    Ok(())
 }
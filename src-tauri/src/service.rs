@@ -0,0 +1,199 @@
+use crate::daemon::ServeArgs;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+pub const UNIT_NAME: &str = "hangar.service";
+
+/// `~/.config/systemd/user/hangar.service`
+pub fn unit_path() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().context("Failed to get config directory")?;
+    Ok(config_dir.join("systemd").join("user").join(UNIT_NAME))
+}
+
+/// Quote an `ExecStart=` argument (systemd expands `%` specifiers and `$` variables)
+fn quote_arg(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if escaped.is_empty() || escaped.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\')
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// Unit running `hangar serve` in the foreground; the server reports readiness with sd_notify
+pub fn render_unit(exe: &Path, args: &ServeArgs) -> String {
+    let exec_start = std::iter::once(exe.to_string_lossy().to_string())
+        .chain(args.to_cli_args())
+        .map(|a| quote_arg(&a))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "[Unit]
+Description=Hangar config server
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=5s
+
+[Install]
+WantedBy=default.target
+",
+        exec_start
+    )
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = std::process::Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("Failed to run systemctl")?;
+    if !status.success() {
+        anyhow::bail!("systemctl --user {} failed ({})", args.join(" "), status);
+    }
+    Ok(())
+}
+
+/// Write the unit file, then enable and start it
+pub fn install(exe: &Path, args: &ServeArgs) -> Result<PathBuf> {
+    let path = unit_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create systemd user directory")?;
+    }
+    std::fs::write(&path, render_unit(exe, args))
+        .with_context(|| format!("Failed to write {:?}", path))?;
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", UNIT_NAME])?;
+    Ok(path)
+}
+
+/// Stop and disable the service and remove its unit file. Returns false if it wasn't installed.
+pub fn uninstall() -> Result<bool> {
+    let path = unit_path()?;
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    systemctl(&["disable", "--now", UNIT_NAME])?;
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+    systemctl(&["daemon-reload"])?;
+    Ok(true)
+}
+
+/// Print `systemctl --user status` for the service
+pub fn status() -> Result<()> {
+    // `status` exits non-zero for stopped units, which is not an error here
+    std::process::Command::new("systemctl")
+        .args(["--user", "status", "--no-pager", UNIT_NAME])
+        .status()
+        .context("Failed to run systemctl")?;
+    Ok(())
+}
+
+/// Send an sd_notify message when started by systemd with `Type=notify` (no-op otherwise)
+pub fn notify(state: &str) {
+    if let Ok(socket) = std::env::var("NOTIFY_SOCKET") {
+        if let Err(e) = notify_to(&socket, state) {
+            tracing::warn!("⚠️ sd_notify 失败: {}", e);
+        }
+    }
+}
+
+pub fn notify_ready(status: &str) {
+    notify(&format!("READY=1\nSTATUS={}", status));
+}
+
+pub fn notify_reloading() {
+    notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec()));
+}
+
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+#[cfg(unix)]
+fn monotonic_usec() -> u64 {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+#[cfg(not(unix))]
+fn monotonic_usec() -> u64 {
+    0
+}
+
+/// Send `state` to the datagram socket at `socket` (`@name` is an abstract socket)
+#[cfg(unix)]
+pub fn notify_to(socket: &str, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let sender = UnixDatagram::unbound()?;
+    match socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            sender.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        _ => {
+            sender.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn notify_to(_socket: &str, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_unit() {
+        let args = ServeArgs {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            interval: 3600,
+            tls_cert: Some("/home/me/My Certs/cert.pem".to_string()),
+            tls_key: Some("/home/me/key%1.pem".to_string()),
+            allow_unauthenticated: false,
+        };
+        let unit = render_unit(Path::new("/usr/local/bin/hangar"), &args);
+
+        assert!(unit.contains("Type=notify\n"));
+        assert!(unit.contains("ExecReload=/bin/kill -HUP $MAINPID\n"));
+        assert!(unit.contains("WantedBy=default.target\n"));
+        assert!(unit.contains(
+            "ExecStart=/usr/local/bin/hangar serve --host 0.0.0.0 --port 8080 --interval 3600 \
+             --tls-cert \"/home/me/My Certs/cert.pem\" --tls-key /home/me/key%%1.pem\n"
+        ));
+        assert!(!unit.contains("--daemon"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_notify_to_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        notify_to(path.to_str().unwrap(), "READY=1\nSTATUS=Serving").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=Serving");
+    }
+}
@@ -1,7 +1,9 @@
 use crate::types::{HangarConfig, Subscription, SubscriptionList};
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Get the Hangar data directory path (~/.hangar/)
 pub fn get_hangar_dir() -> Result<PathBuf> {
@@ -14,6 +16,63 @@ pub fn get_hangar_dir() -> Result<PathBuf> {
     Ok(hangar_dir)
 }
 
+/// Write `content` to a temp file next to `path`, then rename it over `path`,
+/// so readers (e.g. the file watcher) see the old or the new file, never a partial one
+pub fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid file path: {:?}", path))?
+        .to_string_lossy();
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content.as_ref())?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result.with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// An advisory lock on a state file, released when dropped
+pub struct FileLock {
+    _file: fs::File,
+}
+
+/// Lock `path` for a read-modify-write cycle, waiting for other threads and processes
+/// (CLI, GUI, daemon) holding it. The lock lives on a `.<name>.lock` file beside `path`,
+/// since `write_atomic` replaces `path` itself. Not reentrant: don't lock twice on one thread.
+pub fn lock_file(path: &Path) -> Result<FileLock> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid file path: {:?}", path))?
+        .to_string_lossy();
+    let lock_path = path.with_file_name(format!(".{}.lock", file_name));
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {:?}", lock_path))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {:?}", path))?;
+    Ok(FileLock { _file: file })
+}
+
 /// Get the path to config.json
 pub fn get_config_path() -> Result<PathBuf> {
     Ok(get_hangar_dir()?.join("config.json"))
@@ -50,9 +109,31 @@ pub fn get_server_pid_path() -> Result<PathBuf> {
     Ok(get_hangar_dir()?.join("server.pid"))
 }
 
-/// Get the path to server.log
+/// Get the path to server.json (arguments of the last `hangar serve`)
+pub fn get_server_args_path() -> Result<PathBuf> {
+    Ok(get_hangar_dir()?.join("server.json"))
+}
+
+/// Get the directory holding TLS certificates (~/.hangar/tls)
+pub fn get_tls_dir() -> Result<PathBuf> {
+    Ok(get_hangar_dir()?.join("tls"))
+}
+
+/// Get the logs directory path
+pub fn get_logs_dir() -> Result<PathBuf> {
+    let dir = get_hangar_dir()?.join("logs");
+    fs::create_dir_all(&dir).context("Failed to create logs directory")?;
+    Ok(dir)
+}
+
+/// Get the path to the current server log, logs/hangar.log
 pub fn get_server_log_path() -> Result<PathBuf> {
-    Ok(get_hangar_dir()?.join("server.log"))
+    Ok(get_logs_dir()?.join("hangar.log"))
+}
+
+/// Get the path to the daemon's raw stdout/stderr (panics, early startup errors)
+pub fn get_daemon_output_path() -> Result<PathBuf> {
+    Ok(get_logs_dir()?.join("daemon.out"))
 }
 
 /// Get the versions directory path
@@ -62,6 +143,18 @@ pub fn get_versions_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
+/// Get the path to versions/index.json
+pub fn get_versions_index_path() -> Result<PathBuf> {
+    Ok(get_versions_dir()?.join("index.json"))
+}
+
+/// Get the directory holding proxies of local subscriptions (~/.hangar/local)
+pub fn get_local_proxies_dir() -> Result<PathBuf> {
+    let dir = get_hangar_dir()?.join("local");
+    fs::create_dir_all(&dir).context("Failed to create local proxies directory")?;
+    Ok(dir)
+}
+
 /// Get the cache directory path
 pub fn get_cache_dir() -> Result<PathBuf> {
     let dir = get_hangar_dir()?.join("cache");
@@ -120,14 +213,15 @@ pub fn ensure_basic_config_exists() -> Result<()> {
     let target_path = get_basic_config_path()?;
     if !target_path.exists() {
         let default_path = get_default_basic_config_path()?;
-        if default_path.exists() {
-            fs::copy(default_path, target_path)
-                .context("Failed to copy default basic.yml to .hangar")?;
+        let default_content = if default_path.exists() {
+            fs::read_to_string(default_path).context("Failed to read default basic.yml")?
         } else {
             // Use bundled default if file not found
-            let default_content = include_str!("../resources/basic.yml");
-            fs::write(target_path, default_content).context("Failed to write bundled basic.yml")?;
-        }
+            include_str!("../resources/basic.yml").to_string()
+        };
+        write_atomic(&target_path, &default_content)
+            .context("Failed to write basic.yml to .hangar")?;
+        save_installed_template("basic.yml", &default_content)?;
     }
     Ok(())
 }
@@ -147,23 +241,53 @@ pub fn ensure_groups_config_exists() -> Result<()> {
     let target_path = get_groups_config_path()?;
     if !target_path.exists() {
         let default_path = get_default_groups_config_path()?;
-        if default_path.exists() {
-            fs::copy(default_path, target_path)
-                .context("Failed to copy default groups.yml to .hangar")?;
+        let default_content = if default_path.exists() {
+            fs::read_to_string(default_path).context("Failed to read default groups.yml")?
         } else {
             // Use bundled default if file not found
-            let default_content = include_str!("../resources/groups.yml");
-            fs::write(target_path, default_content)
-                .context("Failed to write bundled groups.yml")?;
-        }
+            include_str!("../resources/groups.yml").to_string()
+        };
+        write_atomic(&target_path, &default_content)
+            .context("Failed to write groups.yml to .hangar")?;
+        save_installed_template("groups.yml", &default_content)?;
     }
     Ok(())
 }
 
+/// Get the directory holding the templates Hangar last installed.
+/// They are the common ancestor when merging template updates into user files.
+pub fn get_installed_templates_dir() -> Result<PathBuf> {
+    let dir = get_hangar_dir()?.join("templates");
+    fs::create_dir_all(&dir).context("Failed to create templates directory")?;
+    Ok(dir)
+}
+
+/// Load the template last installed for a config file (e.g. "basic.yml"), if recorded
+pub fn load_installed_template(file_name: &str) -> Result<Option<String>> {
+    let path = get_installed_templates_dir()?.join(file_name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).context("Failed to read installed template")?;
+    Ok(Some(content))
+}
+
+/// Record the template installed for a config file
+pub fn save_installed_template(file_name: &str, content: &str) -> Result<()> {
+    let path = get_installed_templates_dir()?.join(file_name);
+    write_atomic(&path, content).context("Failed to write installed template")?;
+    Ok(())
+}
+
+/// Get path to rules.local.yml (in .hangar directory - personal rule overlay)
+pub fn get_rules_overlay_path() -> Result<PathBuf> {
+    Ok(get_hangar_dir()?.join("rules.local.yml"))
+}
+
 /// Save proxies to cache for a subscription (Raw YAML)
 pub fn save_proxies_cache(subscription_id: &str, content: &str) -> Result<PathBuf> {
     let cache_path = get_subscription_cache_path(subscription_id)?;
-    fs::write(&cache_path, content).context("Failed to write proxies cache file")?;
+    write_atomic(&cache_path, content).context("Failed to write proxies cache file")?;
     Ok(cache_path)
 }
 
@@ -196,7 +320,7 @@ pub fn load_hangar_config() -> Result<HangarConfig> {
     if !config_path.exists() {
         // Create default config
         let default_config = HangarConfig::default();
-        save_hangar_config(&default_config)?;
+        write_hangar_config(&config_path, &default_config)?;
         return Ok(default_config);
     }
 
@@ -207,12 +331,58 @@ pub fn load_hangar_config() -> Result<HangarConfig> {
     Ok(config)
 }
 
-/// Save Hangar configuration
+fn write_hangar_config(path: &Path, config: &HangarConfig) -> Result<()> {
+    let content = serde_json::to_string_pretty(config).context("Failed to serialize config")?;
+    write_atomic(path, content).context("Failed to write config.json")
+}
+
+/// Save Hangar configuration, replacing it as a whole.
+/// To change part of it, use `modify_hangar_config` so concurrent changes aren't lost.
 pub fn save_hangar_config(config: &HangarConfig) -> Result<()> {
     let config_path = get_config_path()?;
-    let content = serde_json::to_string_pretty(config).context("Failed to serialize config")?;
-    fs::write(&config_path, content).context("Failed to write config.json")?;
-    Ok(())
+    let _lock = lock_file(&config_path)?;
+    write_hangar_config(&config_path, config)
+}
+
+/// Load, change and save config.json while holding its lock. Nothing is written if `f`
+/// leaves the config unchanged or fails.
+pub fn modify_hangar_config<T>(f: impl FnOnce(&mut HangarConfig) -> Result<T>) -> Result<T> {
+    let config_path = get_config_path()?;
+    let _lock = lock_file(&config_path)?;
+    let mut config = load_hangar_config()?;
+    let before = serde_json::to_value(&config)?;
+    let result = f(&mut config)?;
+    if serde_json::to_value(&config)? != before {
+        write_hangar_config(&config_path, &config)?;
+    }
+    Ok(result)
+}
+
+/// Merge a partial config (JSON object) into the stored one and save it.
+/// Keys missing from `patch` keep their stored values.
+pub fn update_hangar_config(patch: serde_json::Value) -> Result<HangarConfig> {
+    modify_hangar_config(|config| {
+        let mut merged = serde_json::to_value(&*config).context("Failed to serialize config")?;
+        merge_json(&mut merged, patch);
+        *config = serde_json::from_value(merged).context("Failed to parse updated config")?;
+        Ok(config.clone())
+    })
+}
+
+fn merge_json(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
 }
 
 /// Load subscriptions list
@@ -221,11 +391,8 @@ pub fn load_subscriptions() -> Result<Vec<Subscription>> {
 
     if !subs_path.exists() {
         // Create default subscriptions file
-        let default_list = SubscriptionList {
-            subscriptions: vec![],
-        };
-        save_subscriptions(&default_list.subscriptions)?;
-        return Ok(default_list.subscriptions);
+        write_subscriptions(&subs_path, &[])?;
+        return Ok(vec![]);
     }
 
     let content = fs::read_to_string(&subs_path).context("Failed to read subscriptions.json")?;
@@ -235,14 +402,33 @@ pub fn load_subscriptions() -> Result<Vec<Subscription>> {
     Ok(list.subscriptions)
 }
 
-/// Save subscriptions list
-pub fn save_subscriptions(subscriptions: &[Subscription]) -> Result<()> {
-    let subs_path = get_subscriptions_path()?;
+fn write_subscriptions(path: &Path, subscriptions: &[Subscription]) -> Result<()> {
     let list = SubscriptionList {
         subscriptions: subscriptions.to_vec(),
     };
     let content =
         serde_json::to_string_pretty(&list).context("Failed to serialize subscriptions")?;
-    fs::write(&subs_path, content).context("Failed to write subscriptions.json")?;
-    Ok(())
+    write_atomic(path, content).context("Failed to write subscriptions.json")
+}
+
+/// Save subscriptions list, replacing it as a whole.
+/// To change entries, use `modify_subscriptions` so concurrent changes aren't lost.
+pub fn save_subscriptions(subscriptions: &[Subscription]) -> Result<()> {
+    let subs_path = get_subscriptions_path()?;
+    let _lock = lock_file(&subs_path)?;
+    write_subscriptions(&subs_path, subscriptions)
+}
+
+/// Load, change and save subscriptions.json while holding its lock. Nothing is written
+/// if `f` leaves the list unchanged or fails.
+pub fn modify_subscriptions<T>(f: impl FnOnce(&mut Vec<Subscription>) -> Result<T>) -> Result<T> {
+    let subs_path = get_subscriptions_path()?;
+    let _lock = lock_file(&subs_path)?;
+    let mut subs = load_subscriptions()?;
+    let before = serde_json::to_value(&subs)?;
+    let result = f(&mut subs)?;
+    if serde_json::to_value(&subs)? != before {
+        write_subscriptions(&subs_path, &subs)?;
+    }
+    Ok(result)
 }
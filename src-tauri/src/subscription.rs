@@ -1,8 +1,189 @@
+use crate::events::{self, DaemonEvent};
 use crate::types::Subscription;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine as _};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 
+/// Fields of a subscription that can be changed; `None` keeps the current value
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SubscriptionPatch {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// Find a subscription by name, ID or index
+pub fn find_subscription(subs: &[Subscription], key: &str) -> Option<usize> {
+    subs.iter()
+        .position(|s| s.name == key)
+        .or_else(|| subs.iter().position(|s| s.id == key))
+        .or_else(|| key.parse::<usize>().ok().filter(|&i| i < subs.len()))
+}
+
+/// Add a subscription (not downloaded yet)
+pub fn add_subscription(name: String, url: String) -> Result<Subscription> {
+    let sub = Subscription {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        url,
+        enabled: true,
+        last_updated: None,
+        node_count: None,
+        last_error: None,
+    };
+    crate::storage::modify_subscriptions(|subs| {
+        subs.push(sub.clone());
+        Ok(())
+    })?;
+    Ok(sub)
+}
+
+fn apply_patch(sub: &mut Subscription, patch: SubscriptionPatch) {
+    if let Some(name) = patch.name {
+        sub.name = name;
+    }
+    if let Some(url) = patch.url {
+        if url != sub.url {
+            // The cached nodes belong to the old URL
+            sub.last_updated = None;
+            sub.node_count = None;
+            sub.last_error = None;
+        }
+        sub.url = url;
+    }
+    if let Some(enabled) = patch.enabled {
+        sub.enabled = enabled;
+    }
+}
+
+/// Update a subscription by name, ID or index
+pub fn update_subscription(key: &str, patch: SubscriptionPatch) -> Result<Subscription> {
+    crate::storage::modify_subscriptions(|subs| {
+        let idx = find_subscription(subs, key)
+            .with_context(|| format!("Subscription not found: '{}'", key))?;
+
+        let sub = &mut subs[idx];
+        apply_patch(sub, patch);
+        Ok(sub.clone())
+    })
+}
+
+/// Update the subscription at `index` (never matched by name, unlike `update_subscription`)
+pub fn update_subscription_at(index: usize, patch: SubscriptionPatch) -> Result<Subscription> {
+    crate::storage::modify_subscriptions(|subs| {
+        let sub = subs
+            .get_mut(index)
+            .with_context(|| format!("Subscription index out of range: {}", index))?;
+        apply_patch(sub, patch);
+        Ok(sub.clone())
+    })
+}
+
+fn remove_cached_nodes(removed: &Subscription) -> Result<()> {
+    let cache_path = crate::storage::get_subscription_cache_path(&removed.id)?;
+    if cache_path.exists() {
+        std::fs::remove_file(&cache_path).context("Failed to remove cache file")?;
+    }
+    Ok(())
+}
+
+/// Remove a subscription by name, ID or index, along with its cached nodes
+pub fn remove_subscription(key: &str) -> Result<Subscription> {
+    let removed = crate::storage::modify_subscriptions(|subs| {
+        let idx = find_subscription(subs, key)
+            .with_context(|| format!("Subscription not found: '{}'", key))?;
+        Ok(subs.remove(idx))
+    })?;
+
+    remove_cached_nodes(&removed)?;
+    Ok(removed)
+}
+
+/// Remove the subscription at `index`, along with its cached nodes
+pub fn remove_subscription_at(index: usize) -> Result<Subscription> {
+    let removed = crate::storage::modify_subscriptions(|subs| {
+        if index >= subs.len() {
+            anyhow::bail!("Subscription index out of range: {}", index);
+        }
+        Ok(subs.remove(index))
+    })?;
+
+    remove_cached_nodes(&removed)?;
+    Ok(removed)
+}
+
+/// Download a subscription and record its node count and update time
+pub async fn refresh_subscription(key: &str) -> Result<Subscription> {
+    let subs = crate::storage::load_subscriptions()?;
+    let idx = find_subscription(&subs, key)
+        .with_context(|| format!("Subscription not found: '{}'", key))?;
+    let sub = subs[idx].clone();
+
+    events::publish(DaemonEvent::RefreshStarted {
+        subscription: sub.name.clone(),
+    });
+    let result = download_subscription(&sub)
+        .await
+        .context("Failed to download subscription")
+        .and_then(|_| {
+            crate::proxy::parse_cached_subscription(&sub).context("Failed to parse subscription")
+        });
+    crate::metrics::record_download(&sub.name, result.is_ok());
+    events::publish(DaemonEvent::RefreshFinished {
+        subscription: sub.name.clone(),
+        node_count: result.as_ref().ok().map(|proxies| proxies.len()),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+    });
+
+    // Reload: the list may have changed during the download
+    let updated = crate::storage::modify_subscriptions(|subs| {
+        let stored = subs
+            .iter_mut()
+            .find(|s| s.id == sub.id)
+            .with_context(|| format!("Subscription not found: '{}'", key))?;
+        match &result {
+            Ok(proxies) => {
+                stored.node_count = Some(proxies.len());
+                stored.last_updated =
+                    Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+                stored.last_error = None;
+            }
+            Err(e) => stored.last_error = Some(format!("{:#}", e)),
+        }
+        Ok(stored.clone())
+    })?;
+    result.map(|_| updated)
+}
+
+/// Download a subscription into the proxies cache. `file://` URLs (local subscriptions)
+/// are read from disk, but only from ~/.hangar/local/.
 pub async fn download_subscription(sub: &Subscription) -> Result<std::path::PathBuf> {
+    if sub.url.starts_with("file://") {
+        let path = reqwest::Url::parse(&sub.url)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .with_context(|| format!("Invalid file URL: {}", sub.url))?;
+        // Canonicalized so `..` and symlinks can't lead out of the directory
+        let local_dir = crate::storage::get_local_proxies_dir()?.canonicalize()?;
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        if !path.starts_with(&local_dir) {
+            anyhow::bail!(
+                "Local subscriptions must be stored in {:?}: {:?}",
+                local_dir,
+                path
+            );
+        }
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        return crate::storage::save_proxies_cache(&sub.id, &content);
+    }
+
     let client = reqwest::Client::new();
     let response = client
         .get(&sub.url)
@@ -23,6 +204,32 @@ pub async fn download_subscription(sub: &Subscription) -> Result<std::path::Path
     crate::storage::save_proxies_cache(&sub.id, &decoded_content)
 }
 
+/// Redact a subscription URL to its scheme and host plus a hash, since the path or
+/// query usually carries an access token
+pub fn redact_url(url: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let origin = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| {
+            u.host_str()
+                .map(|host| format!("{}://{}", u.scheme(), host))
+        })
+        .unwrap_or_else(|| "redacted".to_string());
+    format!("{}/…#sha256={}", origin, &hash[..12])
+}
+
+lazy_static! {
+    static ref URL_PATTERN: Regex =
+        Regex::new(r#"[a-zA-Z][a-zA-Z0-9+.-]*://[^\s"'<>()]+"#).unwrap();
+}
+
+/// Redact every URL in free text, such as a download error
+pub fn redact_urls(text: &str) -> String {
+    URL_PATTERN
+        .replace_all(text, |caps: &regex::Captures| redact_url(&caps[0]))
+        .into_owned()
+}
+
 /// Count proxies in a subscription's cached YAML file
 pub fn count_proxies(subscription_id: &str) -> Result<usize> {
     let cache_path = crate::storage::get_subscription_cache_path(subscription_id)?;
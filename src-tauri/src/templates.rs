@@ -0,0 +1,302 @@
+use crate::types::{ProxyGroup, TemplateParams, TemplateSelection};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+const BASIC_TEMPLATE: &str = include_str!("../resources/basic.yml");
+const GROUPS_TEMPLATE: &str = include_str!("../resources/groups.yml");
+const MINIMAL_TEMPLATE: &str = include_str!("../resources/templates/minimal.yml");
+const MINIMAL_GROUPS_TEMPLATE: &str = include_str!("../resources/templates/minimal-groups.yml");
+
+/// Group names used by the English template
+const ENGLISH_GROUP_NAMES: &[(&str, &str)] = &[
+    ("节点选择", "Proxy"),
+    ("自动选择", "Auto"),
+    ("微软服务", "Microsoft"),
+    ("苹果服务", "Apple"),
+    ("谷歌服务", "Google"),
+    ("哔哩哔哩", "Bilibili"),
+    ("抖音", "Douyin"),
+    ("磁力下载", "P2P"),
+    ("AI-专用", "AI"),
+    ("漏网之鱼", "Final"),
+];
+
+/// A selectable base-config template
+#[derive(Debug, Clone, Serialize)]
+pub struct BaseTemplate {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// All built-in base-config templates
+pub fn list_templates() -> Vec<BaseTemplate> {
+    vec![
+        BaseTemplate {
+            name: "fake-ip",
+            description: "Default: fake-ip DNS, full rule set, Chinese group names",
+        },
+        BaseTemplate {
+            name: "redir-host",
+            description: "Full rule set with redir-host DNS (real IPs, no fake-ip range)",
+        },
+        BaseTemplate {
+            name: "tun",
+            description: "Full rule set with TUN mode enabled (system-wide capture)",
+        },
+        BaseTemplate {
+            name: "router",
+            description:
+                "Gateway for a LAN: TUN, auto-redirect, DNS on :53, LAN-reachable controller",
+        },
+        BaseTemplate {
+            name: "english",
+            description: "Full rule set with English group names",
+        },
+        BaseTemplate {
+            name: "minimal",
+            description: "Minimal config: LAN/CN direct, everything else proxied",
+        },
+    ]
+}
+
+/// Rendered basic.yml and groups.yml content
+#[derive(Debug, Clone)]
+pub struct RenderedTemplate {
+    pub basic: String,
+    pub groups: String,
+}
+
+/// Render a template with its parameters into basic.yml and groups.yml content
+pub fn render(selection: &TemplateSelection) -> Result<RenderedTemplate> {
+    let name = selection.name.as_str();
+    if !list_templates().iter().any(|t| t.name == name) {
+        anyhow::bail!("Unknown template '{}'", name);
+    }
+
+    let (basic_src, groups_src) = if name == "minimal" {
+        (MINIMAL_TEMPLATE, MINIMAL_GROUPS_TEMPLATE)
+    } else {
+        (BASIC_TEMPLATE, GROUPS_TEMPLATE)
+    };
+
+    // The rules section is kept as text so its comments survive
+    let (head_src, rules_src) = split_rules_section(basic_src);
+    let original: Mapping =
+        serde_yaml::from_str(head_src).context("Failed to parse template base config")?;
+
+    let mut head = original.clone();
+    let router = name == "router";
+    apply_params(&mut head, &selection.params, router);
+    match name {
+        "redir-host" => use_redir_host(&mut head),
+        "tun" => enable_tun(&mut head, false),
+        "router" => {
+            enable_tun(&mut head, true);
+            set_nested(&mut head, "dns", "listen", Value::from("0.0.0.0:53"));
+            head.insert(Value::from("bind-address"), Value::from("*"));
+        }
+        _ => {}
+    }
+
+    let english = name == "english";
+    let targets: IndexMap<String, String> = ENGLISH_GROUP_NAMES
+        .iter()
+        .map(|(cn, en)| (cn.to_string(), en.to_string()))
+        .collect();
+
+    let basic = if head == original && !english {
+        basic_src.to_string()
+    } else {
+        let head_yaml =
+            serde_yaml::to_string(&head).context("Failed to serialize template base config")?;
+        let rules = if english {
+            retarget_rules_section(rules_src, &targets)
+        } else {
+            rules_src.to_string()
+        };
+        format!("{}{}", head_yaml, rules)
+    };
+
+    let groups = if english {
+        rename_groups(groups_src, &targets)?
+    } else {
+        groups_src.to_string()
+    };
+
+    Ok(RenderedTemplate { basic, groups })
+}
+
+/// Split a base config into everything before `rules:` and the rules section itself
+fn split_rules_section(content: &str) -> (&str, &str) {
+    if content.starts_with("rules:") {
+        return ("", content);
+    }
+    match content.find("\nrules:") {
+        Some(idx) => content.split_at(idx + 1),
+        None => (content, ""),
+    }
+}
+
+fn apply_params(head: &mut Mapping, params: &TemplateParams, lan_controller: bool) {
+    head.insert(Value::from("mixed-port"), Value::from(params.mixed_port));
+    head.insert(
+        Value::from("allow-lan"),
+        Value::from(params.allow_lan || lan_controller),
+    );
+
+    let controller_host = if lan_controller {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    };
+    head.insert(
+        Value::from("external-controller"),
+        Value::from(format!("{}:{}", controller_host, params.controller_port)),
+    );
+
+    match params.secret.as_deref() {
+        Some(secret) if !secret.is_empty() => {
+            head.insert(Value::from("secret"), Value::from(secret));
+        }
+        _ => {
+            head.remove("secret");
+        }
+    }
+}
+
+fn use_redir_host(head: &mut Mapping) {
+    set_nested(head, "dns", "enhanced-mode", Value::from("redir-host"));
+    if let Some(Value::Mapping(dns)) = head.get_mut("dns") {
+        dns.remove("fake-ip-range");
+        dns.remove("fake-ip-filter");
+    }
+}
+
+fn enable_tun(head: &mut Mapping, auto_redirect: bool) {
+    let mut tun = Mapping::new();
+    tun.insert(Value::from("enable"), Value::from(true));
+    tun.insert(Value::from("stack"), Value::from("mixed"));
+    tun.insert(Value::from("auto-route"), Value::from(true));
+    tun.insert(Value::from("auto-detect-interface"), Value::from(true));
+    if auto_redirect {
+        tun.insert(Value::from("auto-redirect"), Value::from(true));
+    }
+    tun.insert(
+        Value::from("dns-hijack"),
+        Value::Sequence(vec![Value::from("any:53")]),
+    );
+    head.insert(Value::from("tun"), Value::Mapping(tun));
+}
+
+fn set_nested(head: &mut Mapping, section: &str, key: &str, value: Value) {
+    let entry = head
+        .entry(Value::from(section))
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if let Value::Mapping(map) = entry {
+        map.insert(Value::from(key), value);
+    }
+}
+
+/// Rewrite rule targets line by line, keeping comments and layout
+fn retarget_rules_section(rules: &str, targets: &IndexMap<String, String>) -> String {
+    rules
+        .split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim_end_matches('\n');
+            match trimmed.strip_prefix("- ") {
+                Some(rule) => match crate::rules::retarget_rule(rule, targets) {
+                    Some(new_rule) => format!("- {}{}", new_rule, &line[trimmed.len()..]),
+                    None => line.to_string(),
+                },
+                None => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn rename_groups(groups_src: &str, targets: &IndexMap<String, String>) -> Result<String> {
+    let mut groups: Vec<ProxyGroup> =
+        serde_yaml::from_str(groups_src).context("Failed to parse template groups")?;
+
+    let rename = |name: &mut String| {
+        if let Some(new_name) = targets.get(name.as_str()) {
+            *name = new_name.clone();
+        }
+    };
+    for group in &mut groups {
+        rename(&mut group.name);
+        group.proxies.iter_mut().for_each(rename);
+    }
+
+    serde_yaml::to_string(&groups).context("Failed to serialize template groups")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(name: &str) -> TemplateSelection {
+        TemplateSelection {
+            name: name.to_string(),
+            params: TemplateParams::default(),
+        }
+    }
+
+    #[test]
+    fn test_default_template_is_bundled_config() {
+        let rendered = render(&TemplateSelection::default()).unwrap();
+        assert_eq!(rendered.basic, BASIC_TEMPLATE);
+        assert_eq!(rendered.groups, GROUPS_TEMPLATE);
+    }
+
+    #[test]
+    fn test_template_params() {
+        let mut selection = select("fake-ip");
+        selection.params = TemplateParams {
+            mixed_port: 7891,
+            controller_port: 9091,
+            secret: Some("s3cret".to_string()),
+            allow_lan: false,
+        };
+        let rendered = render(&selection).unwrap();
+        let config: crate::types::ClashConfig = serde_yaml::from_str(&rendered.basic).unwrap();
+        assert_eq!(config.base_config["mixed-port"], Value::from(7891));
+        assert_eq!(config.base_config["allow-lan"], Value::from(false));
+        assert_eq!(
+            config.base_config["external-controller"],
+            Value::from("127.0.0.1:9091")
+        );
+        assert_eq!(config.base_config["secret"], Value::from("s3cret"));
+        assert!(!config.rules.is_empty());
+    }
+
+    #[test]
+    fn test_template_variants() {
+        let redir: crate::types::ClashConfig =
+            serde_yaml::from_str(&render(&select("redir-host")).unwrap().basic).unwrap();
+        let dns = &redir.base_config["dns"];
+        assert_eq!(dns["enhanced-mode"], Value::from("redir-host"));
+        assert!(dns.get("fake-ip-range").is_none());
+
+        let router: crate::types::ClashConfig =
+            serde_yaml::from_str(&render(&select("router")).unwrap().basic).unwrap();
+        assert_eq!(router.base_config["tun"]["enable"], Value::from(true));
+        assert_eq!(router.base_config["allow-lan"], Value::from(true));
+
+        assert!(render(&select("unknown")).is_err());
+    }
+
+    #[test]
+    fn test_english_template_renames_groups_and_rules() {
+        let rendered = render(&select("english")).unwrap();
+        let groups: Vec<ProxyGroup> = serde_yaml::from_str(&rendered.groups).unwrap();
+        assert!(groups.iter().any(|g| g.name == "Proxy"));
+        assert!(!groups.iter().any(|g| g.name == "节点选择"));
+
+        let config: crate::types::ClashConfig = serde_yaml::from_str(&rendered.basic).unwrap();
+        assert!(config.rules.iter().any(|r| r == "MATCH,Final"));
+        assert!(!config.rules.iter().any(|r| r.ends_with(",谷歌服务")));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::types::TlsConfig;
+use anyhow::{Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    KeyUsagePurpose,
+};
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+const CA_COMMON_NAME: &str = "Hangar Local CA";
+
+/// Leaf validity in days (825 is the longest Apple platforms accept)
+const LEAF_VALIDITY_DAYS: i64 = 825;
+
+/// Files written by `hangar cert self-signed`
+#[derive(Debug, Clone)]
+pub struct SelfSignedFiles {
+    pub ca_cert: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Whether an existing CA was reused (devices trusting it keep working)
+    pub reused_ca: bool,
+}
+
+/// Make sure rustls has a crypto provider (ring) installed for this process
+pub fn install_crypto_provider() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+}
+
+/// Load the cert/key pair, failing early with a readable error
+pub async fn load_rustls_config(tls: &TlsConfig) -> Result<RustlsConfig> {
+    install_crypto_provider();
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS cert {} / key {}",
+                tls.cert_path, tls.key_path
+            )
+        })
+}
+
+/// Reload the cert/key pair whenever either file changes.
+/// A failed reload keeps serving the previous certificate.
+pub fn watch_and_reload(config: RustlsConfig, tls: TlsConfig) {
+    let runtime = tokio::runtime::Handle::current();
+
+    std::thread::spawn(move || {
+        use notify::{RecursiveMode, Watcher};
+        use std::time::{Duration, Instant};
+
+        let cert = PathBuf::from(&tls.cert_path);
+        let key = PathBuf::from(&tls.key_path);
+
+        let (tx, rx) = std::sync::mpsc::channel::<notify::Result<notify::Event>>();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(w) => w,
+            Err(e) => {
+                warn!("⚠️ TLS 证书热加载不可用: {}", e);
+                return;
+            }
+        };
+
+        // Watch the directories: renewals often replace the files instead of writing them
+        let mut dirs: Vec<&Path> = [&cert, &key].iter().filter_map(|p| p.parent()).collect();
+        dirs.dedup();
+        for dir in dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                warn!("⚠️ 无法监听 {:?}: {}", dir, e);
+            }
+        }
+
+        let debounce = Duration::from_millis(500);
+        let mut last_reload = Instant::now() - debounce;
+
+        for res in rx {
+            let Ok(event) = res else { continue };
+            let touches_pair = event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == cert.file_name() || p.file_name() == key.file_name());
+            if !touches_pair || event.kind.is_access() || last_reload.elapsed() < debounce {
+                continue;
+            }
+
+            // Let the writer finish both files
+            std::thread::sleep(debounce);
+            last_reload = Instant::now();
+
+            let config = config.clone();
+            let (cert, key) = (cert.clone(), key.clone());
+            runtime.spawn(async move {
+                match config.reload_from_pem_file(&cert, &key).await {
+                    Ok(_) => info!("🔐 TLS 证书已重新加载"),
+                    Err(e) => error!("❌ TLS 证书重新加载失败，继续使用旧证书: {}", e),
+                }
+            });
+        }
+    });
+}
+
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, CA_COMMON_NAME);
+    params
+        .distinguished_name
+        .push(DnType::OrganizationName, "Hangar");
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params
+}
+
+/// Valid from yesterday (clock skew) until `days` from now
+fn set_validity(params: &mut CertificateParams, days: i64) {
+    use chrono::Datelike;
+    let date = |offset: i64| {
+        let day = chrono::Utc::now().date_naive() + chrono::Duration::days(offset);
+        rcgen::date_time_ymd(day.year(), day.month() as u8, day.day() as u8)
+    };
+    params.not_before = date(-1);
+    params.not_after = date(days);
+}
+
+/// Default names for the leaf certificate: localhost plus this machine's LAN address
+pub fn default_hosts() -> Vec<String> {
+    let mut hosts = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+
+    // Connecting a UDP socket sends nothing but reveals the outbound interface address
+    let lan_ip = std::net::UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| s.connect("8.8.8.8:80").map(|_| s))
+        .and_then(|s| s.local_addr());
+    if let Ok(addr) = lan_ip {
+        if !addr.ip().is_unspecified() && !addr.ip().is_loopback() {
+            hosts.push(addr.ip().to_string());
+        }
+    }
+
+    hosts
+}
+
+/// Write a local CA (reused when present) and a leaf cert for `hosts` into `dir`
+pub fn generate_self_signed(dir: &Path, hosts: &[String]) -> Result<SelfSignedFiles> {
+    std::fs::create_dir_all(dir).context("Failed to create TLS directory")?;
+
+    let ca_cert_path = dir.join("ca.pem");
+    let ca_key_path = dir.join("ca-key.pem");
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+
+    let reused_ca = ca_cert_path.exists() && ca_key_path.exists();
+    let ca_key = if reused_ca {
+        let pem = std::fs::read_to_string(&ca_key_path).context("Failed to read CA key")?;
+        KeyPair::from_pem(&pem).context("Failed to parse CA key")?
+    } else {
+        KeyPair::generate().context("Failed to generate CA key")?
+    };
+
+    // Re-signing the same name with the same key yields an equivalent issuer
+    let mut params = ca_params();
+    set_validity(&mut params, 3650);
+    let ca_cert = params
+        .self_signed(&ca_key)
+        .context("Failed to create CA certificate")?;
+
+    let mut leaf_params =
+        CertificateParams::new(hosts.to_vec()).context("Invalid host name for certificate")?;
+    leaf_params.distinguished_name = rcgen::DistinguishedName::new();
+    leaf_params.distinguished_name.push(
+        DnType::CommonName,
+        hosts.first().cloned().unwrap_or_default(),
+    );
+    leaf_params.is_ca = IsCa::ExplicitNoCa;
+    leaf_params.key_usages = vec![
+        KeyUsagePurpose::DigitalSignature,
+        KeyUsagePurpose::KeyEncipherment,
+    ];
+    leaf_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    leaf_params.use_authority_key_identifier_extension = true;
+    set_validity(&mut leaf_params, LEAF_VALIDITY_DAYS);
+
+    let leaf_key = KeyPair::generate().context("Failed to generate key")?;
+    let leaf_cert = leaf_params
+        .signed_by(&leaf_key, &ca_cert, &ca_key)
+        .context("Failed to sign certificate")?;
+
+    if !reused_ca {
+        std::fs::write(&ca_cert_path, ca_cert.pem())?;
+        write_private(&ca_key_path, &ca_key.serialize_pem())?;
+    }
+    // Key first, so a hot reload triggered by the cert finds the matching key
+    write_private(&key_path, &leaf_key.serialize_pem())?;
+    std::fs::write(&cert_path, leaf_cert.pem())?;
+
+    Ok(SelfSignedFiles {
+        ca_cert: ca_cert_path,
+        cert: cert_path,
+        key: key_path,
+        reused_ca,
+    })
+}
+
+/// Write a private key readable only by the current user
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    // `mode` only applies to new files; tighten an existing key before writing into it
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_signed_reuses_ca() {
+        let dir = tempfile::tempdir().unwrap();
+        let hosts = vec!["localhost".to_string(), "192.168.1.2".to_string()];
+
+        let first = generate_self_signed(dir.path(), &hosts).unwrap();
+        assert!(!first.reused_ca);
+        let ca = std::fs::read_to_string(&first.ca_cert).unwrap();
+        let leaf = std::fs::read_to_string(&first.cert).unwrap();
+
+        let second = generate_self_signed(dir.path(), &hosts).unwrap();
+        assert!(second.reused_ca);
+        assert_eq!(std::fs::read_to_string(&second.ca_cert).unwrap(), ca);
+        assert_ne!(std::fs::read_to_string(&second.cert).unwrap(), leaf);
+    }
+}
@@ -12,6 +12,9 @@ pub struct Subscription {
     pub last_updated: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub node_count: Option<usize>,
+    /// Error of the last failed download, cleared by the next successful one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -112,10 +115,54 @@ impl Default for LlmConfig {
     }
 }
 
+/// Access token for the config server. Only the SHA-256 hash of the secret is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: String,
+    pub name: String,
+    pub token_hash: String,
+    /// Profile served to this token ("default" is the full config)
+    #[serde(default = "default_profile_name")]
+    pub profile: String,
+    /// Unix timestamp after which the token is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+    /// Allows the /api management endpoints
+    #[serde(default)]
+    pub admin: bool,
+}
+
+pub fn default_profile_name() -> String {
+    "default".to_string()
+}
+
+/// A named view of the merged config, limited to the nodes of some subscriptions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Subscription names or IDs whose nodes are served (empty means all)
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
     pub host: String,
+    /// When non-empty, /config requires one of these tokens
+    #[serde(default)]
+    pub tokens: Vec<AccessToken>,
+    /// Serve over HTTPS with this cert/key pair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+}
+
+/// PEM cert/key pair for the config server, reloaded when the files change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
 }
 
 impl Default for ServerConfig {
@@ -123,10 +170,154 @@ impl Default for ServerConfig {
         Self {
             port: 8080,
             host: "127.0.0.1".to_string(),
+            tokens: vec![],
+            tls: None,
+        }
+    }
+}
+
+/// Server log settings; `RUST_LOG` overrides `level`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogConfig {
+    /// error, warn, info, debug or trace
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Rotate `hangar.log` once it grows past this many megabytes (it also rotates daily)
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files kept next to `hangar.log`
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+/// Version history retention. Manual snapshots are never pruned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryConfig {
+    /// Snapshot current.yaml whenever a merge changes it
+    #[serde(default = "default_true")]
+    pub snapshot_on_merge: bool,
+    /// Versions of each target always kept, newest first
+    #[serde(default = "default_keep_last")]
+    pub keep_last: usize,
+    /// Also keep the newest version of each target per day for this many days
+    #[serde(default = "default_keep_daily_days")]
+    pub keep_daily_days: u32,
+    /// Also commit the config files to the git repository in ~/.hangar/history
+    #[serde(default)]
+    pub git: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_keep_last() -> usize {
+    20
+}
+
+fn default_keep_daily_days() -> u32 {
+    14
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_on_merge: true,
+            keep_last: default_keep_last(),
+            keep_daily_days: default_keep_daily_days(),
+            git: false,
+        }
+    }
+}
+
+/// Parameters substituted into a base-config template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateParams {
+    pub mixed_port: u16,
+    pub controller_port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub allow_lan: bool,
+}
+
+impl Default for TemplateParams {
+    fn default() -> Self {
+        Self {
+            mixed_port: 7890,
+            controller_port: 9090,
+            secret: None,
+            allow_lan: true,
+        }
+    }
+}
+
+/// The base-config template chosen with `hangar init`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateSelection {
+    pub name: String,
+    #[serde(default)]
+    pub params: TemplateParams,
+}
+
+impl Default for TemplateSelection {
+    fn default() -> Self {
+        Self {
+            name: "fake-ip".to_string(),
+            params: TemplateParams::default(),
         }
     }
 }
 
+/// How a chain dials its landing nodes through the front
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChainMode {
+    /// Set `dialer-proxy` on copies of the landing nodes (mihomo)
+    #[default]
+    DialerProxy,
+    /// Generate `relay` groups (older cores)
+    Relay,
+}
+
+/// A chained proxy: traffic exits through the landing node(s), dialed via `front`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyChain {
+    /// Name of the generated proxy group (use it as a rule target)
+    pub name: String,
+    /// Group or node the landing nodes are dialed through, e.g. "HK-地区"
+    pub front: String,
+    /// Landing subscription (name or ID); it may be disabled to keep its nodes out of other groups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub landing_subscription: Option<String>,
+    /// Inline landing node, e.g. a corporate exit node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub landing_proxy: Option<ProxyNode>,
+    #[serde(default)]
+    pub mode: ChainMode,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HangarConfig {
     #[serde(default)]
@@ -135,6 +326,16 @@ pub struct HangarConfig {
     pub server: ServerConfig,
     #[serde(default)]
     pub rule_sources: Vec<String>,
+    #[serde(default)]
+    pub template: TemplateSelection,
+    #[serde(default)]
+    pub chains: Vec<ProxyChain>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
 }
 
 impl Default for HangarConfig {
@@ -143,6 +344,11 @@ impl Default for HangarConfig {
             llm: LlmConfig::default(),
             server: ServerConfig::default(),
             rule_sources: vec![],
+            template: TemplateSelection::default(),
+            chains: vec![],
+            profiles: vec![],
+            log: LogConfig::default(),
+            history: HistoryConfig::default(),
         }
     }
 }
@@ -152,11 +358,58 @@ pub struct SubscriptionList {
     pub subscriptions: Vec<Subscription>,
 }
 
+/// What created a version snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    #[default]
+    Manual,
+    Ai,
+    Merge,
+    Rollback,
+    /// `hangar init` or `force-update-config` replacing a file with the bundled template
+    Template,
+}
+
+impl std::fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Manual => "manual",
+            Self::Ai => "ai",
+            Self::Merge => "merge",
+            Self::Rollback => "rollback",
+            Self::Template => "template",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A snapshot of one config file, recorded in `versions/index.json`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigVersion {
     pub id: String,
+    /// Sequence number, usable as the alias `v{N}`
+    pub number: usize,
     pub timestamp: i64,
     pub description: String,
+    /// File the snapshot was taken of: current, basic, groups or local
+    pub target: String,
+    pub source: VersionSource,
+    /// Previous version of the same target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// SHA-256 of the snapshot content
+    pub content_hash: String,
+    /// File name in the versions directory
+    pub file: String,
+    /// Names usable instead of the ID; tagged versions are never pruned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Free-text annotation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Absolute path of `file`, filled in when the index is loaded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub file_path: String,
 }
 
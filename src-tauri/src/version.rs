@@ -1,172 +1,449 @@
+use crate::config_diff::{self, SemanticDiff};
 use crate::storage;
-use crate::types::ConfigVersion;
+use crate::types::{ConfigVersion, HistoryConfig, VersionSource};
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-/// Helper to determine the next version number
-fn get_next_version_number() -> Result<usize> {
-    let versions = list_versions()?;
-    let max_ver = versions
-        .iter()
-        .filter_map(|v| {
-            // Try to extract number from id "v{N}_..."
-            let parts: Vec<&str> = v.id.split('_').collect();
-            if parts.len() > 1 && parts[0].starts_with('v') {
-                parts[0][1..].parse::<usize>().ok()
+/// `versions/index.json`: metadata of every snapshot, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VersionIndex {
+    /// Numbers are never reused, even after the newest version is deleted
+    pub next_number: usize,
+    pub versions: Vec<ConfigVersion>,
+}
+
+impl VersionIndex {
+    fn find(&self, id: &str) -> Option<&ConfigVersion> {
+        self.versions.iter().find(|v| v.id == id)
+    }
+
+    /// Find a version by ID, tag or `v{N}` alias
+    fn resolve(&self, alias: &str) -> Option<&ConfigVersion> {
+        self.find(alias)
+            .or_else(|| {
+                self.versions
+                    .iter()
+                    .find(|v| v.tags.iter().any(|t| t == alias))
+            })
+            .or_else(|| {
+                let number = parse_alias_number(alias)?;
+                self.versions.iter().find(|v| v.number == number)
+            })
+    }
+
+    fn latest_for(&self, target: &str) -> Option<&ConfigVersion> {
+        self.versions
+            .iter()
+            .filter(|v| v.target == target)
+            .max_by_key(|v| (v.timestamp, v.number))
+    }
+}
+
+/// Number of a `v{N}` alias
+fn parse_alias_number(alias: &str) -> Option<usize> {
+    alias.strip_prefix('v')?.parse().ok()
+}
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Load the index, building it from the version file names on first use
+pub fn load_index() -> Result<VersionIndex> {
+    let index = match read_index()? {
+        Some(index) => index,
+        None => {
+            let _lock = lock_index()?;
+            load_or_rebuild_index()?
+        }
+    };
+    with_file_paths(index)
+}
+
+/// The stored index, or None if it is missing or can't be parsed
+fn read_index() -> Result<Option<VersionIndex>> {
+    let path = storage::get_versions_index_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).context("Failed to read versions index")?;
+    Ok(serde_json::from_str(&content).ok())
+}
+
+/// The stored index, rebuilt from the version files if it is missing or corrupt.
+/// The caller holds the index lock.
+fn load_or_rebuild_index() -> Result<VersionIndex> {
+    if let Some(index) = read_index()? {
+        return Ok(index);
+    }
+
+    let path = storage::get_versions_index_path()?;
+    if path.exists() {
+        let corrupt = path.with_extension(format!("json.corrupt-{}", Utc::now().timestamp()));
+        tracing::warn!(
+            "⚠️ Versions index is corrupt, moved it to {:?} and rebuilding it",
+            corrupt
+        );
+        fs::rename(&path, &corrupt).context("Failed to move the corrupt versions index")?;
+    }
+
+    let index = migrate_from_filenames(&storage::get_versions_dir()?)?;
+    write_index(&index)?;
+    Ok(index)
+}
+
+fn with_file_paths(mut index: VersionIndex) -> Result<VersionIndex> {
+    let versions_dir = storage::get_versions_dir()?;
+    for version in &mut index.versions {
+        version.file_path = versions_dir
+            .join(&version.file)
+            .to_string_lossy()
+            .to_string();
+    }
+    Ok(index)
+}
+
+fn write_index(index: &VersionIndex) -> Result<()> {
+    // Paths are derived on load, so the versions directory can move
+    let mut index = index.clone();
+    for version in &mut index.versions {
+        version.file_path.clear();
+    }
+    let content = serde_json::to_string_pretty(&index)?;
+    storage::write_atomic(&storage::get_versions_index_path()?, content)
+        .context("Failed to write versions index")
+}
+
+fn lock_index() -> Result<storage::FileLock> {
+    storage::lock_file(&storage::get_versions_index_path()?)
+}
+
+/// Load, change and save the index while holding its lock, so concurrent snapshots
+/// neither reuse a number nor drop each other's entries
+fn modify_index<T>(f: impl FnOnce(&mut VersionIndex) -> Result<T>) -> Result<T> {
+    let _lock = lock_index()?;
+    let mut index = with_file_paths(load_or_rebuild_index()?)?;
+    let result = f(&mut index)?;
+    write_index(&index)?;
+    Ok(result)
+}
+
+/// Fields encoded in a pre-index version file name
+#[derive(Debug, PartialEq)]
+struct LegacyName {
+    number: Option<usize>,
+    file_type: String,
+    timestamp: i64,
+    description: String,
+}
+
+/// Parse `v{N}_{type}_{timestamp}_{desc}`, or the older `v_{type}_{timestamp}_{desc}`
+/// and `v_{timestamp}_{desc}`
+fn parse_legacy_name(file_name: &str) -> Option<LegacyName> {
+    let stem = file_name
+        .strip_suffix(".yaml")
+        .or_else(|| file_name.strip_suffix(".yml"))?;
+    let parts: Vec<&str> = stem.split('_').collect();
+    let prefix = parts.first()?.strip_prefix('v')?;
+
+    if !prefix.is_empty() {
+        let number = prefix.parse::<usize>().ok()?;
+        let timestamp = parts.get(2)?.parse::<i64>().ok()?;
+        return Some(LegacyName {
+            number: Some(number),
+            file_type: parts[1].to_string(),
+            timestamp,
+            description: parts.get(3..).unwrap_or_default().join("_"),
+        });
+    }
+
+    if parts.len() >= 4 {
+        if let Ok(timestamp) = parts[2].parse::<i64>() {
+            return Some(LegacyName {
+                number: None,
+                file_type: parts[1].to_string(),
+                timestamp,
+                description: parts[3..].join("_"),
+            });
+        }
+    }
+    let timestamp = parts.get(1)?.parse::<i64>().ok()?;
+    Some(LegacyName {
+        number: None,
+        file_type: "legacy".to_string(),
+        timestamp,
+        description: parts.get(2..).unwrap_or_default().join("_"),
+    })
+}
+
+/// Number of a version file named by the index (`v{N}.yaml`)
+fn parse_indexed_name(file_name: &str) -> Option<usize> {
+    file_name
+        .strip_suffix(".yaml")?
+        .strip_prefix('v')?
+        .parse()
+        .ok()
+}
+
+/// Target file of a snapshot whose metadata was lost, guessed from its content
+fn guess_target(content: &str) -> &'static str {
+    match serde_yaml::from_str::<serde_yaml::Value>(content) {
+        Ok(serde_yaml::Value::Sequence(_)) => "groups",
+        Ok(serde_yaml::Value::Mapping(map)) => {
+            let has = |key: &str| map.contains_key(serde_yaml::Value::from(key));
+            if has("prepend") || has("append") || has("overrides") {
+                "local"
+            } else if map
+                .get(serde_yaml::Value::from("proxies"))
+                .and_then(|p| p.as_sequence())
+                .is_some_and(|p| !p.is_empty())
+            {
+                // basic.yml carries no nodes, merged configs do
+                "current"
             } else {
-                None
+                "basic"
             }
-        })
-        .max()
-        .unwrap_or(0);
+        }
+        _ => "current",
+    }
+}
+
+/// Target file and source of a pre-index version, from its type and the description
+/// each caller used to pass
+fn classify_legacy(name: &LegacyName) -> (String, VersionSource) {
+    let source = match name.description.as_str() {
+        d if d.starts_with("auto_before_rollback") => VersionSource::Rollback,
+        d if d.starts_with("auto_before_ai_patch") => VersionSource::Ai,
+        d if d.starts_with("auto_before_init") || d.starts_with("auto_before_force_update") => {
+            VersionSource::Template
+        }
+        _ => match name.file_type.as_str() {
+            "manual" | "legacy" => VersionSource::Manual,
+            "backup" => VersionSource::Rollback,
+            // AI edits were saved under their target, with the prompt as description
+            _ => VersionSource::Ai,
+        },
+    };
+    let target = match name.file_type.as_str() {
+        "basic" | "groups" | "local" => name.file_type.clone(),
+        _ => "current".to_string(),
+    };
+    (target, source)
+}
+
+/// Build an index from the version files. Files named the pre-index way keep their names
+/// (and IDs); versions without a number are numbered after the others, oldest first.
+/// `v{N}.yaml` files of a lost index are recovered as manual snapshots dated by their
+/// modification time, with the target guessed from their content.
+fn migrate_from_filenames(dir: &Path) -> Result<VersionIndex> {
+    let mut parsed = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if let Some(name) = parse_legacy_name(&file_name) {
+                let content = fs::read_to_string(entry.path()).unwrap_or_default();
+                parsed.push((file_name, name, content));
+            } else if let Some(number) = parse_indexed_name(&file_name) {
+                let content = fs::read_to_string(entry.path()).unwrap_or_default();
+                let timestamp = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .map(|t| DateTime::<Utc>::from(t).timestamp())
+                    .unwrap_or_default();
+                let name = LegacyName {
+                    number: Some(number),
+                    file_type: guess_target(&content).to_string(),
+                    timestamp,
+                    description: "Recovered without index".to_string(),
+                };
+                parsed.push((file_name, name, content));
+            }
+        }
+    }
+    parsed.sort_by_key(|(file_name, name, _)| (name.timestamp, name.number, file_name.clone()));
 
-    Ok(max_ver + 1)
+    let mut next_number = parsed
+        .iter()
+        .filter_map(|(_, name, _)| name.number)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut index = VersionIndex::default();
+    for (file_name, name, content) in parsed {
+        let number = name.number.unwrap_or_else(|| {
+            next_number += 1;
+            next_number - 1
+        });
+        let (id, target, source) = match file_name.strip_suffix(".yaml") {
+            Some(id) if parse_indexed_name(&file_name).is_some() => (
+                id.to_string(),
+                name.file_type.clone(),
+                VersionSource::Manual,
+            ),
+            _ => {
+                let (target, source) = classify_legacy(&name);
+                (file_name.clone(), target, source)
+            }
+        };
+        let parent = index.latest_for(&target).map(|v| v.id.clone());
+        index.versions.push(ConfigVersion {
+            id,
+            number,
+            timestamp: name.timestamp,
+            description: name.description,
+            target,
+            source,
+            parent,
+            content_hash: content_hash(&content),
+            file: file_name,
+            tags: vec![],
+            note: None,
+            file_path: String::new(),
+        });
+    }
+    index.next_number = next_number;
+    Ok(index)
 }
 
-/// Helper to resolve a version ID or alias (v1, v0) to a specific file or content
+/// Resolve a version ID or alias (`v1`, `v0`) to a version ID
 pub fn resolve_version_id(alias: &str) -> Result<String> {
     if alias == "v0" {
         return Ok("v0".to_string());
     }
 
-    // If it looks like a short alias "v1", "v2" etc.
-    if alias.starts_with('v') && alias[1..].chars().all(|c| c.is_digit(10)) {
-        let target_ver = alias[1..].parse::<usize>().unwrap_or(0);
-        let versions = list_versions()?;
-        for v in versions {
-            let parts: Vec<&str> = v.id.split('_').collect();
-            if parts.len() > 1 && parts[0].starts_with('v') {
-                if let Ok(ver) = parts[0][1..].parse::<usize>() {
-                    if ver == target_ver {
-                        return Ok(v.id);
-                    }
-                }
+    load_index()?
+        .resolve(alias)
+        .map(|v| v.id.clone())
+        .with_context(|| format!("Version {} not found", alias))
+}
+
+/// Tags can't look like version IDs or `v{N}` aliases, so resolving stays unambiguous
+fn validate_tag(index: &VersionIndex, tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        anyhow::bail!("Tag '{}' must be non-empty and contain no whitespace", tag);
+    }
+    if parse_alias_number(tag).is_some() || index.find(tag).is_some() {
+        anyhow::bail!("Tag '{}' would shadow a version ID", tag);
+    }
+    Ok(())
+}
+
+/// Tag a version. A tag names a single version; `force` moves it from the one it names.
+pub fn tag_version(id: &str, tag: &str, force: bool) -> Result<ConfigVersion> {
+    modify_index(|index| {
+        validate_tag(index, tag)?;
+        let target_id = index
+            .resolve(id)
+            .map(|v| v.id.clone())
+            .with_context(|| format!("Version {} not found", id))?;
+
+        if let Some(owner) = index
+            .versions
+            .iter_mut()
+            .find(|v| v.id != target_id && v.tags.iter().any(|t| t == tag))
+        {
+            if !force {
+                anyhow::bail!(
+                    "Tag '{}' already names v{}; use --force to move it",
+                    tag,
+                    owner.number
+                );
             }
+            owner.tags.retain(|t| t != tag);
         }
-        return Err(anyhow::anyhow!("Version alias {} not found", alias));
-    }
 
-    // Otherwise assume it's a full ID
-    Ok(alias.to_string())
+        let version = index
+            .versions
+            .iter_mut()
+            .find(|v| v.id == target_id)
+            .context("Version disappeared from the index")?;
+        if !version.tags.iter().any(|t| t == tag) {
+            version.tags.push(tag.to_string());
+        }
+        Ok(version.clone())
+    })
 }
 
-/// Save current config as a new version
-// filename can be used as the "type" indicator prefix
+/// Remove a tag, returning the version it named
+pub fn untag_version(tag: &str) -> Result<ConfigVersion> {
+    modify_index(|index| {
+        let version = index
+            .versions
+            .iter_mut()
+            .find(|v| v.tags.iter().any(|t| t == tag))
+            .with_context(|| format!("Tag '{}' not found", tag))?;
+        version.tags.retain(|t| t != tag);
+        Ok(version.clone())
+    })
+}
+
+/// Set a version's note; None or an empty note clears it
+pub fn set_version_note(id: &str, note: Option<&str>) -> Result<ConfigVersion> {
+    modify_index(|index| {
+        let target_id = index
+            .resolve(id)
+            .map(|v| v.id.clone())
+            .with_context(|| format!("Version {} not found", id))?;
+        let version = index
+            .versions
+            .iter_mut()
+            .find(|v| v.id == target_id)
+            .context("Version disappeared from the index")?;
+        version.note = note
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(str::to_string);
+        Ok(version.clone())
+    })
+}
+
+/// Snapshot `content` of the `target` file (current, basic, groups or local)
 pub fn save_version(
-    file_type: &str,
+    target: &str,
+    source: VersionSource,
     description: &str,
-    config_content: &str,
+    content: &str,
 ) -> Result<ConfigVersion> {
-    let versions_dir = storage::get_versions_dir()?;
-    let timestamp = Utc::now().timestamp();
-    let version_num = get_next_version_number()?;
-
-    // Sanitize description for filename
-    let safe_desc: String = description
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
-        .take(30)
-        .collect();
-
-    // Format: v{N}_{file_type}_{timestamp}_{description}.yaml
-    // e.g. v1_basic_123456789_clean.yaml
-    let filename = format!(
-        "v{}_{}_{}_{}.yaml",
-        version_num, file_type, timestamp, safe_desc
-    );
-    let file_path = versions_dir.join(&filename);
-
-    fs::write(&file_path, config_content).context("Failed to save version file")?;
+    modify_index(|index| {
+        let versions_dir = storage::get_versions_dir()?;
+        let number = index.next_number.max(1);
 
-    let version = ConfigVersion {
-        id: filename.clone(),
-        timestamp,
-        description: format!("[{}] {}", file_type, description),
-        file_path: file_path.to_string_lossy().to_string(),
-    };
+        let id = format!("v{}", number);
+        let file = format!("{}.yaml", id);
+        let file_path = versions_dir.join(&file);
+        storage::write_atomic(&file_path, content).context("Failed to save version file")?;
 
-    Ok(version)
+        let version = ConfigVersion {
+            id,
+            number,
+            timestamp: Utc::now().timestamp(),
+            description: description.to_string(),
+            target: target.to_string(),
+            source,
+            parent: index.latest_for(target).map(|v| v.id.clone()),
+            content_hash: content_hash(content),
+            file,
+            tags: vec![],
+            note: None,
+            file_path: file_path.to_string_lossy().to_string(),
+        };
+        index.versions.push(version.clone());
+        index.next_number = number + 1;
+        Ok(version)
+    })
 }
 
-/// List all saved versions
+/// List all saved versions, newest first
 pub fn list_versions() -> Result<Vec<ConfigVersion>> {
-    let versions_dir = storage::get_versions_dir()?;
-    let mut versions = Vec::new();
-
-    if let Ok(entries) = fs::read_dir(&versions_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path
-                .extension()
-                .map_or(false, |ext| ext == "yaml" || ext == "yml")
-            {
-                if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                    // Parse filename: v{N}_{type}_{timestamp}_{desc}.yaml
-                    if filename.starts_with("v") {
-                        let parts: Vec<&str> = filename
-                            .trim_end_matches(".yaml")
-                            .trim_end_matches(".yml")
-                            .split('_')
-                            .collect();
-
-                        // Try new format: v{N}, type, timestamp, desc...
-                        if parts.len() >= 4 && parts[0][1..].chars().all(|c| c.is_digit(10)) {
-                            if let Ok(timestamp) = parts[2].parse::<i64>() {
-                                let ver_num = parts[0]; // v1
-                                let file_type = parts[1];
-                                let description_part = parts[3..].join("_");
-                                versions.push(ConfigVersion {
-                                    id: filename.to_string(),
-                                    timestamp,
-                                    description: format!(
-                                        "{} [{}] {}",
-                                        ver_num, file_type, description_part
-                                    ),
-                                    file_path: path.to_string_lossy().to_string(),
-                                });
-                                continue;
-                            }
-                        }
-
-                        // Fallback Legacy format support: v_{type}_{timestamp}_{desc} OR v_{timestamp}_{desc}
-                        // We treat legacy "v_" as just ID without version number
-                        if parts[0] == "v" {
-                            // v_basic_123...
-                            if parts.len() >= 4 {
-                                if let Ok(timestamp) = parts[2].parse::<i64>() {
-                                    let file_type = parts[1];
-                                    let description_part = parts[3..].join("_");
-                                    versions.push(ConfigVersion {
-                                        id: filename.to_string(),
-                                        timestamp,
-                                        description: format!(
-                                            "[{}] {}",
-                                            file_type, description_part
-                                        ),
-                                        file_path: path.to_string_lossy().to_string(),
-                                    });
-                                }
-                            } else if parts.len() >= 3 {
-                                // v_timestamp_desc
-                                if let Ok(timestamp) = parts[1].parse::<i64>() {
-                                    let description = parts[2..].join("_");
-                                    versions.push(ConfigVersion {
-                                        id: filename.to_string(),
-                                        timestamp,
-                                        description: format!("[legacy] {}", description),
-                                        file_path: path.to_string_lossy().to_string(),
-                                    });
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    // Sort by timestamp desc
-    versions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    let mut versions = load_index()?.versions;
+    versions.sort_by_key(|v| std::cmp::Reverse((v.timestamp, v.number)));
     Ok(versions)
 }
 
@@ -176,55 +453,354 @@ pub fn get_version_content(id: &str) -> Result<String> {
         return Ok("".to_string());
     }
 
-    let versions_dir = storage::get_versions_dir()?;
-    let file_path = versions_dir.join(id);
-
-    let content = fs::read_to_string(&file_path).context("Failed to read version file")?;
+    let index = load_index()?;
+    let version = index
+        .resolve(id)
+        .with_context(|| format!("Version {} not found", id))?;
+    let content = fs::read_to_string(&version.file_path).context("Failed to read version file")?;
 
     Ok(content)
 }
 
-/// Rollback to a specific version
-pub fn rollback_to_version(id: &str) -> Result<()> {
-    // If id is v0, maybe clear config? For now assume valid file ID or alias resolved outside
-    // But wait, this receives raw ID or alias?
-    // Let's protect against raw alias if not resolved, but caller usually resolves or passes ID.
-    // If we pass "v1" here, it won't work unless we resolve it.
-    // Let's resolve it inside just in case.
+/// Path of the file a version's target names
+pub fn target_path(target: &str) -> Result<PathBuf> {
+    match target {
+        "current" => storage::get_current_config_path(),
+        "basic" => storage::get_basic_config_path(),
+        "groups" => storage::get_groups_config_path(),
+        "local" => storage::get_rules_overlay_path(),
+        other => anyhow::bail!("Unknown version target: '{}'", other),
+    }
+}
+
+/// What a rollback restored
+#[derive(Debug, Clone, Serialize)]
+pub struct RollbackOutcome {
+    pub version: ConfigVersion,
+    pub path: PathBuf,
+    /// current.yaml was regenerated because a merge source (basic, groups, local) was restored
+    pub remerged: bool,
+}
+
+/// Restore a version into the file it was taken from, snapshotting that file first.
+/// Restoring basic.yml, groups.yml or rules.local.yml re-merges current.yaml.
+pub async fn rollback_to_version(id: &str) -> Result<RollbackOutcome> {
     let resolved_id = resolve_version_id(id)?;
 
     if resolved_id == "v0" {
         return Err(anyhow::anyhow!("Cannot rollback to v0 (empty state) yet"));
     }
 
+    let version = load_index()?
+        .find(&resolved_id)
+        .cloned()
+        .with_context(|| format!("Version {} not found", resolved_id))?;
     let content = get_version_content(&resolved_id)?;
-    let current_path = storage::get_current_config_path()?;
+    let path = target_path(&version.target)?;
 
-    // Save current as a backup version first
-    if current_path.exists() {
-        let current_content = fs::read_to_string(&current_path)?;
-        save_version("backup", "auto_backup_before_rollback", &current_content)?;
+    // Save the file being overwritten as a version first
+    if path.exists() {
+        let existing = fs::read_to_string(&path)?;
+        if existing != content {
+            save_version(
+                &version.target,
+                VersionSource::Rollback,
+                &format!("Before rollback to v{}", version.number),
+                &existing,
+            )?;
+        }
     }
 
-    // Write the rollback content
-    fs::write(&current_path, content).context("Failed to write current config")?;
+    storage::write_atomic(&path, content)?;
 
-    Ok(())
+    let remerged = version.target != "current";
+    if remerged {
+        crate::proxy::regenerate_current_config()
+            .await
+            .with_context(|| format!("Restored {:?} but re-merging failed", path))?;
+    }
+    crate::git_history::record(
+        VersionSource::Rollback,
+        &format!("Rollback {} to v{}", version.target, version.number),
+    );
+
+    Ok(RollbackOutcome {
+        version,
+        path,
+        remerged,
+    })
+}
+
+/// Snapshot the current config with a manual description
+pub fn snapshot_current(description: &str) -> Result<ConfigVersion> {
+    let current_path = storage::get_current_config_path()?;
+    let content = fs::read_to_string(&current_path).context("Failed to read current config")?;
+    let version = save_version("current", VersionSource::Manual, description, &content)?;
+    crate::git_history::record(VersionSource::Manual, description);
+    Ok(version)
 }
 
-/// Delete a version
+/// Contents of a version and of another one, or of the current config
+fn diff_contents(id: &str, other: Option<&str>) -> Result<(String, String)> {
+    let content1 = get_version_content(&resolve_version_id(id)?)?;
+
+    let content2 = match other {
+        Some(other) => get_version_content(&resolve_version_id(other)?)?,
+        None => {
+            let current_path = storage::get_current_config_path_for_diff()?;
+            if current_path.exists() {
+                fs::read_to_string(&current_path)?
+            } else {
+                String::new()
+            }
+        }
+    };
+
+    Ok((content1, content2))
+}
+
+/// Diff a version against another one, or against the current config
+pub fn diff_versions(id: &str, other: Option<&str>) -> Result<VersionDiff> {
+    let (content1, content2) = diff_contents(id, other)?;
+    Ok(VersionDiff {
+        lines: diff_configs(&content1, &content2),
+        // Versions of files other than Clash configs only get the line diff
+        semantic: config_diff::diff_yaml(&content1, &content2).ok(),
+    })
+}
+
+/// Delete a version; versions based on it get its parent instead
 pub fn delete_version(id: &str) -> Result<()> {
     let resolved_id = resolve_version_id(id)?;
     if resolved_id == "v0" {
         return Ok(()); // Nothing to delete
     }
 
+    delete_versions(&[resolved_id])?;
+    Ok(())
+}
+
+/// Remove versions from the index and delete their files
+fn delete_versions(ids: &[String]) -> Result<Vec<ConfigVersion>> {
+    let removed = modify_index(|index| {
+        let mut removed = Vec::new();
+        for id in ids {
+            let position = index
+                .versions
+                .iter()
+                .position(|v| &v.id == id)
+                .with_context(|| format!("Version {} not found", id))?;
+            let version = index.versions.remove(position);
+            for other in &mut index.versions {
+                if other.parent.as_deref() == Some(version.id.as_str()) {
+                    other.parent = version.parent.clone();
+                }
+            }
+            removed.push(version);
+        }
+        Ok(removed)
+    })?;
+
+    for version in &removed {
+        if let Err(e) = fs::remove_file(&version.file_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e).context("Failed to delete version file");
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Snapshot merged `content` of current.yaml unless the newest current.yaml version
+/// already has it, then apply the retention policy
+pub fn snapshot_merge(content: &str, description: &str) -> Result<Option<ConfigVersion>> {
+    let config = storage::load_hangar_config().unwrap_or_default().history;
+    if !config.snapshot_on_merge {
+        return Ok(None);
+    }
+
+    let hash = content_hash(content);
+    if load_index()?
+        .latest_for("current")
+        .is_some_and(|v| v.content_hash == hash)
+    {
+        return Ok(None);
+    }
+
+    let version = save_version("current", VersionSource::Merge, description, content)?;
+    // Only merge snapshots are pruned automatically; AI, rollback and template snapshots
+    // wait for an explicit `history prune`
+    prune_where(&config, false, |v| v.source == VersionSource::Merge)?;
+    Ok(Some(version))
+}
+
+/// Versions `policy` doesn't keep: all but the newest `keep_last` of each target and the
+/// newest of each target per day within `keep_daily_days`. Manual and tagged snapshots
+/// are always kept and don't count towards either.
+pub fn plan_prune(
+    versions: &[ConfigVersion],
+    policy: &HistoryConfig,
+    now: DateTime<Local>,
+) -> Vec<ConfigVersion> {
+    let mut newest_first: Vec<&ConfigVersion> = versions.iter().collect();
+    newest_first.sort_by_key(|v| std::cmp::Reverse((v.timestamp, v.number)));
+
+    let oldest_daily = now.date_naive() - chrono::Days::new(policy.keep_daily_days as u64);
+    let mut kept_per_target: HashMap<&str, usize> = HashMap::new();
+    let mut kept_days: HashSet<(&str, NaiveDate)> = HashSet::new();
+    let mut pruned = Vec::new();
+
+    for version in newest_first {
+        let target = version.target.as_str();
+        let day = DateTime::from_timestamp(version.timestamp, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local)
+            .date_naive();
+
+        if version.source == VersionSource::Manual || !version.tags.is_empty() {
+            continue;
+        }
+
+        let count = kept_per_target.entry(target).or_default();
+        let keep = if *count < policy.keep_last {
+            true
+        } else {
+            // Newest first, so the first version seen on a day is that day's newest
+            policy.keep_daily_days > 0 && day > oldest_daily && !kept_days.contains(&(target, day))
+        };
+
+        if keep {
+            *count += 1;
+            kept_days.insert((target, day));
+        } else {
+            pruned.push(version.clone());
+        }
+    }
+    pruned
+}
+
+/// Delete the versions `policy` doesn't keep (only list them with `dry_run`)
+pub fn prune(policy: &HistoryConfig, dry_run: bool) -> Result<Vec<ConfigVersion>> {
+    prune_where(policy, dry_run, |_| true)
+}
+
+/// `prune`, applying the policy to the versions matching `filter` only
+fn prune_where(
+    policy: &HistoryConfig,
+    dry_run: bool,
+    filter: impl Fn(&ConfigVersion) -> bool,
+) -> Result<Vec<ConfigVersion>> {
+    let versions: Vec<ConfigVersion> = load_index()?
+        .versions
+        .into_iter()
+        .filter(|v| filter(v))
+        .collect();
+    let pruned = plan_prune(&versions, policy, Local::now());
+    if dry_run || pruned.is_empty() {
+        return Ok(pruned);
+    }
+    let ids: Vec<String> = pruned.iter().map(|v| v.id.clone()).collect();
+    delete_versions(&ids)
+}
+
+/// What importing versions from a backup did (or would do)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistoryImport {
+    pub added: usize,
+    /// Already present: same target, time and content
+    pub skipped: usize,
+    /// Tags of imported versions that local versions already use
+    pub dropped_tags: Vec<String>,
+}
+
+/// Add versions and their contents from a backup. Imported versions get new numbers, and
+/// versions already present are skipped. With `replace`, the local history is deleted
+/// first and imported versions keep their IDs. Parents are relinked by time either way.
+pub fn import_versions(
+    mut entries: Vec<(ConfigVersion, String)>,
+    replace: bool,
+    dry_run: bool,
+) -> Result<HistoryImport> {
+    let _lock = lock_index()?;
+    let local = with_file_paths(load_or_rebuild_index()?)?;
+    let mut index = if replace {
+        VersionIndex::default()
+    } else {
+        local.clone()
+    };
+    let mut result = HistoryImport::default();
+    let mut written = Vec::new();
+
+    entries.sort_by_key(|(v, _)| (v.timestamp, v.number));
+    for (mut version, content) in entries {
+        if Path::new(&version.file).file_name() != Some(version.file.as_ref()) {
+            anyhow::bail!("Invalid version file name: {:?}", version.file);
+        }
+        let hash = content_hash(&content);
+        if index.versions.iter().any(|v| {
+            v.target == version.target && v.timestamp == version.timestamp && v.content_hash == hash
+        }) {
+            result.skipped += 1;
+            continue;
+        }
+
+        if replace {
+            index.next_number = index.next_number.max(version.number + 1);
+        } else {
+            let number = index.next_number.max(1);
+            version.id = format!("v{}", number);
+            version.number = number;
+            version.file = format!("{}.yaml", version.id);
+            index.next_number = number + 1;
+            version.tags.retain(|tag| {
+                let taken = index.versions.iter().any(|v| v.tags.contains(tag));
+                if taken {
+                    result.dropped_tags.push(tag.clone());
+                }
+                !taken
+            });
+        }
+        version.content_hash = hash;
+        written.push((version.file.clone(), content));
+        index.versions.push(version);
+        result.added += 1;
+    }
+
+    if dry_run {
+        return Ok(result);
+    }
+
+    // Versions are oldest first, each based on the previous one of its target
+    index.versions.sort_by_key(|v| (v.timestamp, v.number));
+    let mut latest: HashMap<String, String> = HashMap::new();
+    for version in &mut index.versions {
+        version.parent = latest.insert(version.target.clone(), version.id.clone());
+    }
+
     let versions_dir = storage::get_versions_dir()?;
-    let file_path = versions_dir.join(resolved_id);
+    for (file, content) in &written {
+        storage::write_atomic(&versions_dir.join(file), content)
+            .context("Failed to save version file")?;
+    }
+    write_index(&index)?;
 
-    fs::remove_file(&file_path).context("Failed to delete version file")?;
+    if replace {
+        let kept: HashSet<&str> = index.versions.iter().map(|v| v.file.as_str()).collect();
+        for version in local
+            .versions
+            .iter()
+            .filter(|v| !kept.contains(v.file.as_str()))
+        {
+            if let Err(e) = fs::remove_file(versions_dir.join(&version.file)) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e).context("Failed to delete version file");
+                }
+            }
+        }
+    }
 
-    Ok(())
+    Ok(result)
 }
 
 /// Simple text diff between two strings
@@ -249,8 +825,205 @@ pub fn diff_configs(old: &str, new: &str) -> Vec<DiffLine> {
     result
 }
 
+/// Line diff of two versions, plus the structural diff when both parse as Clash configs
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VersionDiff {
+    pub lines: Vec<DiffLine>,
+    pub semantic: Option<SemanticDiff>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DiffLine {
     pub line_type: String, // "added", "removed", "unchanged"
     pub content: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_legacy_names() {
+        assert_eq!(
+            parse_legacy_name("v3_basic_1700000000_add_hk_rule.yaml"),
+            Some(LegacyName {
+                number: Some(3),
+                file_type: "basic".to_string(),
+                timestamp: 1700000000,
+                description: "add_hk_rule".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_legacy_name("v_groups_1600000000_x.yml").map(|n| (n.number, n.file_type)),
+            Some((None, "groups".to_string()))
+        );
+        assert_eq!(
+            parse_legacy_name("v_1500000000_old.yaml").map(|n| (n.file_type, n.description)),
+            Some(("legacy".to_string(), "old".to_string()))
+        );
+        assert_eq!(parse_legacy_name("index.json"), None);
+        assert_eq!(parse_legacy_name("v2.yaml"), None);
+        assert_eq!(parse_indexed_name("v2.yaml"), Some(2));
+        assert_eq!(parse_indexed_name("v2_basic_1_x.yaml"), None);
+    }
+
+    fn version(
+        number: usize,
+        target: &str,
+        source: VersionSource,
+        timestamp: i64,
+    ) -> ConfigVersion {
+        ConfigVersion {
+            id: format!("v{}", number),
+            number,
+            timestamp,
+            description: String::new(),
+            target: target.to_string(),
+            source,
+            parent: None,
+            content_hash: String::new(),
+            file: format!("v{}.yaml", number),
+            tags: vec![],
+            note: None,
+            file_path: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_plan_prune() {
+        let now = DateTime::from_timestamp(100 * 86400 + 43200, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        let day = |d: i64| now.timestamp() - d * 86400;
+        let versions = vec![
+            version(1, "current", VersionSource::Manual, day(90)),
+            version(2, "current", VersionSource::Merge, day(60)),
+            version(3, "current", VersionSource::Merge, day(2) - 60),
+            version(4, "current", VersionSource::Merge, day(2)),
+            version(5, "current", VersionSource::Merge, day(1)),
+            version(6, "current", VersionSource::Merge, day(0) - 60),
+            version(7, "current", VersionSource::Merge, day(0)),
+            version(8, "basic", VersionSource::Ai, day(60)),
+            ConfigVersion {
+                tags: vec!["known-good".to_string()],
+                ..version(9, "current", VersionSource::Merge, day(61))
+            },
+            // Newer than v6, but outside the keep_last budget
+            version(10, "current", VersionSource::Manual, day(0) - 30),
+        ];
+        let policy = HistoryConfig {
+            snapshot_on_merge: true,
+            keep_last: 2,
+            keep_daily_days: 7,
+            git: false,
+        };
+
+        let pruned: Vec<usize> = plan_prune(&versions, &policy, now)
+            .iter()
+            .map(|v| v.number)
+            .collect();
+        // Kept: v7, v6 (last 2), v5 and v4 (newest of their day), v1 and v10 (manual),
+        // v8 (last of basic), v9 (tagged)
+        assert_eq!(pruned, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_migrate_from_filenames() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [
+            ("v1_manual_100_first.yaml", "a: 1\n"),
+            ("v2_basic_200_use_hk.yaml", "b: 1\n"),
+            ("v3_backup_300_auto_backup_before_rollback.yaml", "a: 2\n"),
+            ("v_50_legacy.yaml", "a: 0\n"),
+        ] {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        let index = migrate_from_filenames(dir.path()).unwrap();
+        let summary: Vec<(&str, usize, &str, VersionSource, Option<&str>)> = index
+            .versions
+            .iter()
+            .map(|v| {
+                (
+                    v.id.as_str(),
+                    v.number,
+                    v.target.as_str(),
+                    v.source,
+                    v.parent.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "v_50_legacy.yaml",
+                    4,
+                    "current",
+                    VersionSource::Manual,
+                    None
+                ),
+                (
+                    "v1_manual_100_first.yaml",
+                    1,
+                    "current",
+                    VersionSource::Manual,
+                    Some("v_50_legacy.yaml")
+                ),
+                (
+                    "v2_basic_200_use_hk.yaml",
+                    2,
+                    "basic",
+                    VersionSource::Ai,
+                    None
+                ),
+                (
+                    "v3_backup_300_auto_backup_before_rollback.yaml",
+                    3,
+                    "current",
+                    VersionSource::Rollback,
+                    Some("v1_manual_100_first.yaml")
+                ),
+            ]
+        );
+        assert_eq!(index.next_number, 5);
+        assert_eq!(index.versions[1].content_hash, content_hash("a: 1\n"));
+    }
+
+    #[test]
+    fn test_migrate_recovers_indexed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in [
+            (
+                "v4.yaml",
+                "proxies:\n  - {name: a, type: ss, server: s, port: 1}\n",
+            ),
+            (
+                "v5.yaml",
+                "- {name: Proxy, type: select, proxies: [DIRECT]}\n",
+            ),
+            ("v6.yaml", "prepend:\n  - DOMAIN,a.com,DIRECT\n"),
+            ("v7.yaml", "mixed-port: 7890\n"),
+        ] {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        let index = migrate_from_filenames(dir.path()).unwrap();
+        let mut summary: Vec<(&str, &str, &str, VersionSource)> = index
+            .versions
+            .iter()
+            .map(|v| (v.id.as_str(), v.file.as_str(), v.target.as_str(), v.source))
+            .collect();
+        summary.sort_by_key(|v| v.0);
+        assert_eq!(
+            summary,
+            vec![
+                ("v4", "v4.yaml", "current", VersionSource::Manual),
+                ("v5", "v5.yaml", "groups", VersionSource::Manual),
+                ("v6", "v6.yaml", "local", VersionSource::Manual),
+                ("v7", "v7.yaml", "basic", VersionSource::Manual),
+            ]
+        );
+        assert_eq!(index.next_number, 8);
+    }
+}
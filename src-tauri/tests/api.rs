@@ -0,0 +1,236 @@
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use hangar_lib::{auth, server, storage, types};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+mod common;
+use common::isolated_home;
+
+async fn call(
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let state = server::AppState::new(types::ClashConfig {
+        base_config: Default::default(),
+        proxies: vec![],
+        proxy_groups: vec![],
+        rules: vec![],
+        rule_providers: None,
+    })
+    .unwrap();
+
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = server::router(state).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn admin_token() -> String {
+    auth::create_token("admin", "default", None, true)
+        .unwrap()
+        .0
+}
+
+#[tokio::test]
+async fn test_api_requires_admin_token() {
+    let _home = isolated_home().await;
+
+    // Without any token configured the API stays closed
+    let (status, body) = call(Method::GET, "/api/subscriptions", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["error"].is_string());
+
+    let (client, _) = auth::create_token("phone", "default", None, false).unwrap();
+    let (status, _) = call(Method::GET, "/api/subscriptions", Some(&client), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let admin = admin_token();
+    let (status, _) = call(Method::GET, "/api/subscriptions", Some(&admin), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_subscription_crud() {
+    let _home = isolated_home().await;
+    let admin = admin_token();
+    let token = Some(admin.as_str());
+
+    let (status, sub) = call(
+        Method::POST,
+        "/api/subscriptions",
+        token,
+        Some(json!({ "name": "Home", "url": "https://example.com/sub" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(sub["enabled"], true);
+
+    let (status, sub) = call(
+        Method::PUT,
+        "/api/subscriptions/Home",
+        token,
+        Some(json!({ "enabled": false })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(sub["enabled"], false);
+    assert!(!storage::load_subscriptions().unwrap()[0].enabled);
+
+    let (status, _) = call(Method::GET, "/api/subscriptions/Missing", token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(
+        Method::PUT,
+        "/api/subscriptions/Missing",
+        token,
+        Some(json!({ "enabled": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Client mistakes are 4xx, not server errors
+    let (status, body) = call(
+        Method::POST,
+        "/api/subscriptions",
+        token,
+        Some(json!({ "name": "Home", "url": "https://example.com/other" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].as_str().unwrap().contains("Home"));
+    let (status, _) = call(
+        Method::POST,
+        "/api/subscriptions",
+        token,
+        Some(json!({ "name": "Work", "url": "not a url" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = call(Method::DELETE, "/api/subscriptions/Home", token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(storage::load_subscriptions().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_api_versions() {
+    let _home = isolated_home().await;
+    let admin = admin_token();
+    let token = Some(admin.as_str());
+
+    std::fs::write(
+        storage::get_current_config_path().unwrap(),
+        "mixed-port: 7890\n",
+    )
+    .unwrap();
+
+    let (status, version) = call(
+        Method::POST,
+        "/api/versions",
+        token,
+        Some(json!({ "description": "before travel" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = version["id"].as_str().unwrap().to_string();
+
+    let (status, list) = call(Method::GET, "/api/versions", token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(list
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|v| v["id"] == id.as_str()));
+
+    let (status, content) = call(Method::GET, &format!("/api/versions/{}", id), token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content["content"], "mixed-port: 7890\n");
+
+    let (status, _) = call(Method::GET, "/api/versions/..%2Fconfig.json", token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(Method::DELETE, "/api/versions/v999", token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = call(
+        Method::GET,
+        &format!("/api/versions/{}/diff?against=v999", id),
+        token,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_rule_sources() {
+    let _home = isolated_home().await;
+    let admin = admin_token();
+    let token = Some(admin.as_str());
+
+    let (status, source) = call(
+        Method::POST,
+        "/api/rules",
+        token,
+        Some(json!({ "name": "ads", "url": "https://example.com/ads.list", "interval": 3600 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(source["interval"], 3600);
+
+    let (status, _) = call(
+        Method::POST,
+        "/api/rules",
+        token,
+        Some(json!({ "name": "ads-again", "url": "https://example.com/ads.list" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = call(
+        Method::POST,
+        "/api/rules",
+        token,
+        Some(json!({ "name": "fast", "url": "https://example.com/fast.list", "interval": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call(
+        Method::PUT,
+        "/api/rules/missing",
+        token,
+        Some(json!({ "enabled": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, source) = call(
+        Method::PUT,
+        "/api/rules/ads",
+        token,
+        Some(json!({ "enabled": false })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(source["enabled"], false);
+
+    let (status, _) = call(Method::DELETE, "/api/rules/ads", token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call(Method::GET, "/api/rules/ads", token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,227 @@
+use hangar_lib::backup::{self, ExportOptions, ImportMode, ImportOptions, Kept, SecretsMode};
+use hangar_lib::types::{Subscription, VersionSource};
+use hangar_lib::{storage, version};
+
+mod common;
+use common::isolated_home;
+
+fn subscription(id: &str, name: &str, url: &str) -> Subscription {
+    Subscription {
+        id: id.to_string(),
+        name: name.to_string(),
+        url: url.to_string(),
+        enabled: true,
+        last_updated: None,
+        node_count: None,
+        last_error: None,
+    }
+}
+
+#[tokio::test]
+async fn test_export_and_merge_import() {
+    let _home = isolated_home().await;
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("hangar-backup.tar.gz");
+
+    let mut config = storage::load_hangar_config().unwrap();
+    config.llm.api_key = "sk-secret".to_string();
+    config.llm.model = "gpt-4o-mini".to_string();
+    storage::save_hangar_config(&config).unwrap();
+    storage::save_subscriptions(&[
+        subscription("1", "Home", "https://a.example.com/sub?token=1"),
+        subscription("2", "Work", "https://b.example.com/sub?token=2"),
+    ])
+    .unwrap();
+    let basic_path = storage::get_basic_config_path().unwrap();
+    std::fs::write(&basic_path, "mixed-port: 7890\n").unwrap();
+    version::save_version(
+        "basic",
+        VersionSource::Manual,
+        "exported",
+        "mixed-port: 7890\n",
+    )
+    .unwrap();
+
+    let manifest = backup::export(
+        &bundle,
+        &ExportOptions {
+            passphrase: Some("correct horse".to_string()),
+            plaintext_secrets: false,
+            include_history: true,
+        },
+    )
+    .unwrap();
+    assert_eq!(manifest.secrets, SecretsMode::Encrypted);
+    assert!(manifest.files.contains(&"basic.yml".to_string()));
+    assert!(!manifest.files.contains(&"current.yaml".to_string()));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = || std::fs::metadata(&bundle).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(), 0o600);
+
+        // Re-exporting over a readable bundle tightens it again
+        std::fs::set_permissions(&bundle, std::fs::Permissions::from_mode(0o644)).unwrap();
+        backup::export(
+            &bundle,
+            &ExportOptions {
+                passphrase: Some("correct horse".to_string()),
+                plaintext_secrets: false,
+                include_history: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(mode(), 0o600);
+    }
+
+    // A different machine: its own API key, model, basic.yml and one different subscription
+    let mut config = storage::load_hangar_config().unwrap();
+    config.llm.api_key = "sk-other".to_string();
+    config.llm.model = "gpt-4o".to_string();
+    storage::save_hangar_config(&config).unwrap();
+    storage::save_subscriptions(&[subscription(
+        "1",
+        "Home",
+        "https://a.example.com/sub?token=new",
+    )])
+    .unwrap();
+    std::fs::write(&basic_path, "mixed-port: 7891\n").unwrap();
+
+    // Wrong passphrase fails without changing anything
+    let wrong = ImportOptions {
+        passphrase: Some("wrong".to_string()),
+        ..Default::default()
+    };
+    assert!(backup::import(&bundle, &wrong).is_err());
+
+    let report = backup::import(
+        &bundle,
+        &ImportOptions {
+            passphrase: Some("correct horse".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    // The local API key won the conflict, so nothing was restored
+    assert!(!report.secrets_restored);
+    assert_eq!(report.added, vec!["subscription 'Work'"]);
+
+    let conflicts: Vec<(&str, Kept)> = report
+        .conflicts
+        .iter()
+        .map(|c| (c.item.as_str(), c.kept))
+        .collect();
+    assert!(conflicts.contains(&("llm.model", Kept::Local)));
+    assert!(conflicts.contains(&("llm.api_key", Kept::Local)));
+    assert!(conflicts.contains(&("subscription 'Home'", Kept::Local)));
+    assert!(conflicts.contains(&("basic.yml", Kept::Local)));
+
+    // Local data wins conflicts; the missing subscription and the snapshot are added
+    let config = storage::load_hangar_config().unwrap();
+    assert_eq!(config.llm.api_key, "sk-other");
+    assert_eq!(config.llm.model, "gpt-4o");
+    let subs = storage::load_subscriptions().unwrap();
+    assert_eq!(subs.len(), 2);
+    assert!(subs[0].url.ends_with("token=new"));
+    assert_eq!(
+        std::fs::read_to_string(&basic_path).unwrap(),
+        "mixed-port: 7891\n"
+    );
+    // Every exported version is already present locally
+    let versions = report.versions.as_ref().unwrap();
+    assert_eq!(versions.added, 0);
+    assert!(versions.skipped > 0);
+
+    // Importing again changes nothing
+    let again = backup::import(
+        &bundle,
+        &ImportOptions {
+            passphrase: Some("correct horse".to_string()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(again.added.is_empty());
+    assert_eq!(again.versions.unwrap().added, 0);
+
+    let preferred = backup::import(
+        &bundle,
+        &ImportOptions {
+            passphrase: Some("correct horse".to_string()),
+            prefer_backup: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(preferred.secrets_restored);
+    assert_eq!(
+        storage::load_hangar_config().unwrap().llm.api_key,
+        "sk-secret"
+    );
+}
+
+#[tokio::test]
+async fn test_replace_import() {
+    let _home = isolated_home().await;
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("hangar-backup.tar.gz");
+
+    let mut config = storage::load_hangar_config().unwrap();
+    config.llm.api_key = "sk-secret".to_string();
+    config.llm.model = "gpt-4o-mini".to_string();
+    storage::save_hangar_config(&config).unwrap();
+    storage::save_subscriptions(&[subscription("1", "Home", "https://a.example.com/sub")]).unwrap();
+    let basic_path = storage::get_basic_config_path().unwrap();
+    std::fs::write(&basic_path, "mixed-port: 7890\n").unwrap();
+
+    // Secrets are left out unless encrypted or explicitly included
+    let manifest = backup::export(&bundle, &ExportOptions::default()).unwrap();
+    assert_eq!(manifest.secrets, SecretsMode::Omitted);
+
+    let mut config = storage::load_hangar_config().unwrap();
+    config.llm.api_key = "sk-local".to_string();
+    config.llm.model = "gpt-4o".to_string();
+    storage::save_hangar_config(&config).unwrap();
+    storage::save_subscriptions(&[subscription("2", "Work", "https://b.example.com/sub")]).unwrap();
+    std::fs::write(&basic_path, "mixed-port: 7891\n").unwrap();
+
+    let options = ImportOptions {
+        mode: ImportMode::Replace,
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = backup::import(&bundle, &options).unwrap();
+    assert_eq!(report.removed, vec!["subscription 'Work'"]);
+    assert_eq!(
+        storage::load_subscriptions().unwrap()[0].name,
+        "Work",
+        "dry run writes nothing"
+    );
+
+    backup::import(
+        &bundle,
+        &ImportOptions {
+            dry_run: false,
+            ..options
+        },
+    )
+    .unwrap();
+    let config = storage::load_hangar_config().unwrap();
+    assert_eq!(config.llm.model, "gpt-4o-mini");
+    // The bundle had no secrets, so the local ones are kept
+    assert_eq!(config.llm.api_key, "sk-local");
+    assert_eq!(storage::load_subscriptions().unwrap()[0].name, "Home");
+    assert_eq!(
+        std::fs::read_to_string(&basic_path).unwrap(),
+        "mixed-port: 7890\n"
+    );
+
+    // The overwritten basic.yml was snapshotted
+    let versions = version::list_versions().unwrap();
+    let snapshot = versions.iter().find(|v| v.target == "basic").unwrap();
+    assert_eq!(snapshot.description, "Before backup import");
+    assert_eq!(
+        version::get_version_content(&snapshot.id).unwrap(),
+        "mixed-port: 7891\n"
+    );
+}
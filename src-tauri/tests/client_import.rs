@@ -0,0 +1,154 @@
+use hangar_lib::types::Subscription;
+use hangar_lib::{client_import, storage, subscription, version};
+
+mod common;
+use common::isolated_home;
+
+const SUBCONVERTER: &str = r#"
+mixed-port: 7890
+dns:
+  enable: true
+  nameserver: [223.5.5.5]
+proxies:
+  - {name: "🇭🇰 HK 01", type: ss, server: hk.example.com, port: 443, cipher: aes-128-gcm, password: x}
+  - {name: "🇺🇸 US 01", type: ss, server: us.example.com, port: 443, cipher: aes-128-gcm, password: x}
+proxy-groups:
+  - {name: Proxy, type: select, proxies: ["🇭🇰 HK 01", "🇺🇸 US 01", DIRECT]}
+rules:
+  - DOMAIN-SUFFIX,google.com,Proxy
+  - MATCH,DIRECT
+"#;
+
+#[tokio::test]
+async fn test_import_clash_yaml() {
+    let _home = isolated_home().await;
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("airport.yaml");
+    std::fs::write(&file, SUBCONVERTER).unwrap();
+
+    let basic_path = storage::get_basic_config_path().unwrap();
+    std::fs::write(
+        &basic_path,
+        "mixed-port: 7897\nexternal-controller: 127.0.0.1:9090\n",
+    )
+    .unwrap();
+    storage::save_subscriptions(&[Subscription {
+        id: "1".to_string(),
+        name: "airport".to_string(),
+        url: "https://a.example.com/sub".to_string(),
+        enabled: true,
+        last_updated: None,
+        node_count: None,
+        last_error: None,
+    }])
+    .unwrap();
+
+    // The name is taken, so the local proxies are skipped
+    let plan = client_import::plan_import(&file, None).unwrap();
+    assert_eq!(plan.subscriptions.len(), 1);
+    assert!(plan.subscriptions[0].skipped.is_some());
+    let base = plan.base.as_ref().unwrap();
+    assert_eq!(base.kept_settings, vec!["external-controller"]);
+    assert_eq!(base.dropped_members, 2);
+
+    storage::save_subscriptions(&[]).unwrap();
+    let plan = client_import::plan_import(&file, None).unwrap();
+    let created = client_import::apply_import(&plan, true).unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].node_count, Some(2));
+    assert!(created[0].url.starts_with("file://"));
+    assert_eq!(subscription::count_proxies(&created[0].id).unwrap(), 2);
+
+    // The local subscription refreshes from its file
+    let refreshed = subscription::refresh_subscription("airport").await.unwrap();
+    assert_eq!(refreshed.node_count, Some(2));
+
+    // Other file:// URLs are refused, including ones escaping ~/.hangar/local/
+    let outside = file.parent().unwrap().join("outside.yaml");
+    std::fs::write(&outside, "proxies: []\n").unwrap();
+    let escaping = format!(
+        "{}/../{}",
+        storage::get_local_proxies_dir().unwrap().display(),
+        "config.json"
+    );
+    for path in [outside.display().to_string(), escaping] {
+        let mut sub = created[0].clone();
+        sub.url = format!("file://{}", path);
+        assert!(subscription::download_subscription(&sub).await.is_err());
+    }
+
+    let basic = std::fs::read_to_string(&basic_path).unwrap();
+    assert!(basic.contains("mixed-port: 7890"));
+    assert!(basic.contains("external-controller"));
+    assert!(basic.contains("nameserver"));
+    assert!(!basic.contains("HK 01"));
+    let groups = std::fs::read_to_string(storage::get_groups_config_path().unwrap()).unwrap();
+    assert!(groups.contains("DIRECT"));
+    assert!(!groups.contains("US 01"));
+
+    // The replaced basic.yml was snapshotted
+    let versions = version::list_versions().unwrap();
+    let snapshot = versions.iter().find(|v| v.target == "basic").unwrap();
+    assert_eq!(snapshot.description, "Before import from Clash YAML");
+    assert!(version::get_version_content(&snapshot.id)
+        .unwrap()
+        .contains("mixed-port: 7897"));
+}
+
+#[tokio::test]
+async fn test_import_dedups_urls_and_applies_all_or_nothing() {
+    let _home = isolated_home().await;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("profiles")).unwrap();
+    std::fs::write(
+        dir.path().join("profiles.yaml"),
+        r#"
+current: Ra
+items:
+  - {uid: Ra, type: remote, name: Airport, file: Ra.yaml, url: "https://airport.example.com/sub?token=1"}
+  - {uid: Rb, type: remote, name: Airport copy, file: Rb.yaml, url: "https://airport.example.com/sub?token=1"}
+  - {uid: Rc, type: remote, name: Backup, file: Rc.yaml, url: "https://backup.example.com/sub"}
+"#,
+    )
+    .unwrap();
+    for file in ["Ra.yaml", "Rb.yaml", "Rc.yaml"] {
+        std::fs::write(dir.path().join("profiles").join(file), SUBCONVERTER).unwrap();
+    }
+
+    let plan = client_import::plan_import(dir.path(), None).unwrap();
+    let skipped: Vec<_> = plan
+        .subscriptions
+        .iter()
+        .map(|s| (s.name.as_str(), s.skipped.as_deref()))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![
+            ("Airport", None),
+            ("Airport copy", Some("same URL as 'Airport' in this import")),
+            ("Backup", None),
+        ]
+    );
+
+    // Added between planning and applying: nothing of the import is written
+    subscription::add_subscription(
+        "Backup".to_string(),
+        "https://other.example.com".to_string(),
+    )
+    .unwrap();
+    let basic_before = std::fs::read_to_string(storage::get_basic_config_path().unwrap()).ok();
+    let cache_count = || {
+        std::fs::read_dir(storage::get_proxies_cache_dir().unwrap())
+            .unwrap()
+            .count()
+    };
+    let caches_before = cache_count();
+    assert!(client_import::apply_import(&plan, true).is_err());
+    let subs = storage::load_subscriptions().unwrap();
+    assert_eq!(subs.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(storage::get_basic_config_path().unwrap()).ok(),
+        basic_before
+    );
+    assert_eq!(cache_count(), caches_before);
+}
@@ -0,0 +1,18 @@
+use hangar_lib::{storage, types};
+use std::sync::OnceLock;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Point ~/.hangar at a temp dir shared by the tests of one binary, one test at a time.
+/// Each test starts with a default config.json and no subscriptions.
+pub async fn isolated_home() -> MutexGuard<'static, ()> {
+    static HOME: OnceLock<tempfile::TempDir> = OnceLock::new();
+    static LOCK: Mutex<()> = Mutex::const_new(());
+
+    let home = HOME.get_or_init(|| tempfile::tempdir().unwrap());
+    std::env::set_var("HOME", home.path());
+
+    let guard = LOCK.lock().await;
+    storage::save_hangar_config(&types::HangarConfig::default()).unwrap();
+    storage::save_subscriptions(&[]).unwrap();
+    guard
+}
@@ -0,0 +1,344 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use hangar_lib::{auth, server, storage, types};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+mod common;
+use common::isolated_home;
+
+fn sample_config() -> types::ClashConfig {
+    serde_yaml::from_str(
+        r#"
+mixed-port: 7890
+proxies:
+  - {name: "[A]-HK 01", type: ss, server: a.example.com, port: 1}
+  - {name: "[B]-US 01", type: ss, server: b.example.com, port: 2}
+proxy-groups:
+  - {name: "Proxy", type: select, proxies: ["[A]-HK 01", "[B]-US 01"]}
+rules:
+  - MATCH,Proxy
+"#,
+    )
+    .unwrap()
+}
+
+async fn get(uri: &str, bearer: Option<&str>) -> (StatusCode, String) {
+    let app = server::router(server::AppState::new(sample_config()).unwrap());
+
+    let mut request = Request::builder().uri(uri);
+    if let Some(token) = bearer {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let response = app
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_health() {
+    let _home = isolated_home().await;
+    assert_eq!(
+        get("/health", None).await,
+        (StatusCode::OK, "OK".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_config_without_tokens_is_open() {
+    let _home = isolated_home().await;
+    let (status, body) = get("/config", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let config: types::ClashConfig = serde_yaml::from_str(&body).unwrap();
+    assert_eq!(config.proxies.len(), 2);
+    assert_eq!(config.rules, vec!["MATCH,Proxy"]);
+}
+
+#[tokio::test]
+async fn test_config_requires_valid_token() {
+    let _home = isolated_home().await;
+    let (secret, _) = auth::create_token("phone", "default", None, false).unwrap();
+
+    assert_eq!(get("/config", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        get("/config?token=wrong", None).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(get("/config", Some(&secret)).await.0, StatusCode::OK);
+    assert_eq!(
+        get(&format!("/config?token={}", secret), None).await.0,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_config_serves_token_profile() {
+    let _home = isolated_home().await;
+    storage::save_subscriptions(&[
+        types::Subscription {
+            id: "a".to_string(),
+            name: "A".to_string(),
+            url: String::new(),
+            enabled: true,
+            last_updated: None,
+            node_count: None,
+            last_error: None,
+        },
+        types::Subscription {
+            id: "b".to_string(),
+            name: "B".to_string(),
+            url: String::new(),
+            enabled: true,
+            last_updated: None,
+            node_count: None,
+            last_error: None,
+        },
+    ])
+    .unwrap();
+    hangar_lib::profile::add_profile("only-a", vec!["A".to_string()]).unwrap();
+    let (secret, _) = auth::create_token("tablet", "only-a", None, false).unwrap();
+
+    let (status, body) = get("/config", Some(&secret)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let config: types::ClashConfig = serde_yaml::from_str(&body).unwrap();
+    let names: Vec<_> = config.proxies.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["[A]-HK 01"]);
+    assert_eq!(config.proxy_groups[0].proxies, vec!["[A]-HK 01"]);
+}
+
+#[tokio::test]
+async fn test_config_rejects_token_with_unknown_profile() {
+    let _home = isolated_home().await;
+    storage::modify_hangar_config(|config| {
+        config.profiles.push(types::Profile {
+            name: "only-a".to_string(),
+            subscriptions: vec!["A".to_string()],
+        });
+        Ok(())
+    })
+    .unwrap();
+    let (secret, _) = auth::create_token("tablet", "only-a", None, false).unwrap();
+    // Edited by hand afterwards, leaving the token pointing at nothing
+    storage::modify_hangar_config(|config| {
+        config.profiles.clear();
+        Ok(())
+    })
+    .unwrap();
+
+    let (status, body) = get("/config", Some(&secret)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!body.contains("proxies"));
+}
+
+async fn send(
+    state: &server::AppState,
+    headers: &[(header::HeaderName, &str)],
+) -> axum::response::Response {
+    let mut request = Request::builder().uri("/config");
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    server::router(state.clone())
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_config_conditional_requests() {
+    let _home = isolated_home().await;
+    let state = server::AppState::new(sample_config()).unwrap();
+
+    let response = send(&state, &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let last_modified = response.headers()[header::LAST_MODIFIED]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    assert!(etag.starts_with("W/\""));
+
+    let response = send(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::VARY], "accept-encoding");
+    let response = send(&state, &[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // Same content keeps the validators, new content changes them
+    state.set_config(sample_config()).await.unwrap();
+    let response = send(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let mut changed = sample_config();
+    changed.rules.insert(0, "GEOIP,CN,DIRECT".to_string());
+    state.set_config(changed).await.unwrap();
+    let response = send(&state, &[(header::IF_NONE_MATCH, &etag)]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[header::ETAG], etag.as_str());
+}
+
+#[tokio::test]
+async fn test_config_compression_and_update_interval() {
+    let _home = isolated_home().await;
+    let state = server::AppState::new(sample_config())
+        .unwrap()
+        .with_update_interval(5400);
+
+    let response = send(&state, &[(header::ACCEPT_ENCODING, "gzip")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    assert_eq!(response.headers()["profile-update-interval"], "2");
+    assert!(response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .starts_with("W/"));
+    let vary: Vec<_> = response.headers().get_all(header::VARY).iter().collect();
+    assert_eq!(vary, vec!["accept-encoding"]);
+
+    let response = send(&state, &[(header::ACCEPT_ENCODING, "br")]).await;
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn test_events_stream() {
+    let _home = isolated_home().await;
+    let state = server::AppState::new(sample_config()).unwrap();
+    let (secret, _) = auth::create_token("gui", "default", None, true).unwrap();
+
+    let response = server::router(state)
+        .oneshot(
+            Request::builder()
+                .uri("/events")
+                .header(header::AUTHORIZATION, format!("Bearer {}", secret))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+
+    hangar_lib::events::publish(hangar_lib::events::DaemonEvent::ConfigReloaded { changed: true });
+
+    let mut body = response.into_body();
+    let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+    let text = String::from_utf8(frame.to_vec()).unwrap();
+    assert!(text.contains("event: config_reloaded"));
+    assert!(text.contains("\"changed\":true"));
+}
+
+#[tokio::test]
+async fn test_events_require_admin_token() {
+    let _home = isolated_home().await;
+    // Closed while no admin token exists, like /api
+    assert_eq!(get("/events", None).await.0, StatusCode::UNAUTHORIZED);
+
+    let (secret, _) = auth::create_token("phone", "default", None, false).unwrap();
+    assert_eq!(get("/events", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        get("/events", Some(&secret)).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_metrics_count_config_requests() {
+    let _home = isolated_home().await;
+    // Closed without an admin token, like /events
+    assert_eq!(get("/metrics", None).await.0, StatusCode::UNAUTHORIZED);
+
+    let state = server::AppState::new(sample_config()).unwrap();
+    let app = server::router(state);
+    let (admin, _) = auth::create_token("prometheus", "default", None, true).unwrap();
+    let (phone, _) = auth::create_token("phone", "default", None, false).unwrap();
+    assert_eq!(
+        get("/metrics", Some(&phone)).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let request = Request::builder()
+        .uri("/config")
+        .header(header::USER_AGENT, "clash-verge/v2.4.5")
+        .header(header::AUTHORIZATION, format!("Bearer {}", phone))
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .header(header::AUTHORIZATION, format!("Bearer {}", admin))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains("hangar_config_requests_total{client=\"clash-verge\",status=\"200\"} 1"));
+    assert!(text.contains("# TYPE hangar_subscription_downloads_total counter"));
+}
+
+#[tokio::test]
+async fn test_status_reports_subscriptions_and_config() {
+    let _home = isolated_home().await;
+    storage::save_subscriptions(&[types::Subscription {
+        id: "a".to_string(),
+        name: "A".to_string(),
+        url: String::new(),
+        enabled: true,
+        last_updated: None,
+        node_count: Some(0),
+        last_error: Some("timed out".to_string()),
+    }])
+    .unwrap();
+
+    let (status, body) = get("/status", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let status: server::ServerStatus = serde_json::from_str(&body).unwrap();
+    assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(status.profiles, vec!["default"]);
+    assert_eq!(status.config.proxies, 2);
+    assert_eq!(
+        status.subscriptions[0].last_error.as_deref(),
+        Some("timed out")
+    );
+
+    // Once tokens exist, only admin tokens see it
+    let (phone, _) = auth::create_token("phone", "default", None, false).unwrap();
+    assert_eq!(get("/status", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        get("/status", Some(&phone)).await.0,
+        StatusCode::UNAUTHORIZED
+    );
+    let (admin, _) = auth::create_token("cli", "default", None, true).unwrap();
+    assert_eq!(get("/status", Some(&admin)).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_public_bind_requires_tokens() {
+    let _home = isolated_home().await;
+    assert!(server::check_unprotected("127.0.0.1", false).is_ok());
+    assert!(server::check_unprotected("localhost", false).is_ok());
+    assert!(server::check_unprotected("0.0.0.0", false).is_err());
+    assert!(server::check_unprotected("0.0.0.0", true).is_ok());
+
+    auth::create_token("phone", "default", None, false).unwrap();
+    assert!(server::check_unprotected("0.0.0.0", false).is_ok());
+}
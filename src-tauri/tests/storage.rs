@@ -0,0 +1,107 @@
+use hangar_lib::types::Subscription;
+use hangar_lib::{rules, storage, subscription, version};
+
+mod common;
+use common::isolated_home;
+
+fn subscription(id: String) -> Subscription {
+    Subscription {
+        name: id.clone(),
+        url: format!("https://example.com/{}", id),
+        id,
+        enabled: true,
+        last_updated: None,
+        node_count: None,
+        last_error: None,
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_modifications_are_kept() {
+    let _home = isolated_home().await;
+
+    // Each thread opens its own lock file handle, as separate processes would
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            std::thread::spawn(move || {
+                for i in 0..10 {
+                    storage::modify_subscriptions(|subs| {
+                        subs.push(subscription(format!("{}-{}", t, i)));
+                        Ok(())
+                    })
+                    .unwrap();
+                    storage::update_hangar_config(serde_json::json!({
+                        "llm": { "model": format!("model-{}-{}", t, i) }
+                    }))
+                    .unwrap();
+                    rules::add_rule_source(
+                        format!("{}-{}", t, i),
+                        format!("https://example.com/rules/{}-{}", t, i),
+                        None,
+                    )
+                    .unwrap();
+                    version::save_version("basic", Default::default(), "test", "a: 1\n").unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(storage::load_subscriptions().unwrap().len(), 80);
+    assert_eq!(rules::load_rule_sources().unwrap().len(), 80);
+    let versions = version::list_versions().unwrap();
+    assert_eq!(versions.len(), 80);
+    let mut numbers: Vec<usize> = versions.iter().map(|v| v.number).collect();
+    numbers.sort();
+    numbers.dedup();
+    assert_eq!(numbers.len(), 80, "every snapshot gets its own number");
+    assert!(storage::load_hangar_config()
+        .unwrap()
+        .llm
+        .model
+        .starts_with("model-"));
+
+    // Atomic writes leave no temp files behind
+    let leftovers: Vec<_> = std::fs::read_dir(storage::get_hangar_dir().unwrap())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[tokio::test]
+async fn test_failed_modification_writes_nothing() {
+    let _home = isolated_home().await;
+    storage::save_subscriptions(&[subscription("a".to_string())]).unwrap();
+
+    let result: anyhow::Result<()> = storage::modify_subscriptions(|subs| {
+        subs.clear();
+        anyhow::bail!("validation failed")
+    });
+    assert!(result.is_err());
+    assert_eq!(storage::load_subscriptions().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_index_lookup_ignores_numeric_names() {
+    let _home = isolated_home().await;
+    storage::save_subscriptions(&[subscription("1".to_string()), subscription("b".to_string())])
+        .unwrap();
+
+    // A name-first lookup of "1" would pick the subscription at index 0
+    let patch = subscription::SubscriptionPatch {
+        name: None,
+        url: None,
+        enabled: Some(false),
+    };
+    let updated = subscription::update_subscription_at(1, patch).unwrap();
+    assert_eq!(updated.name, "b");
+
+    let removed = subscription::remove_subscription_at(1).unwrap();
+    assert_eq!(removed.name, "b");
+    assert_eq!(storage::load_subscriptions().unwrap()[0].name, "1");
+    assert!(subscription::remove_subscription_at(1).is_err());
+}
@@ -0,0 +1,103 @@
+use hangar_lib::{server, tls, types::TlsConfig};
+use std::path::Path;
+use std::time::Duration;
+
+fn client_trusting(ca_pem: &Path) -> reqwest::Client {
+    let ca = reqwest::Certificate::from_pem(&std::fs::read(ca_pem).unwrap()).unwrap();
+    reqwest::Client::builder()
+        .use_rustls_tls()
+        .add_root_certificate(ca)
+        .tls_built_in_root_certs(false)
+        .build()
+        .unwrap()
+}
+
+async fn start(dir: &Path) -> (u16, tokio::sync::oneshot::Sender<()>) {
+    let files = tls::generate_self_signed(dir, &["localhost".to_string()]).unwrap();
+    let config = TlsConfig {
+        cert_path: files.cert.to_string_lossy().to_string(),
+        key_path: files.key.to_string_lossy().to_string(),
+    };
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let app = axum::Router::new().route("/health", axum::routing::get(|| async { "OK" }));
+
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(server::serve_app(app, listener, Some(config), async move {
+        let _ = rx.await;
+    }));
+
+    (port, tx)
+}
+
+#[tokio::test]
+async fn test_https_with_local_ca() {
+    let dir = tempfile::tempdir().unwrap();
+    let (port, _shutdown) = start(dir.path()).await;
+    let url = format!("https://localhost:{}/health", port);
+
+    let body = client_trusting(&dir.path().join("ca.pem"))
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, "OK");
+
+    // Plain HTTP clients and clients not trusting the CA are refused
+    let untrusted = reqwest::Client::builder()
+        .use_rustls_tls()
+        .tls_built_in_root_certs(false)
+        .build()
+        .unwrap();
+    assert!(untrusted.get(&url).send().await.is_err());
+}
+
+#[tokio::test]
+async fn test_certificate_hot_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let (port, _shutdown) = start(dir.path()).await;
+    let url = format!("https://localhost:{}/health", port);
+
+    // Replace the pair with one issued by a different CA
+    let other = tempfile::tempdir().unwrap();
+    tls::generate_self_signed(other.path(), &["localhost".to_string()]).unwrap();
+    std::fs::copy(other.path().join("key.pem"), dir.path().join("key.pem")).unwrap();
+    std::fs::copy(other.path().join("cert.pem"), dir.path().join("cert.pem")).unwrap();
+
+    let client = client_trusting(&other.path().join("ca.pem"));
+    for _ in 0..50 {
+        if client.get(&url).send().await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server kept serving the old certificate");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_private_keys_are_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    // A key left readable by an older run is tightened when it is rewritten
+    std::fs::write(dir.path().join("key.pem"), "old").unwrap();
+    std::fs::set_permissions(
+        dir.path().join("key.pem"),
+        std::fs::Permissions::from_mode(0o644),
+    )
+    .unwrap();
+
+    tls::generate_self_signed(dir.path(), &["localhost".to_string()]).unwrap();
+    for name in ["ca-key.pem", "key.pem"] {
+        let mode = std::fs::metadata(dir.path().join(name))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600, "{}", name);
+    }
+}
@@ -0,0 +1,173 @@
+use hangar_lib::types::VersionSource;
+use hangar_lib::{proxy, storage, types, version};
+
+mod common;
+use common::isolated_home;
+
+#[tokio::test]
+async fn test_rollback_restores_target_and_remerges() {
+    let _home = isolated_home().await;
+
+    let basic_path = storage::get_basic_config_path().unwrap();
+    let current_path = storage::get_current_config_path().unwrap();
+    let original = "mixed-port: 7890\nrules:\n  - MATCH,DIRECT\n";
+    std::fs::write(&basic_path, original).unwrap();
+    let snapshot =
+        version::save_version("basic", VersionSource::Ai, "use port 7891", original).unwrap();
+
+    std::fs::write(&basic_path, "mixed-port: 7891\nrules:\n  - MATCH,DIRECT\n").unwrap();
+    std::fs::write(&current_path, "stale: true\n").unwrap();
+
+    let outcome = version::rollback_to_version(&format!("v{}", snapshot.number))
+        .await
+        .unwrap();
+    assert_eq!(outcome.path, basic_path);
+    assert!(outcome.remerged);
+
+    // basic.yml is restored and current.yaml regenerated from it
+    assert_eq!(std::fs::read_to_string(&basic_path).unwrap(), original);
+    let current = std::fs::read_to_string(&current_path).unwrap();
+    assert!(current.contains("mixed-port: 7890"));
+    assert!(!current.contains("stale"));
+
+    // The overwritten basic.yml was snapshotted
+    let versions = version::list_versions().unwrap();
+    let latest = versions.iter().find(|v| v.target == "basic").unwrap();
+    assert_eq!(latest.target, "basic");
+    assert_eq!(latest.source, VersionSource::Rollback);
+    assert_eq!(latest.parent.as_deref(), Some(snapshot.id.as_str()));
+    assert!(version::get_version_content(&latest.id)
+        .unwrap()
+        .contains("7891"));
+}
+
+#[tokio::test]
+async fn test_rollback_current_does_not_remerge() {
+    let _home = isolated_home().await;
+
+    let current_path = storage::get_current_config_path().unwrap();
+    std::fs::write(&current_path, "mixed-port: 1\n").unwrap();
+    let snapshot = version::snapshot_current("before edit").unwrap();
+    std::fs::write(&current_path, "mixed-port: 2\n").unwrap();
+
+    let outcome = version::rollback_to_version(&snapshot.id).await.unwrap();
+    assert_eq!(outcome.path, current_path);
+    assert!(!outcome.remerged);
+    assert_eq!(
+        std::fs::read_to_string(&current_path).unwrap(),
+        "mixed-port: 1\n"
+    );
+}
+
+#[tokio::test]
+async fn test_merge_snapshots_only_changed_content() {
+    let _home = isolated_home().await;
+
+    let mut config = types::ClashConfig {
+        base_config: Default::default(),
+        proxies: vec![],
+        proxy_groups: vec![],
+        rules: vec!["MATCH,DIRECT".to_string()],
+        rule_providers: None,
+    };
+    let merges = |versions: &[types::ConfigVersion]| {
+        versions
+            .iter()
+            .filter(|v| v.source == types::VersionSource::Merge)
+            .count()
+    };
+    let before = merges(&version::list_versions().unwrap());
+
+    proxy::write_current_config(&config).unwrap();
+    proxy::write_current_config(&config).unwrap();
+    assert_eq!(merges(&version::list_versions().unwrap()), before + 1);
+
+    config
+        .rules
+        .insert(0, "DOMAIN,example.com,DIRECT".to_string());
+    proxy::write_current_config(&config).unwrap();
+    let versions = version::list_versions().unwrap();
+    assert_eq!(merges(&versions), before + 2);
+    assert_eq!(
+        versions[0].description,
+        "Merge: 0 proxies, 0 groups, 2 rules"
+    );
+}
+
+#[tokio::test]
+async fn test_tags_and_notes() {
+    let _home = isolated_home().await;
+
+    let current_path = storage::get_current_config_path().unwrap();
+    std::fs::write(&current_path, "mixed-port: 1\n").unwrap();
+    let first = version::snapshot_current("first").unwrap();
+    std::fs::write(&current_path, "mixed-port: 2\n").unwrap();
+    let second = version::snapshot_current("second").unwrap();
+
+    version::tag_version(&first.id, "known-good", false).unwrap();
+    assert_eq!(version::resolve_version_id("known-good").unwrap(), first.id);
+
+    // Tags can't shadow aliases, and move only with force
+    assert!(version::tag_version(&first.id, "v9", false).is_err());
+    assert!(version::tag_version(&second.id, "known-good", false).is_err());
+    let moved = version::tag_version(&format!("v{}", second.number), "known-good", true).unwrap();
+    assert_eq!(moved.tags, vec!["known-good"]);
+    assert_eq!(
+        version::resolve_version_id("known-good").unwrap(),
+        second.id
+    );
+
+    let noted = version::set_version_note("known-good", Some("before the trip")).unwrap();
+    assert_eq!(noted.note.as_deref(), Some("before the trip"));
+    assert!(version::set_version_note(&second.id, Some(" "))
+        .unwrap()
+        .note
+        .is_none());
+
+    version::tag_version(&first.id, "trip", false).unwrap();
+    let outcome = version::rollback_to_version("trip").await.unwrap();
+    assert_eq!(outcome.version.id, first.id);
+    assert_eq!(
+        std::fs::read_to_string(&current_path).unwrap(),
+        "mixed-port: 1\n"
+    );
+
+    version::untag_version("trip").unwrap();
+    assert!(version::resolve_version_id("trip").is_err());
+}
+
+#[tokio::test]
+async fn test_merge_prunes_only_merge_snapshots() {
+    let _home = isolated_home().await;
+    storage::modify_hangar_config(|config| {
+        config.history.keep_last = 1;
+        config.history.keep_daily_days = 0;
+        Ok(())
+    })
+    .unwrap();
+    for i in 0..3 {
+        version::save_version(
+            "current",
+            types::VersionSource::Ai,
+            "Before AI patch",
+            &format!("rules: [{}]\n", i),
+        )
+        .unwrap();
+    }
+
+    let mut config = types::ClashConfig {
+        base_config: Default::default(),
+        proxies: vec![],
+        proxy_groups: vec![],
+        rules: vec!["MATCH,DIRECT".to_string()],
+        rule_providers: None,
+    };
+    proxy::write_current_config(&config).unwrap();
+    config.rules.insert(0, "GEOIP,CN,DIRECT".to_string());
+    proxy::write_current_config(&config).unwrap();
+
+    let versions = version::list_versions().unwrap();
+    let count = |source| versions.iter().filter(|v| v.source == source).count();
+    assert_eq!(count(types::VersionSource::Merge), 1);
+    assert_eq!(count(types::VersionSource::Ai), 3);
+}
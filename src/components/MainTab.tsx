@@ -34,10 +34,13 @@ import {
   X,
   File,
   AlertCircle,
+  Tag,
+  StickyNote,
 } from "lucide-react";
 
 interface AiPatchResult {
   description: string;
+  target: string;
   operations: unknown[];
 }
 
@@ -46,6 +49,8 @@ interface ConfigVersion {
   timestamp: number;
   description: string;
   file_path: string;
+  tags?: string[];
+  note?: string;
 }
 
 interface Subscription {
@@ -230,11 +235,56 @@ export default function MainTab() {
     }
   }
 
+  async function handleTagVersion(id: string) {
+    const tag = prompt("Tag this snapshot (e.g. known-good):");
+    if (!tag) return;
+    try {
+      await invoke("tag_version", { id, tag, force: false });
+      loadVersions();
+    } catch (error) {
+      // Only a tag held by another version can be forced; other errors are final
+      if (!String(error).includes("already names")) {
+        setServerMessage(`Tag failed: ${error}`);
+        return;
+      }
+      if (!confirm(`${error}\nMove the tag to this snapshot?`)) return;
+      try {
+        await invoke("tag_version", { id, tag, force: true });
+        loadVersions();
+      } catch (error) {
+        setServerMessage(`Tag failed: ${error}`);
+      }
+    }
+  }
+
+  async function handleUntagVersion(tag: string) {
+    try {
+      await invoke("untag_version", { tag });
+      loadVersions();
+    } catch (error) {
+      setServerMessage(`Untag failed: ${error}`);
+    }
+  }
+
+  async function handleEditNote(v: ConfigVersion) {
+    const note = prompt("Note for this snapshot (empty to clear):", v.note ?? "");
+    if (note === null) return;
+    try {
+      await invoke("set_version_note", { id: v.id, note });
+      loadVersions();
+    } catch (error) {
+      setServerMessage(`Saving note failed: ${error}`);
+    }
+  }
+
   async function handleApplyAiPatch() {
     if (!aiResult) return;
     setIsLoading(true);
     try {
-      const result = await invoke<string>("apply_ai_patch", { operations: aiResult.operations });
+      const result = await invoke<string>("apply_ai_patch", {
+        target: aiResult.target,
+        operations: aiResult.operations,
+      });
       setServerMessage(result);
       setAiResult(null);
       setAiPrompt("");
@@ -695,6 +745,22 @@ export default function MainTab() {
                           </span>
                         </div>
                         <p className="font-bold text-foreground truncate">{v.description || "Untitled Snapshot"}</p>
+                        {v.tags && v.tags.length > 0 && (
+                          <div className="flex flex-wrap gap-1 mt-1">
+                            {v.tags.map((tag) => (
+                              <span
+                                key={tag}
+                                className="inline-flex items-center gap-1 px-2 py-0.5 bg-secondary text-secondary-foreground text-[10px] font-medium rounded"
+                              >
+                                {tag}
+                                <button title="Remove tag" onClick={() => handleUntagVersion(tag)}>
+                                  <X size={10} />
+                                </button>
+                              </span>
+                            ))}
+                          </div>
+                        )}
+                        {v.note && <p className="text-xs text-muted-foreground mt-1 whitespace-pre-wrap">{v.note}</p>}
                         <p className="text-xs text-muted-foreground font-mono mt-1 truncate">{v.file_path}</p>
                       </div>
                       <div className="flex items-center gap-2 shrink-0">
@@ -710,6 +776,24 @@ export default function MainTab() {
                           <RotateCcw size={14} />
                           Restore
                         </Button>
+                        <Button
+                          size="sm"
+                          variant="ghost"
+                          className="h-8"
+                          title="Add tag"
+                          onClick={() => handleTagVersion(v.id)}
+                        >
+                          <Tag size={14} />
+                        </Button>
+                        <Button
+                          size="sm"
+                          variant="ghost"
+                          className="h-8"
+                          title="Edit note"
+                          onClick={() => handleEditNote(v)}
+                        >
+                          <StickyNote size={14} />
+                        </Button>
                         <Button
                           size="sm"
                           variant="ghost"
@@ -32,6 +32,7 @@ import {
   X,
   AlertCircle,
   Info,
+  Archive,
 } from "lucide-react";
 
 interface Subscription {
@@ -278,6 +279,55 @@ export default function SubscriptionsTab() {
     }
   }
 
+  async function handleExportBackup() {
+    try {
+      const filePath = await save({
+        filters: [{ name: "Hangar Backup", extensions: ["tar.gz", "gz"] }],
+        defaultPath: "hangar-backup.tar.gz",
+      });
+      if (!filePath) return;
+      const passphrase = prompt("Passphrase to encrypt secrets (API key, controller secret). Leave empty to leave them out:");
+      if (passphrase === null) return;
+      const includeHistory = confirm("Include version history?");
+      await invoke("export_backup", { path: filePath, passphrase, includeHistory });
+      showMessage("Backup exported. Subscription URLs contain tokens; keep it private.", "success");
+      setImportExportModal(false);
+    } catch (error) {
+      showMessage(`Backup export failed: ${error}`, "error");
+    }
+  }
+
+  async function handleImportBackup() {
+    try {
+      const filePath = await open({
+        filters: [{ name: "Hangar Backup", extensions: ["tar.gz", "gz"] }],
+        multiple: false,
+      });
+      if (!filePath) return;
+      const passphrase = prompt("Backup passphrase (leave empty if the backup has no encrypted secrets):");
+      if (passphrase === null) return;
+      // Merging is the safe default; replacing needs its own explicit confirmation
+      const merge = confirm("Merge the backup into the local configuration?\n\nOK merges; Cancel lets you choose to replace it instead.");
+      const replace = !merge && confirm("Replace the local configuration with the backup?\n\nLocal subscriptions, rules and settings not in the backup are lost. Cancel aborts the import.");
+      if (!merge && !replace) return;
+      const report = await invoke<{ added: string[]; updated: string[]; conflicts: { item: string }[] }>(
+        "import_backup",
+        { path: filePath, passphrase, replace, preferBackup: false },
+      );
+      await loadSubscriptions();
+      const conflicts = report.conflicts.length
+        ? `; ${report.conflicts.length} conflict(s) kept local: ${report.conflicts.map((c) => c.item).join(", ")}`
+        : "";
+      showMessage(
+        `Backup imported: ${report.added.length} added, ${report.updated.length} updated${conflicts}`,
+        report.conflicts.length ? "error" : "success",
+      );
+      setImportExportModal(false);
+    } catch (error) {
+      showMessage(`Backup import failed: ${error}`, "error");
+    }
+  }
+
   // Notification handlers
   async function markNotificationRead(id: string) {
     await invoke("mark_notification_read", { id });
@@ -678,6 +728,29 @@ export default function SubscriptionsTab() {
                 Import from File
               </Button>
             </div>
+            <div className="p-4 rounded-lg border border-border bg-muted/30 space-y-3">
+              <div className="flex items-center gap-3">
+                <div className="p-2 rounded-lg bg-amber-500/10 text-amber-500">
+                  <Archive size={20} />
+                </div>
+                <div>
+                  <p className="font-bold text-foreground">Full Backup</p>
+                  <p className="text-xs text-muted-foreground">
+                    Subscriptions, rule sources, settings and config files in one archive
+                  </p>
+                </div>
+              </div>
+              <div className="grid grid-cols-2 gap-2">
+                <Button onClick={handleExportBackup} className="gap-2">
+                  <Download size={16} />
+                  Export Backup
+                </Button>
+                <Button onClick={handleImportBackup} variant="outline" className="gap-2">
+                  <Upload size={16} />
+                  Import Backup
+                </Button>
+              </div>
+            </div>
           </DialogBody>
         </DialogContent>
       </Dialog>